@@ -1,12 +1,18 @@
 use crate::concrete::StrVal;
 use crate::environment::Environment;
-use crate::interpreter::{ConcretizedSynth, EvalResult, Evaluable, SynthesisVisitor};
+use crate::grammar::{DefaultGrammar, Grammar};
+use crate::interpreter::{
+    expand_call, ConcretizedSynth, EvalError, EvalResult, Evaluable, SynthesisVisitor,
+};
+use crate::linear::LinearExpr;
+use crate::smt;
 use crate::strlenlat::StrLenLat;
-use crate::syguslang::{Expr, Func};
-use crate::values::{Lattice, MixedValue};
+use crate::syguslang::{Expr, Func, Term};
+use crate::values::{Lattice, MixedValue, Value};
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::rc::Rc;
 
 pub type StrValAbs = MixedValue<StrVal, StrLenLat>;
 
@@ -16,160 +22,178 @@ impl Evaluable<StrValAbs> for Expr<StrValAbs, StrLenLat> {
             Self::Const(v) => Ok(v.clone()),
             Self::Var(x) => env
                 .get(x.clone())
-                .map(|v| v.clone())
-                .ok_or_else(|| "variable not found"),
+                .cloned()
+                .ok_or_else(|| EvalError::UnboundVariable(x.clone())),
             Self::Call(call) => call.eval(env),
-            Self::If(cond, then, otherwise) => unimplemented!(),
-            Self::Hole(abs, _) => Ok(StrValAbs::from_abstract(abs.clone())),
+            Self::If(cond, then, otherwise) => {
+                let cond_evaled = cond.eval(env)?;
+                let then_evaled = then.eval(env)?;
+                let otherwise_evaled = otherwise.eval(env)?;
+                match cond_evaled {
+                    StrValAbs::Conc(StrVal::Bool(b)) => {
+                        Ok(if b { then_evaled } else { otherwise_evaled })
+                    }
+                    _ if then_evaled == otherwise_evaled => Ok(then_evaled),
+                    // an unknown guard may take either branch
+                    _ => match (
+                        StrLenLat::try_from(then_evaled.clone()),
+                        StrLenLat::try_from(otherwise_evaled.clone()),
+                    ) {
+                        (Ok(t), Ok(o)) => Ok(StrValAbs::from_abstract(t.join(&o))),
+                        _ => Err(mismatch(
+                            "if",
+                            &[cond_evaled, then_evaled, otherwise_evaled],
+                        )),
+                    },
+                }
+            }
+            Self::Hole(abs, _, _) => Ok(StrValAbs::from_abstract(abs.clone())),
             _ => unreachable!(),
         }
     }
 }
 
+impl Evaluable<StrValAbs> for Term<StrValAbs, StrLenLat> {
+    fn eval(&self, env: &Environment<StrValAbs>) -> EvalResult<StrValAbs> {
+        let inner: &Expr<StrValAbs, StrLenLat> = self;
+        inner.eval(env)
+    }
+}
+
 impl Evaluable<StrValAbs> for Func<StrValAbs, StrLenLat> {
     fn eval(&self, env: &Environment<StrValAbs>) -> EvalResult<StrValAbs> {
+        let args = self
+            .args()
+            .into_iter()
+            .map(|a| a.eval(env))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // with concrete arguments the abstraction is exact: use the interpreter
+        if args.iter().all(|a| a.is_concrete()) {
+            let mut consts = args.into_iter().map(|a| match a {
+                StrValAbs::Conc(c) => Rc::new(Expr::Const(c)),
+                StrValAbs::Abs(_) => unreachable!(),
+            });
+            let func: Func<StrVal, StrLenLat> = self.map_args(|_| consts.next().unwrap());
+            return func.eval(&Environment::new()).map(StrValAbs::from_concrete);
+        }
+
+        let mut args = args.into_iter();
+        let mut arg = || args.next().unwrap();
         match self {
-            Self::Append(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(StrValAbs::Conc(c1)), Ok(StrValAbs::Conc(c2))) => {
-                        Func::Append::<StrVal, StrLenLat>(Expr::Const(c1), Expr::Const(c2))
-                            .eval(&Environment::new())
-                            .map(|v| StrValAbs::from_concrete(v))
-                    }
-                    (Ok(a1), Ok(a2)) => Self::str_append(a1, a2),
-                    _ => Err("append: invalid argument"),
-                }
-            }
-            Self::Replace(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (Ok(StrValAbs::Conc(c1)), Ok(StrValAbs::Conc(c2)), Ok(StrValAbs::Conc(c3))) => {
-                        Func::Replace::<StrVal, StrLenLat>(
-                            Expr::Const(c1),
-                            Expr::Const(c2),
-                            Expr::Const(c3),
-                        )
-                        .eval(&Environment::new())
-                        .map(|v| StrValAbs::from_concrete(v))
-                    }
-                    (Ok(a1), Ok(a2), Ok(a3)) => Self::str_replace(a1, a2, a3),
-                    _ => Err("replace: invalid argument"),
-                }
-            }
-            Self::Substr(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (Ok(StrValAbs::Conc(c1)), Ok(StrValAbs::Conc(c2)), Ok(StrValAbs::Conc(c3))) => {
-                        Func::Substr::<StrVal, StrLenLat>(
-                            Expr::Const(c1),
-                            Expr::Const(c2),
-                            Expr::Const(c3),
-                        )
-                        .eval(&Environment::new())
-                        .map(|v| StrValAbs::from_concrete(v))
-                    }
-                    (Ok(a1), Ok(a2), Ok(a3)) => Self::str_substr(a1, a2, a3),
-                    _ => Err("substr: invalid argument"),
-                }
-            }
-            Self::Add(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(StrValAbs::Conc(c1)), Ok(StrValAbs::Conc(c2))) => {
-                        Func::Add::<StrVal, StrLenLat>(Expr::Const(c1), Expr::Const(c2))
-                            .eval(&Environment::new())
-                            .map(|v| StrValAbs::from_concrete(v))
-                    }
-                    (Ok(_), Ok(_)) => Err("invalid types"),
-                    _ => Err("substr: invalid argument"),
-                }
-            }
-            Self::Sub(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(StrValAbs::Conc(c1)), Ok(StrValAbs::Conc(c2))) => {
-                        Func::Sub::<StrVal, StrLenLat>(Expr::Const(c1), Expr::Const(c2))
-                            .eval(&Environment::new())
-                            .map(|v| StrValAbs::from_concrete(v))
-                    }
-                    (Ok(_), Ok(_)) => Err("invalid types"),
-                    _ => Err("substr: invalid argument"),
-                }
+            Self::Append(_, _) => Self::str_append(arg(), arg()),
+            Self::Replace(_, _, _) | Self::ReplaceAll(_, _, _) => {
+                Self::str_replace(self.name(), arg(), arg(), arg())
             }
-            Self::Len(arg) => {
-                let arg_evaled = arg.eval(env);
-                match arg_evaled {
-                    Ok(StrValAbs::Conc(c)) => Func::Len::<StrVal, StrLenLat>(Expr::Const(c))
-                        .eval(&Environment::new())
-                        .map(|v| StrValAbs::from_concrete(v)),
-                    Ok(a) => Self::str_len(a),
-                    _ => Err("substr: invalid argument"),
-                }
-            }
-            Self::At(arg1, arg2) => unimplemented!(),
-            Self::ToStr(arg) => unimplemented!(),
-            Self::ToInt(arg) => unimplemented!(),
-            Self::IndexOf(arg1, arg2, arg3) => unimplemented!(),
-            Self::PrefixOf(arg1, arg2) => unimplemented!(),
-            Self::SuffixOf(arg1, arg2) => unimplemented!(),
-            Self::Contains(arg1, arg2) => unimplemented!(),
+            Self::Substr(_, _, _) => Self::str_substr(arg(), arg(), arg()),
+            Self::Len(_) => Self::str_len(arg()),
+            Self::At(_, _) => Self::str_at(arg(), arg()),
+            Self::ToStr(_) => Ok(StrValAbs::from_abstract(StrLenLat::top())),
+            _ => Err(mismatch(self.name(), &args.collect::<Vec<_>>())),
         }
     }
 }
 
+fn int_arg(v: &StrValAbs) -> Option<LinearExpr> {
+    match v {
+        StrValAbs::Conc(c) => c.as_linear(),
+        StrValAbs::Abs(_) => None,
+    }
+}
+
+// abstract arguments are described by their length, concrete ones by type
+fn mismatch(op: &'static str, args: &[StrValAbs]) -> EvalError {
+    let types: Vec<String> = args
+        .iter()
+        .map(|a| match a {
+            StrValAbs::Conc(c) => c.type_name().to_string(),
+            StrValAbs::Abs(l) => format!("Str of length {}", l),
+        })
+        .collect();
+    EvalError::mismatch(op, &types)
+}
+
 impl Func<StrValAbs, StrLenLat> {
     fn str_append(arg1: StrValAbs, arg2: StrValAbs) -> EvalResult<StrValAbs> {
-        let absarg1 = StrLenLat::try_from(arg1);
-        let absarg2 = StrLenLat::try_from(arg2);
+        let absarg1 = StrLenLat::try_from(arg1.clone());
+        let absarg2 = StrLenLat::try_from(arg2.clone());
         match (absarg1, absarg2) {
-            (Ok(a1), Ok(a2)) => Ok(StrValAbs::from_abstract(a1 + a2)),
-            _ => Err("invalid types"),
+            (Ok(a1), Ok(a2)) => a1
+                .checked_add(a2)
+                .map(StrValAbs::from_abstract)
+                .ok_or(EvalError::Overflow("append")),
+            _ => Err(mismatch("append", &[arg1, arg2])),
         }
     }
 
-    fn str_replace(arg1: StrValAbs, arg2: StrValAbs, arg3: StrValAbs) -> EvalResult<StrValAbs> {
-        let absarg1 = StrLenLat::try_from(arg1);
-        let absarg2 = StrLenLat::try_from(arg2);
-        let absarg3 = StrLenLat::try_from(arg3);
+    fn str_replace(
+        op: &'static str,
+        arg1: StrValAbs,
+        arg2: StrValAbs,
+        arg3: StrValAbs,
+    ) -> EvalResult<StrValAbs> {
+        let absarg1 = StrLenLat::try_from(arg1.clone());
+        let absarg2 = StrLenLat::try_from(arg2.clone());
+        let absarg3 = StrLenLat::try_from(arg3.clone());
         match (absarg1, absarg2, absarg3) {
             (Ok(_), Ok(_), Ok(_)) => Ok(StrValAbs::from_abstract(StrLenLat::top())),
-            _ => Err("invalid types"),
+            _ => Err(mismatch(op, &[arg1, arg2, arg3])),
         }
     }
 
+    // (substr s i n) has length n when i + n fits in s, which is only decided
+    // here when the slack len(s) - i - n is a known constant
     fn str_substr(arg1: StrValAbs, arg2: StrValAbs, arg3: StrValAbs) -> EvalResult<StrValAbs> {
-        let absarg1 = StrLenLat::try_from(arg1);
-        match (absarg1, arg2, arg3) {
-            (Ok(s), StrValAbs::Conc(StrVal::Int(start)), StrValAbs::Conc(StrVal::Int(end))) => {
-                match s {
-                    StrLenLat::Top => Ok(StrValAbs::from_abstract(StrLenLat::top())),
-                    StrLenLat::Len(l) => {
-                        if l >= start && end <= l && start <= end {
-                            Ok(StrValAbs::from_abstract(StrLenLat::from(end - start)))
-                        } else {
-                            Err("substring index mismatch")
+        let absarg1 = StrLenLat::try_from(arg1.clone());
+        match (absarg1, int_arg(&arg2), int_arg(&arg3)) {
+            (Ok(s), Some(start), Some(len)) => match s {
+                StrLenLat::Len(l) => {
+                    let slack = l
+                        .checked_sub(start.clone())
+                        .and_then(|d| d.checked_sub(len.clone()))
+                        .ok_or(EvalError::Overflow("substr"))?
+                        .as_const();
+                    match (start.as_const(), slack) {
+                        (Some(i), Some(d)) if i >= 0 && d >= 0 => {
+                            let n = len.as_const().map_or(len.clone(), |n| n.max(0).into());
+                            Ok(StrValAbs::from_abstract(StrLenLat::from(n)))
                         }
+                        _ => Ok(StrValAbs::from_abstract(StrLenLat::top())),
                     }
-                    StrLenLat::Bot => Ok(StrValAbs::from_abstract(StrLenLat::bot())),
                 }
-            }
-            _ => Err("invalid types"),
+                StrLenLat::Top => Ok(StrValAbs::from_abstract(StrLenLat::top())),
+                StrLenLat::Bot => Ok(StrValAbs::from_abstract(StrLenLat::bot())),
+            },
+            _ => Err(mismatch("substr", &[arg1, arg2, arg3])),
         }
     }
 
     fn str_len(arg: StrValAbs) -> EvalResult<StrValAbs> {
-        let absarg = StrLenLat::try_from(arg);
+        let absarg = StrLenLat::try_from(arg.clone());
         match absarg {
             Ok(StrLenLat::Len(l)) => Ok(StrValAbs::from_concrete(StrVal::from(l))),
-            _ => Err("cannot lift ⊤/⊥ to concrete int"),
+            _ => Err(mismatch("len", &[arg])),
+        }
+    }
+
+    fn str_at(arg1: StrValAbs, arg2: StrValAbs) -> EvalResult<StrValAbs> {
+        let absarg1 = StrLenLat::try_from(arg1.clone());
+        match (absarg1, int_arg(&arg2)) {
+            (Ok(StrLenLat::Len(l)), Some(i)) => {
+                let slack = l
+                    .checked_sub(i.clone())
+                    .and_then(|d| d.checked_sub(1.into()))
+                    .ok_or(EvalError::Overflow("at"))?
+                    .as_const();
+                match (i.as_const(), slack) {
+                    (Some(i), Some(d)) if i >= 0 && d >= 0 => {
+                        Ok(StrValAbs::from_abstract(StrLenLat::from(1)))
+                    }
+                    _ => Ok(StrValAbs::from_abstract(StrLenLat::top())),
+                }
+            }
+            (Ok(_), Some(_)) => Ok(StrValAbs::from_abstract(StrLenLat::top())),
+            _ => Err(mismatch("at", &[arg1, arg2])),
         }
     }
 }
@@ -178,25 +202,22 @@ impl ConcretizedSynth<StrValAbs, StrLenLat> for Expr<StrValAbs, StrLenLat> {
     fn concretize(
         env: &Environment<StrValAbs>,
         size: u32,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
+        cache: &mut HashMap<u32, Vec<Term<StrValAbs, StrLenLat>>>,
+    ) -> Vec<Term<StrValAbs, StrLenLat>> {
         let cached = cache.get(&size);
 
         match cached {
             Some(res) => (*res).clone(),
             None => {
-                let generated: Vec<Expr<StrValAbs, StrLenLat>> =
-                    Self::concretize_appends(env, size, cache)
+                let generated: Vec<Term<StrValAbs, StrLenLat>> =
+                    Self::concretize_binary(env, size, cache, Func::Append)
                         .into_iter()
-                        .chain(Self::concretize_replaces(env, size, cache).into_iter())
-                        .chain(Self::concretize_substrs(env, size, cache).into_iter())
-                        .chain(Self::concretize_adds(env, size, cache).into_iter())
-                        .chain(Self::concretize_subs(env, size, cache).into_iter())
-                        .chain(Self::concretize_lens(env, size, cache).into_iter())
-                        .filter(|p| match p.eval(env) {
-                            Err(_) => false,
-                            _ => true,
-                        })
+                        .chain(Self::concretize_ternary(env, size, cache, Func::Replace))
+                        .chain(Self::concretize_ternary(env, size, cache, Func::Substr))
+                        .chain(Self::concretize_binary(env, size, cache, Func::Add))
+                        .chain(Self::concretize_binary(env, size, cache, Func::Sub))
+                        .chain(Self::concretize_unary(env, size, cache, Func::Len))
+                        .filter(|p| p.eval(env).is_ok())
                         .collect();
                 cache.insert(size, generated.clone());
                 generated
@@ -205,155 +226,99 @@ impl ConcretizedSynth<StrValAbs, StrLenLat> for Expr<StrValAbs, StrLenLat> {
     }
 }
 
-impl Expr<StrValAbs, StrLenLat> {
-    fn concretize_appends(
-        env: &Environment<StrValAbs>,
-        size: u32,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        (0..size)
-            .permutations(2)
-            .map(|args| {
-                let arg1_vec = Self::concretize(env, args[0], cache);
-                let arg2_vec = Self::concretize(env, args[1], cache);
-
-                arg1_vec
-                    .into_iter()
-                    .cartesian_product(arg2_vec.into_iter())
-                    .map(|(arg1, arg2)| Expr::Call(Box::new(Func::Append(arg1, arg2))))
-            })
-            .flatten()
-            .collect()
-    }
-
-    fn concretize_replaces(
-        env: &Environment<StrValAbs>,
-        size: u32,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        (0..size)
-            .permutations(3)
-            .map(|args| {
-                let arg1_vec = Self::concretize(env, args[0], cache);
-                let arg2_vec = Self::concretize(env, args[1], cache);
-                let arg3_vec = Self::concretize(env, args[2], cache);
-
-                arg1_vec
-                    .into_iter()
-                    .cartesian_product(arg2_vec.into_iter())
-                    .cartesian_product(arg3_vec.into_iter())
-                    .map(|((arg1, arg2), arg3)| {
-                        Expr::Call(Box::new(Func::Replace(arg1, arg2, arg3)))
-                    })
-            })
-            .flatten()
-            .collect()
-    }
-
-    fn concretize_substrs(
-        env: &Environment<StrValAbs>,
-        size: u32,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        (0..size)
-            .permutations(3)
-            .map(|args| {
-                let arg1_vec = Self::concretize(env, args[0], cache);
-                let arg2_vec = Self::concretize(env, args[1], cache);
-                let arg3_vec = Self::concretize(env, args[2], cache);
-
-                arg1_vec
-                    .into_iter()
-                    .cartesian_product(arg2_vec.into_iter())
-                    .cartesian_product(arg3_vec.into_iter())
-                    .map(|((arg1, arg2), arg3)| {
-                        Expr::Call(Box::new(Func::Substr(arg1, arg2, arg3)))
-                    })
-            })
-            .flatten()
-            .collect()
-    }
+type AbsTerm = Term<StrValAbs, StrLenLat>;
 
-    fn concretize_adds(
+impl Expr<StrValAbs, StrLenLat> {
+    fn concretize_unary(
         env: &Environment<StrValAbs>,
         size: u32,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        (0..size)
-            .permutations(2)
-            .map(|args| {
-                let arg1_vec = Self::concretize(env, args[0], cache);
-                let arg2_vec = Self::concretize(env, args[1], cache);
-
-                arg1_vec
-                    .into_iter()
-                    .cartesian_product(arg2_vec.into_iter())
-                    .map(|(arg1, arg2)| Expr::Call(Box::new(Func::Add(arg1, arg2))))
-            })
-            .flatten()
+        cache: &mut HashMap<u32, Vec<AbsTerm>>,
+        func: fn(AbsTerm) -> Func<StrValAbs, StrLenLat>,
+    ) -> Vec<AbsTerm> {
+        if size < 1 {
+            return vec![];
+        }
+        Self::concretize(env, size - 1, cache)
+            .into_iter()
+            .map(|arg| Rc::new(Expr::Call(func(arg))))
             .collect()
     }
 
-    fn concretize_subs(
+    fn concretize_binary(
         env: &Environment<StrValAbs>,
         size: u32,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        (0..size)
-            .permutations(2)
-            .map(|args| {
-                let arg1_vec = Self::concretize(env, args[0], cache);
-                let arg2_vec = Self::concretize(env, args[1], cache);
+        cache: &mut HashMap<u32, Vec<AbsTerm>>,
+        func: fn(AbsTerm, AbsTerm) -> Func<StrValAbs, StrLenLat>,
+    ) -> Vec<AbsTerm> {
+        if size < 2 {
+            return vec![];
+        }
+        let rest = size - 2;
+        (0..=rest)
+            .flat_map(|s1| {
+                let arg1_vec = Self::concretize(env, s1, cache);
+                let arg2_vec = Self::concretize(env, rest - s1, cache);
 
                 arg1_vec
                     .into_iter()
-                    .cartesian_product(arg2_vec.into_iter())
-                    .map(|(arg1, arg2)| Expr::Call(Box::new(Func::Sub(arg1, arg2))))
+                    .cartesian_product(arg2_vec)
+                    .map(|(arg1, arg2)| Rc::new(Expr::Call(func(arg1, arg2))))
+                    .collect::<Vec<_>>()
             })
-            .flatten()
             .collect()
     }
 
-    fn concretize_lens(
+    fn concretize_ternary(
         env: &Environment<StrValAbs>,
         size: u32,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        (0..size)
-            .permutations(2)
-            .map(|args| {
-                let arg1_vec = Self::concretize(env, args[0], cache);
+        cache: &mut HashMap<u32, Vec<AbsTerm>>,
+        func: fn(AbsTerm, AbsTerm, AbsTerm) -> Func<StrValAbs, StrLenLat>,
+    ) -> Vec<AbsTerm> {
+        if size < 3 {
+            return vec![];
+        }
+        let rest = size - 3;
+        (0..=rest)
+            .flat_map(|s1| (0..=(rest - s1)).map(move |s2| (s1, s2, rest - s1 - s2)))
+            .flat_map(|(s1, s2, s3)| {
+                let arg1_vec = Self::concretize(env, s1, cache);
+                let arg2_vec = Self::concretize(env, s2, cache);
+                let arg3_vec = Self::concretize(env, s3, cache);
 
                 arg1_vec
                     .into_iter()
-                    .map(|arg1| Expr::Call(Box::new(Func::Len(arg1))))
+                    .cartesian_product(arg2_vec)
+                    .cartesian_product(arg3_vec)
+                    .map(|((arg1, arg2), arg3)| Rc::new(Expr::Call(func(arg1, arg2, arg3))))
+                    .collect::<Vec<_>>()
             })
-            .flatten()
             .collect()
     }
 }
 
-impl SynthesisVisitor<StrValAbs, StrLenLat> for Expr<StrValAbs, StrLenLat> {
+impl SynthesisVisitor<StrValAbs, StrLenLat> for Term<StrValAbs, StrLenLat> {
     fn visit(
         &self,
         env: &Environment<StrValAbs>,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        // println!("{}", self);
-        match self {
+        grammar: &Grammar<StrValAbs, StrLenLat>,
+        cache: &mut HashMap<u32, Vec<Term<StrValAbs, StrLenLat>>>,
+    ) -> Vec<Term<StrValAbs, StrLenLat>> {
+        let inner: &Expr<StrValAbs, StrLenLat> = self;
+        match inner {
             Expr::If(cond, then, otherwise) => cond
-                .visit(env, cache)
+                .visit(env, grammar, cache)
                 .into_iter()
-                .zip(then.visit(env, cache).into_iter())
-                .zip(otherwise.visit(env, cache).into_iter())
-                .map(|((a1, a2), a3)| Expr::If(Box::new(a1), Box::new(a2), Box::new(a3)))
+                .cartesian_product(then.visit(env, grammar, cache))
+                .cartesian_product(otherwise.visit(env, grammar, cache))
+                .map(|((a1, a2), a3)| Rc::new(Expr::If(a1, a2, a3)))
                 .collect(),
-            Expr::Call(f) => f.visit(env, cache),
-            Expr::Hole(abs, expr) => Self::visit_hole(abs, expr, env, cache),
+            Expr::Call(_) if !self.has_hole() => vec![self.clone()],
+            Expr::Call(f) => f.visit(env, grammar, cache),
+            Expr::Hole(abs, expr, _) => Expr::visit_hole(abs, expr, env, cache),
             Expr::ConcHole(_) => unreachable!(),
             Expr::DepHole => unreachable!(),
-            Expr::Const(c) => vec![Expr::Const(c.clone())],
-            Expr::Var(v) => vec![Expr::Var(v.clone())],
+            Expr::Const(_) => vec![self.clone()],
+            Expr::Var(_) => vec![self.clone()],
         }
     }
 }
@@ -361,10 +326,17 @@ impl SynthesisVisitor<StrValAbs, StrLenLat> for Expr<StrValAbs, StrLenLat> {
 impl Expr<StrValAbs, StrLenLat> {
     fn visit_hole(
         target: &StrLenLat,
-        expr: &Option<Box<Func<StrValAbs, StrLenLat>>>,
+        expr: &Option<Func<StrValAbs, StrLenLat>>,
         env: &Environment<StrValAbs>,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
+        cache: &mut HashMap<u32, Vec<Term<StrValAbs, StrLenLat>>>,
+    ) -> Vec<Term<StrValAbs, StrLenLat>> {
+        let conc_hole = |size| Rc::new(Expr::ConcHole(size));
+        let dep_hole = Rc::new(Expr::DepHole);
+        let partial = |f| Rc::new(Expr::Hole(target.clone(), Some(f), None));
+        // no program can fill a hole whose length is negative on every input
+        if !smt::feasible(target) {
+            return vec![];
+        }
         match expr {
             None => cache
                 .get(&0)
@@ -374,63 +346,50 @@ impl Expr<StrValAbs, StrLenLat> {
                     Ok(v) => v <= StrValAbs::from_abstract(target.clone()),
                     Err(_) => false,
                 })
-                .chain(
-                    Self::gen_call()
-                        .into_iter()
-                        .map(|f| Expr::Hole(target.clone(), Some(Box::new(f)))),
-                )
+                .chain(Self::gen_call().into_iter().map(partial))
                 .collect(),
             // the following match should have all the functions returned by gen_call
-            Some(partial_expr) => match **partial_expr {
-                Func::Append(Expr::ConcHole(size), Expr::DepHole) => {
+            Some(partial_expr) => match partial_expr {
+                Func::Append(arg1, _) => {
+                    let size = arg1.size();
                     Self::concretize(env, size, cache)
                         .into_iter()
                         .filter_map(|conc| {
                             Self::str_append_inv(target, &conc, env).map(|lat| {
-                                Expr::Call(Box::new(Func::Append(conc, Expr::Hole(lat, None))))
+                                Rc::new(Expr::Call(Func::Append(
+                                    conc,
+                                    Rc::new(Expr::Hole(lat, None, None)),
+                                )))
                             })
                         })
-                        .chain(vec![Expr::Hole(
-                            target.clone(),
-                            Some(Box::new(Func::Append(
-                                Expr::ConcHole(size + 1),
-                                Expr::DepHole,
-                            ))),
-                        )])
+                        .chain(vec![partial(Func::Append(conc_hole(size + 1), dep_hole))])
                         .collect()
                 }
-                Func::Substr(Expr::DepHole, Expr::ConcHole(size1), Expr::ConcHole(size2)) => {
-                    // println!("{}", partial_expr);
-                    // println!("here ==> {} {}", size1, size2);
+                Func::Substr(_, arg2, arg3) => {
+                    let (size1, size2) = (arg2.size(), arg3.size());
                     Self::concretize(env, size1, cache)
                         .into_iter()
-                        .cartesian_product(Self::concretize(env, size2, cache).into_iter())
+                        .cartesian_product(Self::concretize(env, size2, cache))
                         .filter_map(|(conc1, conc2)| {
                             Self::str_substr_inv(target, &conc1, &conc2, env).map(|lat| {
-                                Expr::Call(Box::new(Func::Substr(
-                                    Expr::Hole(lat, None),
+                                Rc::new(Expr::Call(Func::Substr(
+                                    Rc::new(Expr::Hole(lat, None, None)),
                                     conc1,
                                     conc2,
                                 )))
                             })
                         })
                         .chain(vec![
-                            Expr::Hole(
-                                target.clone(),
-                                Some(Box::new(Func::Substr(
-                                    Expr::DepHole,
-                                    Expr::ConcHole(size1 + 1),
-                                    Expr::ConcHole(size2),
-                                ))),
-                            ),
-                            Expr::Hole(
-                                target.clone(),
-                                Some(Box::new(Func::Substr(
-                                    Expr::DepHole,
-                                    Expr::ConcHole(size1),
-                                    Expr::ConcHole(size2 + 1),
-                                ))),
-                            ),
+                            partial(Func::Substr(
+                                dep_hole.clone(),
+                                conc_hole(size1 + 1),
+                                conc_hole(size2),
+                            )),
+                            partial(Func::Substr(
+                                dep_hole,
+                                conc_hole(size1),
+                                conc_hole(size2 + 1),
+                            )),
                         ])
                         .collect()
                 }
@@ -439,38 +398,45 @@ impl Expr<StrValAbs, StrLenLat> {
         }
     }
 
+    /// The length the second argument of `append` needs so that the result
+    /// has the target length.
     fn str_append_inv(
         target: &StrLenLat,
-        arg1: &Expr<StrValAbs, StrLenLat>,
+        arg1: &Term<StrValAbs, StrLenLat>,
         env: &Environment<StrValAbs>,
     ) -> Option<StrLenLat> {
-        match arg1.eval(env) {
-            Ok(v) => match v {
-                StrValAbs::Abs(lat) => Some(target.clone() - lat),
-                StrValAbs::Conc(c) => StrLenLat::try_from(c).ok().map(|lat| target.clone() - lat),
-            },
-            _ => None,
-        }
+        let prefix = match arg1.eval(env) {
+            Ok(StrValAbs::Abs(lat)) => lat,
+            Ok(StrValAbs::Conc(c)) => StrLenLat::try_from(c).ok()?,
+            Err(_) => return None,
+        };
+        smt::append_inv(target, &prefix)
     }
 
     fn str_substr_inv(
-        _target: &StrLenLat,
-        arg2: &Expr<StrValAbs, StrLenLat>,
-        arg3: &Expr<StrValAbs, StrLenLat>,
+        target: &StrLenLat,
+        arg2: &Term<StrValAbs, StrLenLat>,
+        arg3: &Term<StrValAbs, StrLenLat>,
         env: &Environment<StrValAbs>,
     ) -> Option<StrLenLat> {
-        match (arg2.eval(env), arg3.eval(env)) {
-            (Ok(StrValAbs::Conc(StrVal::Int(_))), Ok(StrValAbs::Conc(StrVal::Int(_)))) => {
-                Some(StrLenLat::top())
-            }
+        let (start, len) = (arg2.eval(env), arg3.eval(env));
+        match (
+            start.ok().and_then(|v| int_arg(&v)),
+            len.ok().and_then(|v| int_arg(&v)),
+        ) {
+            (Some(start), Some(len)) => smt::substr_inv(target, &start, &len),
             _ => None,
         }
     }
 
     fn gen_call() -> Vec<Func<StrValAbs, StrLenLat>> {
         vec![
-            Func::Append(Expr::ConcHole(0), Expr::DepHole),
-            Func::Substr(Expr::DepHole, Expr::ConcHole(0), Expr::ConcHole(0)),
+            Func::Append(Rc::new(Expr::ConcHole(0)), Rc::new(Expr::DepHole)),
+            Func::Substr(
+                Rc::new(Expr::DepHole),
+                Rc::new(Expr::ConcHole(0)),
+                Rc::new(Expr::ConcHole(0)),
+            ),
         ]
     }
 }
@@ -479,93 +445,49 @@ impl SynthesisVisitor<StrValAbs, StrLenLat> for Func<StrValAbs, StrLenLat> {
     fn visit(
         &self,
         env: &Environment<StrValAbs>,
-        cache: &mut HashMap<u32, Vec<Expr<StrValAbs, StrLenLat>>>,
-    ) -> Vec<Expr<StrValAbs, StrLenLat>> {
-        match self {
-            Func::Append(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Expr::Call(Box::new(Func::Append(a1, a2))))
-                .collect(),
-            Func::Replace(arg1, arg2, arg3) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .cartesian_product(arg3.visit(env, cache).into_iter())
-                .map(|((a1, a2), a3)| Expr::Call(Box::new(Func::Replace(a1, a2, a3))))
-                .collect(),
-            Func::Substr(arg1, arg2, arg3) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .cartesian_product(arg3.visit(env, cache).into_iter())
-                .map(|((a1, a2), a3)| Expr::Call(Box::new(Func::Substr(a1, a2, a3))))
-                .collect(),
-            Func::Add(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Expr::Call(Box::new(Func::Add(a1, a2))))
-                .collect(),
-            Func::Sub(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Expr::Call(Box::new(Func::Sub(a1, a2))))
-                .collect(),
-            Func::Len(arg) => arg
-                .visit(env, cache)
-                .into_iter()
-                .map(|a| Expr::Call(Box::new(Func::Len(a))))
-                .collect(),
-            Func::At(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Expr::Call(Box::new(Func::At(a1, a2))))
-                .collect(),
-            Func::ToStr(arg) => arg
-                .visit(env, cache)
-                .into_iter()
-                .map(|a| Expr::Call(Box::new(Func::ToStr(a))))
-                .collect(),
-            Func::ToInt(arg) => arg
-                .visit(env, cache)
-                .into_iter()
-                .map(|a| Expr::Call(Box::new(Func::ToInt(a))))
-                .collect(),
-            Func::IndexOf(arg1, arg2, arg3) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .cartesian_product(arg3.visit(env, cache).into_iter())
-                .map(|((a1, a2), a3)| Expr::Call(Box::new(Func::IndexOf(a1, a2, a3))))
-                .collect(),
-            Func::PrefixOf(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Expr::Call(Box::new(Func::PrefixOf(a1, a2))))
-                .collect(),
-            Func::SuffixOf(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Expr::Call(Box::new(Func::SuffixOf(a1, a2))))
-                .collect(),
-            Func::Contains(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Expr::Call(Box::new(Func::Contains(a1, a2))))
-                .collect(),
+        grammar: &Grammar<StrValAbs, StrLenLat>,
+        cache: &mut HashMap<u32, Vec<Term<StrValAbs, StrLenLat>>>,
+    ) -> Vec<Term<StrValAbs, StrLenLat>> {
+        let expansions = self
+            .args()
+            .into_iter()
+            .map(|a| a.visit(env, grammar, cache))
+            .collect();
+        expand_call(self, expansions)
+    }
+}
+
+// holes are expanded by inverting the length semantics rather than from a
+// grammar, so there are no productions to list
+impl DefaultGrammar<StrValAbs> for StrLenLat {
+    fn default_grammar(
+        _leaves: &[Term<StrValAbs, StrLenLat>],
+        _env: &Environment<StrValAbs>,
+    ) -> Grammar<StrValAbs, StrLenLat> {
+        Grammar::new()
+    }
+}
+
+impl From<Term<StrValAbs, StrLenLat>> for Expr<StrVal, StrLenLat> {
+    fn from(value: Term<StrValAbs, StrLenLat>) -> Self {
+        let inner: &Expr<StrValAbs, StrLenLat> = &value;
+        match inner {
+            Expr::Const(StrValAbs::Conc(v)) => Expr::Const(v.clone()),
+            Expr::Const(_) => unreachable!(),
+            Expr::Var(x) => Expr::Var(x.clone()),
+            Expr::Call(f) => Expr::Call(f.map_args(|a| Rc::new(Expr::from(a.clone())))),
+            Expr::If(cond, then, otherwise) => Expr::If(
+                Rc::new(Expr::from(cond.clone())),
+                Rc::new(Expr::from(then.clone())),
+                Rc::new(Expr::from(otherwise.clone())),
+            ),
+            _ => unreachable!(),
         }
     }
 }
 
 impl TryFrom<StrValAbs> for StrLenLat {
-    type Error = &'static str;
+    type Error = EvalError;
 
     fn try_from(value: StrValAbs) -> Result<Self, Self::Error> {
         match value {
@@ -576,12 +498,47 @@ impl TryFrom<StrValAbs> for StrLenLat {
 }
 
 impl TryFrom<StrVal> for StrLenLat {
-    type Error = &'static str;
+    type Error = EvalError;
 
     fn try_from(value: StrVal) -> Result<Self, Self::Error> {
         match value {
-            StrVal::Str(s) => Ok(Self::from(s)),
-            _ => Err("cannot lift to lattice"),
+            StrVal::Str(s) => i32::try_from(s.chars().count())
+                .map(Self::from)
+                .map_err(|_| EvalError::Overflow("len")),
+            v => Err(EvalError::mismatch("len", &[v.type_name()])),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow() {
+        let mut env = Environment::new();
+        let len = |l: LinearExpr| StrValAbs::from_abstract(StrLenLat::from(l));
+        env.put("arg0".to_string(), len(LinearExpr::from("x".to_string())));
+        env.put("arg1".to_string(), len(LinearExpr::from(i64::MAX)));
+        let var = |x: &str| Rc::new(Expr::Var(x.to_string()));
+        let int = |i: i64| Rc::new(Expr::Const(StrValAbs::from_concrete(StrVal::from(i))));
+
+        // the slack x - max - max does not fit
+        let substr: Expr<StrValAbs, StrLenLat> =
+            Expr::Call(Func::Substr(var("arg0"), int(i64::MAX), int(i64::MAX)));
+        assert_eq!(substr.eval(&env), Err(EvalError::Overflow("substr")));
+
+        let at: Expr<StrValAbs, StrLenLat> = Expr::Call(Func::At(var("arg0"), int(i64::MIN)));
+        assert_eq!(at.eval(&env), Err(EvalError::Overflow("at")));
+
+        let ab = Rc::new(Expr::Const(StrValAbs::from_concrete(StrVal::from(
+            "ab".to_string(),
+        ))));
+        let append: Expr<StrValAbs, StrLenLat> = Expr::Call(Func::Append(var("arg1"), ab));
+        assert_eq!(append.eval(&env), Err(EvalError::Overflow("append")));
+
+        let fits: Expr<StrValAbs, StrLenLat> =
+            Expr::Call(Func::Substr(var("arg1"), int(0), int(i64::MAX)));
+        assert_eq!(fits.eval(&env), Ok(len(LinearExpr::from(i64::MAX))));
+    }
+}
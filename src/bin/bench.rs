@@ -0,0 +1,222 @@
+use absynthe::cegis::Cegis;
+use absynthe::simplify::simplify;
+use absynthe::sygus::Problem;
+use absynthe::syguslang::Expr;
+use absynthe::synthesizer::{Context, Engine, Outcome, SearchConfig};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: bench [<dir>] [--timeout <seconds>] \
+                     [--engine top-down|bottom-up] [--oe] \
+                     [--csv <file>] [--markdown <file>]";
+
+struct Options {
+    dir: PathBuf,
+    timeout: Duration,
+    engine: Engine,
+    observational_equivalence: bool,
+    csv: PathBuf,
+    markdown: PathBuf,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Option<Options> {
+        let mut opts = Options {
+            dir: PathBuf::from("sygus-strings"),
+            timeout: Duration::from_secs(10),
+            engine: Engine::TopDown,
+            observational_equivalence: false,
+            csv: PathBuf::from("bench.csv"),
+            markdown: PathBuf::from("bench.md"),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--timeout" => opts.timeout = Duration::from_secs(args.next()?.parse().ok()?),
+                "--engine" => {
+                    opts.engine = match args.next()?.as_str() {
+                        "top-down" => Engine::TopDown,
+                        "bottom-up" => Engine::BottomUp,
+                        _ => return None,
+                    }
+                }
+                "--oe" => opts.observational_equivalence = true,
+                "--csv" => opts.csv = PathBuf::from(args.next()?),
+                "--markdown" => opts.markdown = PathBuf::from(args.next()?),
+                dir if !dir.starts_with("--") => opts.dir = PathBuf::from(dir),
+                _ => return None,
+            }
+        }
+        Some(opts)
+    }
+}
+
+/// How one problem went. Sizes are those of the simplified solution, which
+/// is what `absynthe solve` prints.
+struct Row {
+    problem: String,
+    status: &'static str,
+    time: Duration,
+    explored: usize,
+    size: Option<u32>,
+    solution: Option<String>,
+}
+
+fn run(path: &Path, opts: &Options) -> Row {
+    let started = Instant::now();
+    let mut row = Row {
+        problem: path.file_name().unwrap().to_string_lossy().into_owned(),
+        status: "error",
+        time: Duration::default(),
+        explored: 0,
+        size: None,
+        solution: None,
+    };
+    let problem = match Problem::from_file(path) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{}", e);
+            row.time = started.elapsed();
+            return row;
+        }
+    };
+
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(
+        SearchConfig::new()
+            .engine(opts.engine)
+            .observational_equivalence(opts.observational_equivalence)
+            .conditionals(problem.has_conditionals())
+            .timeout(opts.timeout),
+    );
+    match Cegis::synthesize(&mut ctx, problem.target(), &env, &problem) {
+        Ok(result) => {
+            row.explored = result.stats.explored;
+            row.status = match result.outcome {
+                Outcome::Solved(progs) => {
+                    let prog = simplify(&Rc::new(Expr::from(progs[0].clone())));
+                    row.size = Some(prog.size());
+                    row.solution = Some(problem.define_fun(&prog));
+                    "solved"
+                }
+                Outcome::Exhausted { .. } => "exhausted",
+                Outcome::NodeLimit { .. } => "node-limit",
+                Outcome::Timeout | Outcome::Cancelled => "timeout",
+                Outcome::Unsupported(reason) => {
+                    eprintln!("{}: {}", row.problem, reason);
+                    "error"
+                }
+            }
+        }
+        Err(e) => eprintln!("{}: {}", row.problem, e),
+    }
+    row.time = started.elapsed();
+    row
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv(rows: &[Row]) -> String {
+    let mut out = String::from("problem,status,time_s,explored,size,solution\n");
+    for row in rows {
+        let fields = [
+            csv_field(&row.problem),
+            row.status.to_string(),
+            format!("{:.3}", row.time.as_secs_f64()),
+            row.explored.to_string(),
+            row.size.map_or(String::new(), |s| s.to_string()),
+            csv_field(row.solution.as_deref().unwrap_or("")),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn markdown(rows: &[Row], opts: &Options) -> String {
+    let solved = rows.iter().filter(|r| r.status == "solved").count();
+    let mut out = format!(
+        "Solved {} of {} with the {} engine{} and a {}s timeout.\n\n",
+        solved,
+        rows.len(),
+        match opts.engine {
+            Engine::TopDown => "top-down",
+            Engine::BottomUp => "bottom-up",
+        },
+        if opts.observational_equivalence {
+            ", observational equivalence"
+        } else {
+            ""
+        },
+        opts.timeout.as_secs()
+    );
+    out.push_str("| problem | status | time (s) | explored | size |\n");
+    out.push_str("|---|---|---:|---:|---:|\n");
+    for row in rows {
+        out.push_str(&format!(
+            "| {} | {} | {:.3} | {} | {} |\n",
+            row.problem,
+            row.status,
+            row.time.as_secs_f64(),
+            row.explored,
+            row.size.map_or("-".to_string(), |s| s.to_string())
+        ));
+    }
+    out
+}
+
+fn bench(opts: &Options) -> Result<(), String> {
+    let entries = fs::read_dir(&opts.dir).map_err(|e| format!("{}: {}", opts.dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "sl"))
+        .collect();
+    paths.sort();
+
+    let mut rows = vec![];
+    for path in &paths {
+        let row = run(path, opts);
+        println!(
+            "{:<20} {:<10} {:>8.3}s {:>10}",
+            row.problem,
+            row.status,
+            row.time.as_secs_f64(),
+            row.explored
+        );
+        rows.push(row);
+    }
+
+    let write = |path: &Path, contents: String| {
+        fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    write(&opts.csv, csv(&rows))?;
+    write(&opts.markdown, markdown(&rows, opts))
+}
+
+// exit codes: 2 on usage errors or when the suite or a report cannot be
+// read or written; unsolved problems are results, not failures
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let opts = match Options::parse(&args) {
+        Some(opts) => opts,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = bench(&opts) {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
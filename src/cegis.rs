@@ -0,0 +1,222 @@
+use crate::concrete::StrVal;
+use crate::environment::Environment;
+use crate::interpreter::{Evaluable, SynthesisVisitor};
+use crate::smt::{self, Session};
+use crate::spec::{CandidateFn, Spec};
+use crate::sygus::{sort_name, ParseResult, Problem, SExpr};
+use crate::syguslang::{Expr, Term};
+use crate::synthesizer::{Context, Outcome, SearchStats, SynthResult, Synthesizer};
+use crate::values::{Lattice, Value};
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// Counterexample-guided synthesis for problems whose constraints are not
+/// all input/output examples. Each round searches for a program meeting the
+/// constraints at the points (values of the declared variables) found so
+/// far, then asks Z3 for a point where it fails them; the loop ends when
+/// there is none.
+pub struct Cegis<T: Value, U: Lattice> {
+    _domain: PhantomData<(T, U)>,
+}
+
+impl<T, U> Cegis<T, U>
+where
+    T: Value + Eq + Debug + 'static,
+    U: Lattice + Eq + TryFrom<T> + 'static,
+    Term<T, U>: SynthesisVisitor<T, U> + Evaluable<T>,
+    Expr<StrVal, U>: From<Term<T, U>>,
+{
+    /// Fails if Z3 cannot decide a candidate or disagrees with the
+    /// interpreter about one. The deadline and cancellation flag of the
+    /// context's configuration are checked between rounds, and each query
+    /// to Z3 is given the time left before the deadline; a query that runs
+    /// out of it ends the search as `Outcome::Timeout`.
+    pub fn synthesize(
+        ctx: &mut Context<T, U>,
+        target: U,
+        env: &Environment<T>,
+        problem: &Problem,
+    ) -> ParseResult<SynthResult<T, U>> {
+        // examples alone need no verifier, and keep the search able to use
+        // observational equivalence and case splits
+        if problem.constraints.iter().all(|c| problem.is_example(c)) {
+            let spec = problem.examples().into();
+            return Ok(Synthesizer::synthesize(ctx, target, env, spec));
+        }
+
+        let mut points: Vec<Vec<StrVal>> = vec![];
+        let mut stats = SearchStats::default();
+        loop {
+            if let Some(outcome) = ctx.config().halted() {
+                return Ok(SynthResult { outcome, stats });
+            }
+            let spec = Self::spec(problem, &points);
+            let result = Synthesizer::synthesize(ctx, target.clone(), env, spec);
+            stats += result.stats;
+            let prog = match result.outcome {
+                Outcome::Solved(progs) => progs[0].clone(),
+                outcome => return Ok(SynthResult { outcome, stats }),
+            };
+            let candidate = Expr::from(prog.clone());
+            match counterexample(problem, &candidate, ctx.config().remaining())? {
+                Check::Verified => {
+                    return Ok(SynthResult {
+                        outcome: Outcome::Solved(vec![prog]),
+                        stats,
+                    })
+                }
+                Check::Timeout => {
+                    return Ok(SynthResult {
+                        outcome: Outcome::Timeout,
+                        stats,
+                    })
+                }
+                Check::Counterexample(point) if points.contains(&point) => {
+                    return Err(format!(
+                        "z3 and the interpreter disagree on {} at {:?}",
+                        problem.define_fun(&candidate),
+                        point
+                    ))
+                }
+                Check::Counterexample(point) => points.push(point),
+            }
+        }
+    }
+
+    fn spec(problem: &Problem, points: &[Vec<StrVal>]) -> Spec {
+        // constraints without variables do not depend on the point, and the
+        // others hold vacuously until there is one
+        let (ground, open): (Vec<_>, Vec<_>) = problem
+            .constraints
+            .iter()
+            .cloned()
+            .partition(|c| !mentions(c, problem));
+        let problem = problem.clone();
+        let envs: Vec<_> = points.iter().map(|p| bind_vars(&problem, p)).collect();
+        Spec::Test(Box::new(move |f: CandidateFn| {
+            let holds = |c: &SExpr, env: &Environment<StrVal>| {
+                problem.eval_constraint(c, env, &*f) == Ok(StrVal::Bool(true))
+            };
+            ground.iter().all(|c| holds(c, &Environment::new()))
+                && envs.iter().all(|env| open.iter().all(|c| holds(c, env)))
+        }))
+    }
+}
+
+fn mentions(constraint: &SExpr, problem: &Problem) -> bool {
+    match constraint {
+        SExpr::Atom(a) => problem.vars.iter().any(|(x, _)| x == a),
+        SExpr::List(items) => items.iter().any(|c| mentions(c, problem)),
+        _ => false,
+    }
+}
+
+fn bind_vars(problem: &Problem, point: &[StrVal]) -> Environment<StrVal> {
+    let mut env = Environment::new();
+    problem
+        .vars
+        .iter()
+        .zip(point)
+        .for_each(|((x, _), v)| env.put(x.clone(), v.clone()));
+    env
+}
+
+fn command(items: Vec<SExpr>) -> SExpr {
+    SExpr::List(items)
+}
+
+fn atom(a: &str) -> SExpr {
+    SExpr::Atom(a.to_string())
+}
+
+/// What Z3 makes of a candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    /// The candidate meets every constraint.
+    Verified,
+    /// Values of the declared variables, in order, at which the candidate
+    /// breaks a constraint.
+    Counterexample(Vec<StrVal>),
+    /// Z3 ran out of time before deciding.
+    Timeout,
+}
+
+/// Checks `candidate` against the constraints, giving Z3 at most `timeout`
+/// to decide.
+pub fn counterexample<U: Lattice>(
+    problem: &Problem,
+    candidate: &Expr<StrVal, U>,
+    timeout: Option<Duration>,
+) -> ParseResult<Check> {
+    // z3 takes the timeout in milliseconds, where 0 means none
+    let millis = match timeout.map(|t| t.as_millis()) {
+        Some(0) => return Ok(Check::Timeout),
+        Some(ms) => Some(i64::from(u32::try_from(ms).unwrap_or(u32::MAX))),
+        None => None,
+    };
+    // no set-logic: z3 does not know the SyGuS logic names and picks its own
+    let mut script: Vec<_> = millis
+        .map(|ms| command(vec![atom("set-option"), atom(":timeout"), SExpr::Int(ms)]))
+        .into_iter()
+        .collect();
+    script.extend(problem.vars.iter().map(|(x, sort)| {
+        command(vec![
+            atom("declare-fun"),
+            atom(x),
+            command(vec![]),
+            atom(sort_name(sort)),
+        ])
+    }));
+    script.extend(SExpr::parse_all(&problem.define_fun(candidate))?);
+    let all = std::iter::once(atom("and"))
+        .chain(problem.constraints.iter().cloned())
+        .collect();
+    script.push(command(vec![
+        atom("assert"),
+        command(vec![atom("not"), command(all)]),
+    ]));
+    script.push(command(vec![atom("check-sat")]));
+
+    let mut session = Session::new();
+    match session.run(&script)?.as_slice() {
+        [SExpr::Atom(r)] if r == "unsat" => return Ok(Check::Verified),
+        [SExpr::Atom(r)] if r == "sat" => {}
+        [SExpr::Atom(r)] if r == "unknown" && timed_out(&mut session)? => {
+            return Ok(Check::Timeout)
+        }
+        responses => {
+            let responses = responses.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+            return Err(format!("z3: cannot verify: {}", responses.join(" ")));
+        }
+    }
+    if problem.vars.is_empty() {
+        return Ok(Check::Counterexample(vec![]));
+    }
+
+    let vars = problem.vars.iter().map(|(x, _)| atom(x)).collect();
+    let values = session.run(&[command(vec![atom("get-value"), command(vars)])])?;
+    let bindings = values
+        .first()
+        .and_then(|v| v.as_list())
+        .ok_or("z3: malformed model")?;
+    bindings
+        .iter()
+        .map(|b| match b.as_list() {
+            Some([_, SExpr::Str(s)]) => Ok(StrVal::from(smt::unescape(s))),
+            Some([_, v]) => v.as_literal().ok_or(format!("z3: unexpected value {}", v)),
+            _ => Err(format!("z3: malformed binding {}", b)),
+        })
+        .collect::<ParseResult<Vec<_>>>()
+        .map(Check::Counterexample)
+}
+
+// whether z3 gave up on the last check-sat because it ran out of time,
+// rather than because the query is beyond it
+fn timed_out(session: &mut Session) -> ParseResult<bool> {
+    let info = session.run(&[command(vec![atom("get-info"), atom(":reason-unknown")])])?;
+    Ok(info.iter().any(|i| {
+        matches!(i.as_list(), Some([_, SExpr::Str(reason)]) if reason == "timeout" || reason == "canceled")
+    }))
+}
@@ -0,0 +1,1150 @@
+use crate::concrete::StrVal;
+use crate::regex::Regex;
+use crate::syguslang::{Expr, Func};
+use crate::types::{TypeLattice, TypeValues};
+use crate::values::Lattice;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+}
+
+/// Why a program has no counterpart in the target language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmitError {
+    /// An integer known only as a linear expression over string lengths.
+    SymbolicInteger(String),
+    /// A program that still has holes to fill.
+    Incomplete,
+    /// A parameter or result whose type is not String, Int or Bool.
+    Sort(TypeLattice),
+}
+
+impl Display for EmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SymbolicInteger(e) => write!(f, "no code for the symbolic integer {}", e),
+            Self::Incomplete => write!(f, "no code for a program with holes"),
+            Self::Sort(sort) => write!(f, "no type for the sort {}", sort),
+        }
+    }
+}
+
+impl Error for EmitError {}
+
+/// Compiles a program into a standalone function `name`, preceded by the
+/// helpers it calls. The i-th parameter is the one the program refers to as
+/// `argi`; a name that is not a valid identifier, or clashes with a keyword,
+/// a helper or another name, is renamed. String operations keep their
+/// SMT-LIB meaning: positions count code points and out-of-range arguments
+/// give "" or -1. Integers are `i64` in Rust, where overflow panics, and
+/// unbounded in Python and JavaScript, where they are `BigInt`s.
+pub fn emit<U: Lattice>(
+    lang: Language,
+    name: &str,
+    params: &[(String, TypeLattice)],
+    ret: &TypeLattice,
+    body: &Expr<StrVal, U>,
+) -> Result<String, EmitError> {
+    // the function comes first, so it keeps its name when a parameter
+    // sanitizes to the same identifier
+    let mut names = idents(
+        lang,
+        std::iter::once(name).chain(params.iter().map(|(x, _)| &**x)),
+    );
+    let name = names.remove(0);
+    let mut emitter = Emitter {
+        lang,
+        params,
+        names: &names,
+        helpers: BTreeSet::new(),
+    };
+    let body = emitter.owned(body)?;
+    let mut out = String::new();
+    for helper in &emitter.helpers {
+        out.push_str(helper_source(lang, helper));
+        out.push('\n');
+    }
+    let args = params
+        .iter()
+        .zip(&names)
+        .map(|((_, sort), x)| match lang {
+            Language::Rust => Ok(format!("{}: {}", x, rust_type(sort, true)?)),
+            _ => Ok(x.clone()),
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", ");
+    out.push_str(&match lang {
+        Language::Rust => format!(
+            // operands are parenthesized whether or not precedence needs it
+            "#[allow(unused_parens)]\npub fn {}({}) -> {} {{\n    {}\n}}\n",
+            name,
+            args,
+            rust_type(ret, false)?,
+            body
+        ),
+        Language::Python => format!("def {}({}):\n    return {}\n", name, args, body),
+        Language::JavaScript => {
+            format!("function {}({}) {{\n  return {};\n}}\n", name, args, body)
+        }
+    });
+    Ok(out)
+}
+
+struct Emitter<'a> {
+    lang: Language,
+    params: &'a [(String, TypeLattice)],
+    // the identifier each parameter is emitted as
+    names: &'a [String],
+    helpers: BTreeSet<&'static str>,
+}
+
+impl<'a> Emitter<'a> {
+    fn index(var: &str) -> Option<usize> {
+        var.strip_prefix("arg").and_then(|idx| idx.parse().ok())
+    }
+
+    fn param(&self, var: &str) -> Option<&'a (String, TypeLattice)> {
+        Self::index(var).and_then(|idx| self.params.get(idx))
+    }
+
+    fn sort<U: Lattice>(&self, expr: &Expr<StrVal, U>) -> TypeLattice {
+        match expr {
+            Expr::Const(StrVal::Str(_)) => TypeLattice::String,
+            Expr::Const(StrVal::Bool(_)) => TypeLattice::Bool,
+            Expr::Const(_) => TypeLattice::Integer,
+            Expr::Var(x) => self.param(x).map_or(TypeLattice::Top, |(_, s)| s.clone()),
+            Expr::Call(f) => {
+                let typed: Func<TypeValues, TypeLattice> =
+                    f.map_args(|_| Rc::new(Expr::Hole(TypeLattice::Top, None, None)));
+                typed.signature().1
+            }
+            Expr::If(_, then, _) => self.sort(then),
+            _ => unreachable!(),
+        }
+    }
+
+    // a value the function can return; in Rust that means a `String`
+    // rather than a `&str`
+    fn owned<U: Lattice>(&mut self, expr: &Expr<StrVal, U>) -> Result<String, EmitError> {
+        let code = self.expr(expr)?;
+        Ok(match (self.lang, expr) {
+            (Language::Rust, Expr::Var(_)) | (Language::Rust, Expr::Const(StrVal::Str(_)))
+                if self.sort(expr) == TypeLattice::String =>
+            {
+                format!("{}.to_string()", code)
+            }
+            _ => code,
+        })
+    }
+
+    // an argument to a helper; in Rust strings are passed as `&str`
+    fn arg<U: Lattice>(&mut self, expr: &Expr<StrVal, U>) -> Result<String, EmitError> {
+        let code = self.expr(expr)?;
+        Ok(match (self.lang, expr) {
+            (Language::Rust, Expr::Var(_)) | (Language::Rust, Expr::Const(_)) => code,
+            (Language::Rust, _) if self.sort(expr) == TypeLattice::String => {
+                format!("&{}", code)
+            }
+            _ => code,
+        })
+    }
+
+    fn expr<U: Lattice>(&mut self, expr: &Expr<StrVal, U>) -> Result<String, EmitError> {
+        Ok(match expr {
+            Expr::Const(c) => literal(self.lang, c)?,
+            Expr::Var(x) => match Self::index(x).and_then(|idx| self.names.get(idx)) {
+                Some(name) => name.clone(),
+                None => idents(self.lang, std::iter::once(&**x)).remove(0),
+            },
+            Expr::If(cond, then, otherwise) => {
+                let (c, t, o) = (self.expr(cond)?, self.owned(then)?, self.owned(otherwise)?);
+                match self.lang {
+                    Language::Rust => format!("(if {} {{ {} }} else {{ {} }})", c, t, o),
+                    Language::Python => format!("({} if {} else {})", t, c, o),
+                    Language::JavaScript => format!("({} ? {} : {})", c, t, o),
+                }
+            }
+            Expr::Call(f) => self.call(f)?,
+            Expr::Hole(_, _, _) | Expr::ConcHole(_) | Expr::DepHole => {
+                return Err(EmitError::Incomplete)
+            }
+        })
+    }
+
+    fn call<U: Lattice>(&mut self, func: &Func<StrVal, U>) -> Result<String, EmitError> {
+        let args = func
+            .args()
+            .into_iter()
+            .map(|a| self.arg(a))
+            .collect::<Result<Vec<_>, _>>()?;
+        let infix = |op: &str| format!("({} {} {})", args[0], op, args[1]);
+        Ok(match (self.lang, func) {
+            (Language::Python, Func::Append(_, _)) | (Language::JavaScript, Func::Append(_, _)) => {
+                infix("+")
+            }
+            (Language::Python, Func::Len(_)) => format!("len({})", args[0]),
+            (Language::Python, Func::StrLt(_, _)) => infix("<"),
+            (Language::Python, Func::StrLe(_, _)) => infix("<="),
+            (Language::JavaScript, Func::StrLt(_, _)) => self.helper("str_cmp", &args, " < 0"),
+            (Language::JavaScript, Func::StrLe(_, _)) => self.helper("str_cmp", &args, " <= 0"),
+            (Language::Rust, Func::Add(_, _)) => self.helper("int_add", &args, ""),
+            (Language::Rust, Func::Sub(_, _)) => self.helper("int_sub", &args, ""),
+            (_, Func::Add(_, _)) => infix("+"),
+            (_, Func::Sub(_, _)) => infix("-"),
+            (_, Func::Lt(_, _)) => infix("<"),
+            (_, Func::Le(_, _)) => infix("<="),
+            (Language::JavaScript, Func::Eq(_, _)) => infix("==="),
+            (_, Func::Eq(_, _)) => infix("=="),
+            (Language::Python, Func::And(_, _)) => infix("and"),
+            (Language::Python, Func::Or(_, _)) => infix("or"),
+            (_, Func::And(_, _)) => infix("&&"),
+            (_, Func::Or(_, _)) => infix("||"),
+            (Language::Python, Func::Not(_)) => format!("(not {})", args[0]),
+            (_, Func::Not(_)) => format!("!{}", args[0]),
+            (_, Func::InRe(_, re)) => {
+                let args = vec![args[0].clone(), regex(self.lang, re, true)];
+                self.helper("str_in_re", &args, "")
+            }
+            (_, f) => {
+                let helper = match f {
+                    Func::Append(_, _) => "str_append",
+                    Func::Replace(_, _, _) => "str_replace",
+                    Func::Substr(_, _, _) => "str_substr",
+                    Func::Len(_) => "str_len",
+                    Func::At(_, _) => "str_at",
+                    Func::ToStr(_) => "int_to_str",
+                    Func::ToInt(_) => "str_to_int",
+                    Func::IndexOf(_, _, _) => "str_indexof",
+                    Func::PrefixOf(_, _) => "str_prefixof",
+                    Func::SuffixOf(_, _) => "str_suffixof",
+                    Func::Contains(_, _) => "str_contains",
+                    Func::ReplaceAll(_, _, _) => "str_replace_all",
+                    Func::StrLt(_, _) => "str_lt",
+                    Func::StrLe(_, _) => "str_le",
+                    Func::IsDigit(_) => "str_is_digit",
+                    Func::FromCode(_) => "str_from_code",
+                    Func::ToCode(_) => "str_to_code",
+                    _ => unreachable!(),
+                };
+                self.helper(helper, &args, "")
+            }
+        })
+    }
+
+    fn helper(&mut self, name: &'static str, args: &[String], suffix: &str) -> String {
+        self.helpers.insert(name);
+        let call = format!("{}({})", name, args.join(", "));
+        if suffix.is_empty() {
+            call
+        } else {
+            format!("({}{})", call, suffix)
+        }
+    }
+}
+
+fn rust_type(sort: &TypeLattice, borrowed: bool) -> Result<&'static str, EmitError> {
+    match sort {
+        TypeLattice::String if borrowed => Ok("&str"),
+        TypeLattice::String => Ok("String"),
+        TypeLattice::Integer => Ok("i64"),
+        TypeLattice::Bool => Ok("bool"),
+        _ => Err(EmitError::Sort(sort.clone())),
+    }
+}
+
+// SyGuS symbols may contain characters, or be words, that the target
+// language does not allow in a name. Each name becomes a distinct identifier
+// that is neither a keyword nor a name the generated code calls, which a
+// parameter would otherwise shadow
+fn idents<'a>(lang: Language, names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let keywords = match lang {
+        Language::Rust => RUST_KEYWORDS,
+        Language::Python => PYTHON_KEYWORDS,
+        Language::JavaScript => JS_KEYWORDS,
+    };
+    let mut taken: BTreeSet<String> = keywords
+        .iter()
+        .copied()
+        .chain(reserved(lang))
+        .map(String::from)
+        .collect();
+    names
+        .into_iter()
+        .map(|name| {
+            let mut stem: String = name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            if stem.is_empty() || stem.starts_with(|c: char| c.is_ascii_digit()) {
+                stem.insert(0, '_');
+            }
+            // Rust spells most keywords as raw identifiers; the others, and
+            // keywords in the other languages, get a trailing underscore
+            let first = if !keywords.contains(&&*stem) {
+                stem.clone()
+            } else if lang == Language::Rust && !RUST_NOT_RAW.contains(&&*stem) {
+                format!("r#{}", stem)
+            } else {
+                format!("{}_", stem)
+            };
+            let id = std::iter::once(first)
+                .chain((1..).map(|n| format!("{}_{}", stem, n)))
+                .find(|id| !taken.contains(id))
+                .unwrap();
+            taken.insert(id.clone());
+            id
+        })
+        .collect()
+}
+
+// the names the helpers define, and the builtins the helpers and the body call
+fn reserved(lang: Language) -> Vec<&'static str> {
+    let (helpers, builtins): (_, &[&str]) = match lang {
+        Language::Rust => (RUST_HELPERS, &["Box", "String", "Vec", "std"]),
+        Language::Python => (
+            PYTHON_HELPERS,
+            &["all", "chr", "int", "len", "ord", "range", "set", "str"],
+        ),
+        Language::JavaScript => (JS_HELPERS, &["Array", "BigInt", "Number", "Set", "String"]),
+    };
+    let defined = helpers
+        .iter()
+        .flat_map(|(_, src)| src.lines())
+        .filter_map(|line| {
+            let rest = ["fn ", "enum ", "def ", "function "]
+                .iter()
+                .find_map(|kw| line.strip_prefix(kw))?;
+            rest.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .next()
+        });
+    defined.chain(builtins.iter().copied()).collect()
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "_", "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+// keywords that cannot be raw identifiers
+const RUST_NOT_RAW: &[&str] = &["_", "Self", "crate", "self", "super"];
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+// reserved words, and the globals a parameter should not rebind
+const JS_KEYWORDS: &[&str] = &[
+    "Infinity",
+    "NaN",
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+fn quote(lang: Language, s: &str) -> String {
+    if lang == Language::Rust {
+        return format!("{:?}", s);
+    }
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                out.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn literal(lang: Language, value: &StrVal) -> Result<String, EmitError> {
+    Ok(match (lang, value) {
+        (_, StrVal::Str(s)) => quote(lang, s),
+        (Language::JavaScript, StrVal::Int(i)) if *i < 0 => format!("(-{}n)", i.unsigned_abs()),
+        (Language::JavaScript, StrVal::Int(i)) => format!("{}n", i),
+        (_, StrVal::Int(i)) if *i < 0 => format!("({})", i),
+        (_, StrVal::Int(i)) => i.to_string(),
+        (Language::Python, StrVal::Bool(b)) => if *b { "True" } else { "False" }.to_string(),
+        (_, StrVal::Bool(b)) => b.to_string(),
+        (_, StrVal::Linear(e)) => return Err(EmitError::SymbolicInteger(e.to_string())),
+    })
+}
+
+// the regex as data for the `str_in_re` helper: a `Re` value in Rust, and
+// nested tuples or arrays tagged with the operator elsewhere
+fn regex(lang: Language, re: &Regex, outer: bool) -> String {
+    let sub = |r: &Regex| regex(lang, r, false);
+    let list = |rs: &[Regex]| {
+        let items = rs.iter().map(sub).collect::<Vec<_>>().join(", ");
+        match lang {
+            Language::Rust => format!("vec![{}]", items),
+            _ => format!("[{}]", items),
+        }
+    };
+    let boxed = |r: &Regex| match lang {
+        Language::Rust => format!("Box::new({})", sub(r)),
+        _ => sub(r),
+    };
+    let char_lit = |c: &char| match lang {
+        Language::Rust => format!("{:?}", c),
+        _ => quote(lang, &c.to_string()),
+    };
+    let (tag, args) = match re {
+        Regex::None => ("None", vec![]),
+        Regex::All => ("All", vec![]),
+        Regex::AllChar => ("AllChar", vec![]),
+        Regex::Str(s) => ("Str", vec![quote(lang, s)]),
+        Regex::Range(lo, hi) => ("Range", vec![char_lit(lo), char_lit(hi)]),
+        Regex::Concat(rs) => ("Concat", vec![list(rs)]),
+        Regex::Union(rs) => ("Union", vec![list(rs)]),
+        Regex::Inter(rs) => ("Inter", vec![list(rs)]),
+        Regex::Star(r) => ("Star", vec![boxed(r)]),
+        Regex::Plus(r) => ("Plus", vec![boxed(r)]),
+        Regex::Opt(r) => ("Opt", vec![boxed(r)]),
+        Regex::Comp(r) => ("Comp", vec![boxed(r)]),
+        Regex::Diff(r1, r2) => ("Diff", vec![boxed(r1), boxed(r2)]),
+        Regex::Loop(r, lo, hi) => ("Loop", vec![boxed(r), lo.to_string(), hi.to_string()]),
+    };
+    match lang {
+        Language::Rust => {
+            let value = if args.is_empty() {
+                format!("Re::{}", tag)
+            } else {
+                format!("Re::{}({})", tag, args.join(", "))
+            };
+            if outer {
+                format!("&{}", value)
+            } else {
+                value
+            }
+        }
+        Language::Python => {
+            let items = std::iter::once(format!("\"{}\"", tag))
+                .chain(args)
+                .collect::<Vec<_>>();
+            format!("({},)", items.join(", "))
+        }
+        Language::JavaScript => {
+            let items = std::iter::once(format!("\"{}\"", tag))
+                .chain(args)
+                .collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+    }
+}
+
+fn helper_source(lang: Language, name: &str) -> &'static str {
+    let helpers = match lang {
+        Language::Rust => RUST_HELPERS,
+        Language::Python => PYTHON_HELPERS,
+        Language::JavaScript => JS_HELPERS,
+    };
+    helpers
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, src)| *src)
+        .unwrap_or_else(|| panic!("no {} helper for {:?}", name, lang))
+}
+
+const RUST_HELPERS: &[(&str, &str)] = &[
+    (
+        "int_add",
+        r#"fn int_add(a: i64, b: i64) -> i64 {
+    a.checked_add(b).expect("integer overflow")
+}
+"#,
+    ),
+    (
+        "int_sub",
+        r#"fn int_sub(a: i64, b: i64) -> i64 {
+    a.checked_sub(b).expect("integer overflow")
+}
+"#,
+    ),
+    (
+        "int_to_str",
+        r#"fn int_to_str(n: i64) -> String {
+    if n < 0 {
+        String::new()
+    } else {
+        n.to_string()
+    }
+}
+"#,
+    ),
+    (
+        "str_append",
+        r#"fn str_append(s: &str, t: &str) -> String {
+    format!("{}{}", s, t)
+}
+"#,
+    ),
+    (
+        "str_at",
+        r#"fn str_at(s: &str, i: i64) -> String {
+    if i < 0 {
+        return String::new();
+    }
+    s.chars().nth(i as usize).map(String::from).unwrap_or_default()
+}
+"#,
+    ),
+    (
+        "str_contains",
+        r#"fn str_contains(s: &str, t: &str) -> bool {
+    s.contains(t)
+}
+"#,
+    ),
+    (
+        "str_from_code",
+        r#"fn str_from_code(n: i64) -> String {
+    if !(0..=0x2FFFF).contains(&n) {
+        return String::new();
+    }
+    std::char::from_u32(n as u32).map(String::from).unwrap_or_default()
+}
+"#,
+    ),
+    (
+        "str_in_re",
+        r#"#[allow(dead_code)]
+enum Re {
+    None,
+    All,
+    AllChar,
+    Str(&'static str),
+    Range(char, char),
+    Concat(Vec<Re>),
+    Union(Vec<Re>),
+    Inter(Vec<Re>),
+    Star(Box<Re>),
+    Plus(Box<Re>),
+    Opt(Box<Re>),
+    Comp(Box<Re>),
+    Diff(Box<Re>, Box<Re>),
+    Loop(Box<Re>, u32, u32),
+}
+
+// every position at which a match of `re` starting at `from` can end
+fn re_ends(re: &Re, s: &[char], from: usize) -> std::collections::BTreeSet<usize> {
+    use std::collections::BTreeSet;
+    let single = |end: Option<usize>| end.into_iter().collect::<BTreeSet<usize>>();
+    match re {
+        Re::None => BTreeSet::new(),
+        Re::All => (from..=s.len()).collect(),
+        Re::AllChar => single(Some(from + 1).filter(|e| *e <= s.len())),
+        Re::Str(t) => {
+            let t: Vec<char> = t.chars().collect();
+            single(Some(from + t.len()).filter(|_| s[from..].starts_with(&t)))
+        }
+        Re::Range(lo, hi) => single(s.get(from).filter(|c| (*lo..=*hi).contains(*c)).map(|_| from + 1)),
+        Re::Concat(rs) => rs.iter().fold(single(Some(from)), |starts, r| {
+            starts.into_iter().flat_map(|p| re_ends(r, s, p)).collect()
+        }),
+        Re::Union(rs) => rs.iter().flat_map(|r| re_ends(r, s, from)).collect(),
+        Re::Inter(rs) => rs.iter().fold((from..=s.len()).collect::<BTreeSet<usize>>(), |ends, r| {
+            ends.intersection(&re_ends(r, s, from)).copied().collect()
+        }),
+        Re::Star(r) => re_repeat(r, s, single(Some(from))),
+        Re::Plus(r) => re_repeat(r, s, re_ends(r, s, from)),
+        Re::Opt(r) => re_ends(r, s, from).into_iter().chain(Some(from)).collect(),
+        Re::Comp(r) => {
+            let ends = re_ends(r, s, from);
+            (from..=s.len()).filter(|p| !ends.contains(p)).collect()
+        }
+        Re::Diff(r1, r2) => {
+            let excluded = re_ends(r2, s, from);
+            re_ends(r1, s, from).into_iter().filter(|p| !excluded.contains(p)).collect()
+        }
+        Re::Loop(r, lo, hi) => {
+            let mut ends = BTreeSet::new();
+            let mut current = single(Some(from));
+            for n in 0..=*hi {
+                if n >= *lo {
+                    ends.extend(current.iter().copied());
+                }
+                current = current.into_iter().flat_map(|p| re_ends(r, s, p)).collect();
+                if current.is_empty() {
+                    break;
+                }
+            }
+            ends
+        }
+    }
+}
+
+fn re_repeat(
+    re: &Re,
+    s: &[char],
+    starts: std::collections::BTreeSet<usize>,
+) -> std::collections::BTreeSet<usize> {
+    let mut ends = starts.clone();
+    let mut frontier: Vec<usize> = starts.into_iter().collect();
+    while let Some(p) = frontier.pop() {
+        for e in re_ends(re, s, p) {
+            if ends.insert(e) {
+                frontier.push(e);
+            }
+        }
+    }
+    ends
+}
+
+fn str_in_re(s: &str, re: &Re) -> bool {
+    let s: Vec<char> = s.chars().collect();
+    re_ends(re, &s, 0).contains(&s.len())
+}
+"#,
+    ),
+    (
+        "str_indexof",
+        r#"fn str_indexof(s: &str, t: &str, i: i64) -> i64 {
+    let s: Vec<char> = s.chars().collect();
+    let t: Vec<char> = t.chars().collect();
+    if i < 0 || i as usize > s.len() {
+        return -1;
+    }
+    let i = i as usize;
+    if t.is_empty() {
+        return i as i64;
+    }
+    s[i..]
+        .windows(t.len())
+        .position(|w| w == t.as_slice())
+        .map_or(-1, |k| (i + k) as i64)
+}
+"#,
+    ),
+    (
+        "str_is_digit",
+        r#"fn str_is_digit(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!((chars.next(), chars.next()), (Some('0'..='9'), None))
+}
+"#,
+    ),
+    (
+        "str_le",
+        r#"fn str_le(s: &str, t: &str) -> bool {
+    s <= t
+}
+"#,
+    ),
+    (
+        "str_len",
+        r#"fn str_len(s: &str) -> i64 {
+    s.chars().count() as i64
+}
+"#,
+    ),
+    (
+        "str_lt",
+        r#"fn str_lt(s: &str, t: &str) -> bool {
+    s < t
+}
+"#,
+    ),
+    (
+        "str_prefixof",
+        r#"fn str_prefixof(s: &str, t: &str) -> bool {
+    t.starts_with(s)
+}
+"#,
+    ),
+    (
+        "str_replace",
+        r#"fn str_replace(s: &str, t: &str, u: &str) -> String {
+    s.replacen(t, u, 1)
+}
+"#,
+    ),
+    (
+        "str_replace_all",
+        r#"fn str_replace_all(s: &str, t: &str, u: &str) -> String {
+    if t.is_empty() {
+        s.to_string()
+    } else {
+        s.replace(t, u)
+    }
+}
+"#,
+    ),
+    (
+        "str_substr",
+        r#"fn str_substr(s: &str, i: i64, n: i64) -> String {
+    if i < 0 || n <= 0 {
+        return String::new();
+    }
+    s.chars().skip(i as usize).take(n as usize).collect()
+}
+"#,
+    ),
+    (
+        "str_suffixof",
+        r#"fn str_suffixof(s: &str, t: &str) -> bool {
+    t.ends_with(s)
+}
+"#,
+    ),
+    (
+        "str_to_code",
+        r#"fn str_to_code(s: &str) -> i64 {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c as i64,
+        _ => -1,
+    }
+}
+"#,
+    ),
+    (
+        "str_to_int",
+        r#"fn str_to_int(s: &str) -> i64 {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return -1;
+    }
+    s.parse().expect("integer overflow")
+}
+"#,
+    ),
+];
+
+const PYTHON_HELPERS: &[(&str, &str)] = &[
+    (
+        "int_to_str",
+        r#"def int_to_str(n):
+    return "" if n < 0 else str(n)
+"#,
+    ),
+    (
+        "str_at",
+        r#"def str_at(s, i):
+    return s[i] if 0 <= i < len(s) else ""
+"#,
+    ),
+    (
+        "str_contains",
+        r#"def str_contains(s, t):
+    return t in s
+"#,
+    ),
+    (
+        "str_from_code",
+        r#"def str_from_code(n):
+    # surrogates are left out, as they are by the interpreter
+    if 0 <= n <= 0x2FFFF and not 0xD800 <= n <= 0xDFFF:
+        return chr(n)
+    return ""
+"#,
+    ),
+    (
+        "str_in_re",
+        r#"def re_ends(re, s, start):
+    """Every position at which a match of re starting at start can end."""
+    tag = re[0]
+    if tag == "None":
+        return set()
+    if tag == "All":
+        return set(range(start, len(s) + 1))
+    if tag == "AllChar":
+        return {start + 1} if start < len(s) else set()
+    if tag == "Str":
+        return {start + len(re[1])} if s.startswith(re[1], start) else set()
+    if tag == "Range":
+        return {start + 1} if start < len(s) and re[1] <= s[start] <= re[2] else set()
+    if tag == "Concat":
+        ends = {start}
+        for r in re[1]:
+            ends = {e for p in ends for e in re_ends(r, s, p)}
+        return ends
+    if tag == "Union":
+        return set().union(*(re_ends(r, s, start) for r in re[1]))
+    if tag == "Inter":
+        ends = set(range(start, len(s) + 1))
+        for r in re[1]:
+            ends &= re_ends(r, s, start)
+        return ends
+    if tag == "Star":
+        return re_repeat(re[1], s, {start})
+    if tag == "Plus":
+        return re_repeat(re[1], s, re_ends(re[1], s, start))
+    if tag == "Opt":
+        return re_ends(re[1], s, start) | {start}
+    if tag == "Comp":
+        return set(range(start, len(s) + 1)) - re_ends(re[1], s, start)
+    if tag == "Diff":
+        return re_ends(re[1], s, start) - re_ends(re[2], s, start)
+    ends, current = set(), {start}
+    for n in range(re[3] + 1):
+        if n >= re[2]:
+            ends |= current
+        current = {e for p in current for e in re_ends(re[1], s, p)}
+        if not current:
+            break
+    return ends
+
+
+def re_repeat(re, s, starts):
+    ends, frontier = set(starts), list(starts)
+    while frontier:
+        for e in re_ends(re, s, frontier.pop()):
+            if e not in ends:
+                ends.add(e)
+                frontier.append(e)
+    return ends
+
+
+def str_in_re(s, re):
+    return len(s) in re_ends(re, s, 0)
+"#,
+    ),
+    (
+        "str_indexof",
+        r#"def str_indexof(s, t, i):
+    if i < 0 or i > len(s):
+        return -1
+    return s.find(t, i)
+"#,
+    ),
+    (
+        "str_is_digit",
+        r#"def str_is_digit(s):
+    return len(s) == 1 and "0" <= s <= "9"
+"#,
+    ),
+    (
+        "str_prefixof",
+        r#"def str_prefixof(s, t):
+    return t.startswith(s)
+"#,
+    ),
+    (
+        "str_replace",
+        r#"def str_replace(s, t, u):
+    return s.replace(t, u, 1)
+"#,
+    ),
+    (
+        "str_replace_all",
+        r#"def str_replace_all(s, t, u):
+    return s.replace(t, u) if t else s
+"#,
+    ),
+    (
+        "str_substr",
+        r#"def str_substr(s, i, n):
+    return "" if i < 0 or n <= 0 else s[i:i + n]
+"#,
+    ),
+    (
+        "str_suffixof",
+        r#"def str_suffixof(s, t):
+    return t.endswith(s)
+"#,
+    ),
+    (
+        "str_to_code",
+        r#"def str_to_code(s):
+    return ord(s) if len(s) == 1 else -1
+"#,
+    ),
+    (
+        "str_to_int",
+        r#"def str_to_int(s):
+    if s and all("0" <= c <= "9" for c in s):
+        return int(s)
+    return -1
+"#,
+    ),
+];
+
+// strings are handled as arrays of code points, since JavaScript indexes
+// them by UTF-16 code unit
+const JS_HELPERS: &[(&str, &str)] = &[
+    (
+        "int_to_str",
+        r#"function int_to_str(n) {
+  return n < 0n ? "" : n.toString();
+}
+"#,
+    ),
+    (
+        "str_append",
+        r#"function str_append(s, t) {
+  return s + t;
+}
+"#,
+    ),
+    (
+        "str_at",
+        r#"function str_at(s, i) {
+  const chars = Array.from(s);
+  return i >= 0n && i < BigInt(chars.length) ? chars[Number(i)] : "";
+}
+"#,
+    ),
+    (
+        "str_cmp",
+        r#"function str_cmp(s, t) {
+  const a = Array.from(s, (c) => c.codePointAt(0));
+  const b = Array.from(t, (c) => c.codePointAt(0));
+  for (let k = 0; k < a.length && k < b.length; k++) {
+    if (a[k] !== b[k]) {
+      return a[k] - b[k];
+    }
+  }
+  return a.length - b.length;
+}
+"#,
+    ),
+    (
+        "str_contains",
+        r#"function str_contains(s, t) {
+  return s.includes(t);
+}
+"#,
+    ),
+    (
+        "str_from_code",
+        r#"function str_from_code(n) {
+  // surrogates are left out, as they are by the interpreter
+  if (n < 0n || n > 0x2ffffn || (n >= 0xd800n && n <= 0xdfffn)) {
+    return "";
+  }
+  return String.fromCodePoint(Number(n));
+}
+"#,
+    ),
+    (
+        "str_in_re",
+        r#"// every position at which a match of re starting at start can end
+function re_ends(re, s, start) {
+  const all = () => new Set(Array.from({ length: s.length - start + 1 }, (_, k) => start + k));
+  const after = (ends, r) => new Set([...ends].flatMap((p) => [...re_ends(r, s, p)]));
+  switch (re[0]) {
+    case "None":
+      return new Set();
+    case "All":
+      return all();
+    case "AllChar":
+      return new Set(start < s.length ? [start + 1] : []);
+    case "Str": {
+      const t = Array.from(re[1]);
+      const found = t.every((c, k) => s[start + k] === c);
+      return new Set(found ? [start + t.length] : []);
+    }
+    case "Range": {
+      const c = s[start];
+      const inside = c !== undefined && re[1].codePointAt(0) <= c.codePointAt(0) && c.codePointAt(0) <= re[2].codePointAt(0);
+      return new Set(inside ? [start + 1] : []);
+    }
+    case "Concat":
+      return re[1].reduce(after, new Set([start]));
+    case "Union":
+      return new Set(re[1].flatMap((r) => [...re_ends(r, s, start)]));
+    case "Inter":
+      return re[1].reduce((ends, r) => {
+        const next = re_ends(r, s, start);
+        return new Set([...ends].filter((p) => next.has(p)));
+      }, all());
+    case "Star":
+      return re_repeat(re[1], s, new Set([start]));
+    case "Plus":
+      return re_repeat(re[1], s, re_ends(re[1], s, start));
+    case "Opt":
+      return new Set([...re_ends(re[1], s, start), start]);
+    case "Comp": {
+      const ends = re_ends(re[1], s, start);
+      return new Set([...all()].filter((p) => !ends.has(p)));
+    }
+    case "Diff": {
+      const excluded = re_ends(re[2], s, start);
+      return new Set([...re_ends(re[1], s, start)].filter((p) => !excluded.has(p)));
+    }
+    case "Loop": {
+      const ends = new Set();
+      let current = new Set([start]);
+      for (let n = 0; n <= re[3]; n++) {
+        if (n >= re[2]) {
+          current.forEach((p) => ends.add(p));
+        }
+        current = after(current, re[1]);
+        if (current.size === 0) {
+          break;
+        }
+      }
+      return ends;
+    }
+  }
+}
+
+function re_repeat(re, s, starts) {
+  const ends = new Set(starts);
+  const frontier = [...starts];
+  while (frontier.length > 0) {
+    for (const e of re_ends(re, s, frontier.pop())) {
+      if (!ends.has(e)) {
+        ends.add(e);
+        frontier.push(e);
+      }
+    }
+  }
+  return ends;
+}
+
+function str_in_re(s, re) {
+  const chars = Array.from(s);
+  return re_ends(re, chars, 0).has(chars.length);
+}
+"#,
+    ),
+    (
+        "str_indexof",
+        r#"function str_indexof(s, t, i) {
+  const a = Array.from(s);
+  const b = Array.from(t);
+  if (i < 0n || i > BigInt(a.length)) {
+    return -1n;
+  }
+  for (let k = Number(i); k + b.length <= a.length; k++) {
+    if (b.every((c, j) => a[k + j] === c)) {
+      return BigInt(k);
+    }
+  }
+  return -1n;
+}
+"#,
+    ),
+    (
+        "str_is_digit",
+        r#"function str_is_digit(s) {
+  return s.length === 1 && s >= "0" && s <= "9";
+}
+"#,
+    ),
+    (
+        "str_len",
+        r#"function str_len(s) {
+  return BigInt(Array.from(s).length);
+}
+"#,
+    ),
+    (
+        "str_prefixof",
+        r#"function str_prefixof(s, t) {
+  return t.startsWith(s);
+}
+"#,
+    ),
+    (
+        "str_replace",
+        r#"function str_replace(s, t, u) {
+  // a function as the replacement keeps `$` patterns in u literal
+  return s.replace(t, () => u);
+}
+"#,
+    ),
+    (
+        "str_replace_all",
+        r#"function str_replace_all(s, t, u) {
+  return t === "" ? s : s.split(t).join(u);
+}
+"#,
+    ),
+    (
+        "str_substr",
+        r#"function str_substr(s, i, n) {
+  if (i < 0n || n <= 0n) {
+    return "";
+  }
+  return Array.from(s).slice(Number(i), Number(i + n)).join("");
+}
+"#,
+    ),
+    (
+        "str_suffixof",
+        r#"function str_suffixof(s, t) {
+  return t.endsWith(s);
+}
+"#,
+    ),
+    (
+        "str_to_code",
+        r#"function str_to_code(s) {
+  const chars = Array.from(s);
+  return chars.length === 1 ? BigInt(chars[0].codePointAt(0)) : -1n;
+}
+"#,
+    ),
+    (
+        "str_to_int",
+        r#"function str_to_int(s) {
+  return /^[0-9]+$/.test(s) ? BigInt(s) : -1n;
+}
+"#,
+    ),
+];
@@ -1,16 +1,21 @@
 use crate::environment::Environment;
-use crate::interpreter::{EvalResult, Evaluable};
+use crate::interpreter::{EvalError, EvalResult, Evaluable};
 use crate::linear::LinearExpr;
+use crate::regex::Regex;
 use crate::syguslang::{Expr, Func};
 use crate::values::{Lattice, Value};
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Display;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum StrVal {
     Str(String),
-    Int(LinearExpr),
+    Int(i64),
     Bool(bool),
+    /// A symbolic integer, such as the length of a string only known
+    /// abstractly. Constant expressions are always kept as `Int`.
+    Linear(LinearExpr),
 }
 
 impl Value for StrVal {
@@ -25,6 +30,7 @@ impl Display for StrVal {
             StrVal::Str(s) => write!(f, "\"{}\"", s),
             StrVal::Int(i) => write!(f, "{}", i),
             StrVal::Bool(b) => write!(f, "{}", b),
+            StrVal::Linear(e) => write!(f, "{}", e),
         }
     }
 }
@@ -37,13 +43,41 @@ impl From<String> for StrVal {
 
 impl From<i32> for StrVal {
     fn from(item: i32) -> Self {
-        StrVal::Int(LinearExpr::from(item))
+        StrVal::Int(item as i64)
+    }
+}
+
+impl From<i64> for StrVal {
+    fn from(item: i64) -> Self {
+        StrVal::Int(item)
     }
 }
 
 impl From<LinearExpr> for StrVal {
     fn from(item: LinearExpr) -> Self {
-        StrVal::Int(item)
+        match item.as_const() {
+            Some(i) => StrVal::Int(i),
+            None => StrVal::Linear(item),
+        }
+    }
+}
+
+impl StrVal {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            StrVal::Str(_) => "Str",
+            StrVal::Int(_) | StrVal::Linear(_) => "Int",
+            StrVal::Bool(_) => "Bool",
+        }
+    }
+
+    /// Integers of either representation as a linear expression.
+    pub fn as_linear(&self) -> Option<LinearExpr> {
+        match self {
+            StrVal::Int(i) => Some(LinearExpr::from(*i)),
+            StrVal::Linear(e) => Some(e.clone()),
+            _ => None,
+        }
     }
 }
 
@@ -53,17 +87,11 @@ impl<U: Lattice> Evaluable<StrVal> for Expr<StrVal, U> {
             Self::Const(v) => Ok(v.clone()),
             Self::Var(x) => env
                 .get(x.clone())
-                .map(|v| v.clone())
-                .ok_or_else(|| "variable not found"),
+                .cloned()
+                .ok_or_else(|| EvalError::UnboundVariable(x.clone())),
             Self::Call(call) => call.eval(env),
             Self::If(cond, then, otherwise) => {
-                let cond_evaled = cond.eval(env);
-                let then_evaled = then.eval(env);
-                let otherwise_evaled = otherwise.eval(env);
-                match (cond_evaled, then_evaled, otherwise_evaled) {
-                    (Ok(c), Ok(t), Ok(o)) => Self::eval_if(c, t, o),
-                    _ => Err("if: invalid argument"),
-                }
+                Self::eval_if(cond.eval(env)?, then.eval(env)?, otherwise.eval(env)?)
             }
             _ => unreachable!(),
         }
@@ -74,118 +102,65 @@ impl<U: Lattice> Expr<StrVal, U> {
     fn eval_if(cond: StrVal, then: StrVal, otherwise: StrVal) -> EvalResult<StrVal> {
         match (cond, then, otherwise) {
             (StrVal::Bool(b), t, o) => Ok(if b { t } else { o }),
-            _ => Err("invalid types"),
+            (c, t, o) => Err(mismatch("if", &[c, t, o])),
         }
     }
 }
 
+// symbolic integers have the right type but cannot be computed with
+fn mismatch(op: &'static str, args: &[StrVal]) -> EvalError {
+    if args.iter().any(|a| matches!(a, StrVal::Linear(_))) {
+        return EvalError::NonConstantIndex(op);
+    }
+    let types: Vec<_> = args.iter().map(|a| a.type_name()).collect();
+    EvalError::mismatch(op, &types)
+}
+
 impl<U: Lattice> Evaluable<StrVal> for Func<StrVal, U> {
     fn eval(&self, env: &Environment<StrVal>) -> EvalResult<StrVal> {
         match self {
-            Self::Append(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(a1), Ok(a2)) => Self::str_append(a1, a2),
-                    _ => Err("append: invalid argument"),
-                }
-            }
+            Self::Append(arg1, arg2) => Self::str_append(arg1.eval(env)?, arg2.eval(env)?),
             Self::Replace(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (Ok(a1), Ok(a2), Ok(a3)) => Self::str_replace(a1, a2, a3),
-                    _ => Err("replace: invalid argument"),
-                }
+                Self::str_replace(arg1.eval(env)?, arg2.eval(env)?, arg3.eval(env)?)
             }
             Self::Substr(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (Ok(a1), Ok(a2), Ok(a3)) => Self::str_substr(a1, a2, a3),
-                    _ => Err("substr: invalid argument"),
-                }
-            }
-            Self::Add(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(a1), Ok(a2)) => Self::int_add(a1, a2),
-                    _ => Err("add: invalid argument"),
-                }
-            }
-            Self::Sub(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(a1), Ok(a2)) => Self::int_sub(a1, a2),
-                    _ => Err("sub: invalid argument"),
-                }
+                Self::str_substr(arg1.eval(env)?, arg2.eval(env)?, arg3.eval(env)?)
             }
-            Self::Len(arg) => {
-                let arg_evaled = arg.eval(env);
-                match arg_evaled {
-                    Ok(a) => Self::str_len(a),
-                    _ => Err("len: invalid argument"),
-                }
-            }
-            Self::At(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(a1), Ok(a2)) => Self::str_at(a1, a2),
-                    _ => Err("at: invalid argument"),
-                }
+            Self::Add(arg1, arg2) => Self::int_add(arg1.eval(env)?, arg2.eval(env)?),
+            Self::Sub(arg1, arg2) => Self::int_sub(arg1.eval(env)?, arg2.eval(env)?),
+            Self::Len(arg) => Self::str_len(arg.eval(env)?),
+            Self::At(arg1, arg2) => Self::str_at(arg1.eval(env)?, arg2.eval(env)?),
+            Self::ToStr(arg) => Self::int_to_str(arg.eval(env)?),
+            Self::ToInt(arg) => Self::str_to_int(arg.eval(env)?),
+            Self::IndexOf(arg1, arg2, arg3) => {
+                Self::str_indexof(arg1.eval(env)?, arg2.eval(env)?, arg3.eval(env)?)
             }
-            Self::ToStr(arg) => {
-                let arg_evaled = arg.eval(env);
-                match arg_evaled {
-                    Ok(a) => Self::int_to_str(a),
-                    _ => Err("tostr: invalid argument"),
-                }
+            Self::PrefixOf(arg1, arg2) => Self::str_prefixof(arg1.eval(env)?, arg2.eval(env)?),
+            Self::SuffixOf(arg1, arg2) => Self::str_suffixof(arg1.eval(env)?, arg2.eval(env)?),
+            Self::Contains(arg1, arg2) => Self::str_contains(arg1.eval(env)?, arg2.eval(env)?),
+            Self::ReplaceAll(arg1, arg2, arg3) => {
+                Self::str_replace_all(arg1.eval(env)?, arg2.eval(env)?, arg3.eval(env)?)
             }
-            Self::ToInt(arg) => {
-                let arg_evaled = arg.eval(env);
-                match arg_evaled {
-                    Ok(a) => Self::str_to_int(a),
-                    _ => Err("toint: invalid argument"),
-                }
+            Self::StrLt(arg1, arg2) => Self::str_lt(arg1.eval(env)?, arg2.eval(env)?),
+            Self::StrLe(arg1, arg2) => Self::str_le(arg1.eval(env)?, arg2.eval(env)?),
+            Self::IsDigit(arg) => Self::str_is_digit(arg.eval(env)?),
+            Self::FromCode(arg) => Self::str_from_code(arg.eval(env)?),
+            Self::ToCode(arg) => Self::str_to_code(arg.eval(env)?),
+            Self::InRe(arg, re) => Self::str_in_re(arg.eval(env)?, re),
+            Self::Lt(arg1, arg2) => {
+                Self::int_cmp("<", arg1.eval(env)?, arg2.eval(env)?, |i, j| i < j)
             }
-            Self::IndexOf(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (Ok(a1), Ok(a2), Ok(a3)) => Self::str_indexof(a1, a2, a3),
-                    _ => Err("indexof: invalid argument"),
-                }
+            Self::Le(arg1, arg2) => {
+                Self::int_cmp("<=", arg1.eval(env)?, arg2.eval(env)?, |i, j| i <= j)
             }
-            Self::PrefixOf(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(a1), Ok(a2)) => Self::str_prefixof(a1, a2),
-                    _ => Err("at: invalid argument"),
-                }
+            Self::Eq(arg1, arg2) => Self::eq(arg1.eval(env)?, arg2.eval(env)?),
+            Self::And(arg1, arg2) => {
+                Self::bool_op("and", arg1.eval(env)?, arg2.eval(env)?, |a, b| a && b)
             }
-            Self::SuffixOf(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(a1), Ok(a2)) => Self::str_suffixof(a1, a2),
-                    _ => Err("at: invalid argument"),
-                }
-            }
-            Self::Contains(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (Ok(a1), Ok(a2)) => Self::str_contains(a1, a2),
-                    _ => Err("at: invalid argument"),
-                }
+            Self::Or(arg1, arg2) => {
+                Self::bool_op("or", arg1.eval(env)?, arg2.eval(env)?, |a, b| a || b)
             }
+            Self::Not(arg) => Self::not(arg.eval(env)?),
         }
     }
 }
@@ -194,128 +169,272 @@ impl<U: Lattice> Func<StrVal, U> {
     fn str_append(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
         match (v1, v2) {
             (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Str(s1 + &s2)),
-            _ => Err("invalid types"),
+            (v1, v2) => Err(mismatch("append", &[v1, v2])),
         }
     }
 
+    // only the first occurrence is replaced; an empty pattern matches at 0
     fn str_replace(v1: StrVal, v2: StrVal, v3: StrVal) -> EvalResult<StrVal> {
         match (v1, v2, v3) {
             (StrVal::Str(s1), StrVal::Str(s2), StrVal::Str(s3)) => {
-                Ok(StrVal::Str(s1.replace(&s2, &s3)))
+                Ok(StrVal::Str(s1.replacen(&s2, &s3, 1)))
             }
-            _ => Err("invalid types"),
+            (v1, v2, v3) => Err(mismatch("replace", &[v1, v2, v3])),
         }
     }
 
+    // (substr s i n) is "" unless 0 <= i < len(s) and n > 0, and is
+    // truncated at the end of s
     fn str_substr(v1: StrVal, v2: StrVal, v3: StrVal) -> EvalResult<StrVal> {
         match (v1, v2, v3) {
-            (StrVal::Str(s1), StrVal::Int(start), StrVal::Int(end)) => {
-                let start_int = start.as_const();
-                let end_int = end.as_const();
-                match (start_int, end_int) {
-                    (Some(s), Some(e)) => Ok(StrVal::Str(
-                        s1.chars().skip(s as usize).take(e as usize).collect(),
-                    )),
-                    _ => Err("arguments not constant"),
-                }
+            (StrVal::Str(_), StrVal::Int(i), StrVal::Int(n)) if i < 0 || n <= 0 => {
+                Ok(StrVal::Str(String::new()))
             }
-            _ => Err("invalid types"),
+            (StrVal::Str(s1), StrVal::Int(i), StrVal::Int(n)) => Ok(StrVal::Str(
+                s1.chars()
+                    .skip(usize::try_from(i).unwrap_or(usize::MAX))
+                    .take(usize::try_from(n).unwrap_or(usize::MAX))
+                    .collect(),
+            )),
+            (v1, v2, v3) => Err(mismatch("substr", &[v1, v2, v3])),
         }
     }
 
+    // constant operands are added as plain integers, symbolic ones as linear
+    // expressions
     fn int_add(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
         match (v1, v2) {
-            (StrVal::Int(i), StrVal::Int(j)) => Ok(StrVal::Int(i + j)),
-            _ => Err("invalid types"),
+            (StrVal::Int(i), StrVal::Int(j)) => i
+                .checked_add(j)
+                .map(StrVal::Int)
+                .ok_or(EvalError::Overflow("+")),
+            (v1, v2) => match (v1.as_linear(), v2.as_linear()) {
+                (Some(i), Some(j)) => i
+                    .checked_add(j)
+                    .map(StrVal::from)
+                    .ok_or(EvalError::Overflow("+")),
+                _ => Err(mismatch("+", &[v1, v2])),
+            },
         }
     }
 
     fn int_sub(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
         match (v1, v2) {
-            (StrVal::Int(i), StrVal::Int(j)) => Ok(StrVal::Int(i - j)),
-            _ => Err("invalid types"),
+            (StrVal::Int(i), StrVal::Int(j)) => i
+                .checked_sub(j)
+                .map(StrVal::Int)
+                .ok_or(EvalError::Overflow("-")),
+            (v1, v2) => match (v1.as_linear(), v2.as_linear()) {
+                (Some(i), Some(j)) => i
+                    .checked_sub(j)
+                    .map(StrVal::from)
+                    .ok_or(EvalError::Overflow("-")),
+                _ => Err(mismatch("-", &[v1, v2])),
+            },
         }
     }
 
     fn str_len(v: StrVal) -> EvalResult<StrVal> {
         match v {
-            StrVal::Str(s) => Ok(StrVal::Int(LinearExpr::from(s.chars().count() as i32))),
-            _ => Err("invalid types"),
+            StrVal::Str(s) => Ok(StrVal::Int(s.chars().count() as i64)),
+            v => Err(mismatch("len", &[v])),
         }
     }
 
     fn str_at(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
         match (v1, v2) {
-            (StrVal::Str(s), StrVal::Int(i)) => i
-                .as_const()
-                .and_then(|i| s.chars().nth(i as usize))
-                .ok_or_else(|| "invalid index")
-                .map(|c| StrVal::Str(c.to_string())),
-            _ => Err("invalid types"),
+            (StrVal::Str(s), StrVal::Int(i)) => Ok(StrVal::Str(
+                usize::try_from(i)
+                    .ok()
+                    .and_then(|i| s.chars().nth(i))
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+            )),
+            (v1, v2) => Err(mismatch("at", &[v1, v2])),
         }
     }
 
-    fn str_to_int(v: StrVal) -> EvalResult<StrVal> {
+    // negative numbers have no string form and give ""
+    fn int_to_str(v: StrVal) -> EvalResult<StrVal> {
         match v {
-            StrVal::Int(i) => i
-                .as_const()
-                .ok_or_else(|| "expected constant")
-                .map(|s| StrVal::Str(s.to_string())),
-            _ => Err("invalid types"),
+            StrVal::Int(i) if i < 0 => Ok(StrVal::Str(String::new())),
+            StrVal::Int(i) => Ok(StrVal::Str(i.to_string())),
+            v => Err(mismatch("to-str", &[v])),
         }
     }
 
-    fn int_to_str(v: StrVal) -> EvalResult<StrVal> {
+    // anything but a non-empty run of ASCII digits gives -1
+    fn str_to_int(v: StrVal) -> EvalResult<StrVal> {
         match v {
+            StrVal::Str(s) if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) => {
+                Ok(StrVal::from(-1))
+            }
             StrVal::Str(s) => s
-                .parse::<i32>()
-                .map_err(|_| "not a number")
-                .map(|v| StrVal::Int(LinearExpr::from(v))),
-            _ => Err("invalid types"),
+                .parse::<i64>()
+                .map_err(|_| EvalError::Overflow("to-int"))
+                .map(StrVal::Int),
+            v => Err(mismatch("to-int", &[v])),
         }
     }
 
+    // positions count characters, not bytes; a start outside [0, len(s)]
+    // gives -1 and an empty pattern is found at the start
     fn str_indexof(v1: StrVal, v2: StrVal, v3: StrVal) -> EvalResult<StrVal> {
         match (v1, v2, v3) {
             (StrVal::Str(recv), StrVal::Str(pat), StrVal::Int(start)) => {
-                let at = start.as_const();
-                match at {
-                    Some(s) => {
-                        if recv.len() > (s as usize) {
-                            Ok(StrVal::Int(LinearExpr::from(
-                                recv[(s as usize)..]
-                                    .find(&pat)
-                                    .map_or_else(|| -1, |i| s + (i as i32)),
-                            )))
-                        } else {
-                            Err("index overflow")
-                        }
-                    }
-                    None => Err("expected constant index"),
-                }
+                let chars: Vec<char> = recv.chars().collect();
+                let pat: Vec<char> = pat.chars().collect();
+                let found = match usize::try_from(start) {
+                    Ok(start) if start > chars.len() => None,
+                    Ok(start) if pat.is_empty() => Some(start),
+                    Ok(start) => chars[start..]
+                        .windows(pat.len())
+                        .position(|w| w == pat.as_slice())
+                        .map(|i| start + i),
+                    Err(_) => None,
+                };
+                Ok(StrVal::Int(found.map_or(-1, |i| i as i64)))
             }
-            _ => Err("invalid types"),
+            (v1, v2, v3) => Err(mismatch("indexof", &[v1, v2, v3])),
         }
     }
 
+    // (prefixof s t) holds when s is a prefix of t
     fn str_prefixof(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
         match (v1, v2) {
-            (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s1.starts_with(&s2))),
-            _ => Err("invalid types"),
+            (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s2.starts_with(&s1))),
+            (v1, v2) => Err(mismatch("prefixof", &[v1, v2])),
         }
     }
 
     fn str_suffixof(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
         match (v1, v2) {
-            (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s1.ends_with(&s2))),
-            _ => Err("invalid types"),
+            (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s2.ends_with(&s1))),
+            (v1, v2) => Err(mismatch("suffixof", &[v1, v2])),
         }
     }
 
     fn str_contains(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
         match (v1, v2) {
             (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s1.contains(&s2))),
-            _ => Err("invalid types"),
+            (v1, v2) => Err(mismatch("contains", &[v1, v2])),
+        }
+    }
+
+    // an empty pattern leaves the string unchanged
+    fn str_replace_all(v1: StrVal, v2: StrVal, v3: StrVal) -> EvalResult<StrVal> {
+        match (v1, v2, v3) {
+            (StrVal::Str(s1), StrVal::Str(s2), StrVal::Str(_)) if s2.is_empty() => {
+                Ok(StrVal::Str(s1))
+            }
+            (StrVal::Str(s1), StrVal::Str(s2), StrVal::Str(s3)) => {
+                Ok(StrVal::Str(s1.replace(&s2, &s3)))
+            }
+            (v1, v2, v3) => Err(mismatch("replace_all", &[v1, v2, v3])),
+        }
+    }
+
+    // UTF-8 strings order bytewise the same as their code points
+    fn str_lt(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
+        match (v1, v2) {
+            (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s1 < s2)),
+            (v1, v2) => Err(mismatch("str.<", &[v1, v2])),
+        }
+    }
+
+    fn str_le(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
+        match (v1, v2) {
+            (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s1 <= s2)),
+            (v1, v2) => Err(mismatch("str.<=", &[v1, v2])),
+        }
+    }
+
+    fn str_is_digit(v: StrVal) -> EvalResult<StrVal> {
+        match v {
+            StrVal::Str(s) => {
+                let mut chars = s.chars();
+                Ok(StrVal::Bool(matches!(
+                    (chars.next(), chars.next()),
+                    (Some('0'..='9'), None)
+                )))
+            }
+            v => Err(mismatch("is_digit", &[v])),
+        }
+    }
+
+    // surrogate code points are valid in SMT-LIB but cannot be held in a
+    // Rust string, so they give "" like the codes outside the alphabet
+    fn str_from_code(v: StrVal) -> EvalResult<StrVal> {
+        match v {
+            StrVal::Int(i) => Ok(StrVal::Str(
+                u32::try_from(i)
+                    .ok()
+                    .filter(|c| *c <= 0x2FFFF)
+                    .and_then(char::from_u32)
+                    .map(String::from)
+                    .unwrap_or_default(),
+            )),
+            v => Err(mismatch("from_code", &[v])),
+        }
+    }
+
+    fn str_to_code(v: StrVal) -> EvalResult<StrVal> {
+        match v {
+            StrVal::Str(s) => {
+                let mut chars = s.chars();
+                Ok(StrVal::from(match (chars.next(), chars.next()) {
+                    (Some(c), None) => c as i64,
+                    _ => -1,
+                }))
+            }
+            v => Err(mismatch("to_code", &[v])),
+        }
+    }
+
+    fn str_in_re(v: StrVal, re: &Regex) -> EvalResult<StrVal> {
+        match v {
+            StrVal::Str(s) => Ok(StrVal::Bool(re.matches(&s))),
+            v => Err(mismatch("in_re", &[v])),
+        }
+    }
+
+    fn int_cmp(
+        op: &'static str,
+        v1: StrVal,
+        v2: StrVal,
+        cmp: fn(i64, i64) -> bool,
+    ) -> EvalResult<StrVal> {
+        match (v1, v2) {
+            (StrVal::Int(i), StrVal::Int(j)) => Ok(StrVal::Bool(cmp(i, j))),
+            (v1, v2) => Err(mismatch(op, &[v1, v2])),
+        }
+    }
+
+    fn eq(v1: StrVal, v2: StrVal) -> EvalResult<StrVal> {
+        match (v1, v2) {
+            (StrVal::Int(i), StrVal::Int(j)) => Ok(StrVal::Bool(i == j)),
+            (StrVal::Str(s1), StrVal::Str(s2)) => Ok(StrVal::Bool(s1 == s2)),
+            (StrVal::Bool(b1), StrVal::Bool(b2)) => Ok(StrVal::Bool(b1 == b2)),
+            (v1, v2) => Err(mismatch("=", &[v1, v2])),
+        }
+    }
+
+    fn bool_op(
+        op: &'static str,
+        v1: StrVal,
+        v2: StrVal,
+        f: fn(bool, bool) -> bool,
+    ) -> EvalResult<StrVal> {
+        match (v1, v2) {
+            (StrVal::Bool(a), StrVal::Bool(b)) => Ok(StrVal::Bool(f(a, b))),
+            (v1, v2) => Err(mismatch(op, &[v1, v2])),
+        }
+    }
+
+    fn not(v: StrVal) -> EvalResult<StrVal> {
+        match v {
+            StrVal::Bool(b) => Ok(StrVal::Bool(!b)),
+            v => Err(mismatch("not", &[v])),
         }
     }
 }
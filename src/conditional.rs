@@ -0,0 +1,182 @@
+use crate::concrete::StrVal;
+use crate::grammar::Grammar;
+use crate::spec::Examples;
+use crate::syguslang::{Expr, Func, Term};
+use crate::values::{Lattice, Value};
+use std::rc::Rc;
+
+type GuardFn<T, U> = fn(Term<T, U>, Term<T, U>) -> Func<T, U>;
+
+// nested ifs beyond this rarely generalize beyond the examples
+const MAX_DEPTH: usize = 3;
+
+/// Learns programs of the form `(if g1 p1 (if g2 p2 p3))` from closed
+/// candidates that each solve only part of the examples. A candidate is kept
+/// as a branch when it passes examples no earlier branch covers; guards are
+/// the `PrefixOf`, `SuffixOf` and `Contains` productions of the grammar
+/// applied to its leaves.
+pub struct Unifier<T: Value, U: Lattice> {
+    // each term is paired with its verdict on every example
+    guards: Vec<(Term<T, U>, Vec<bool>)>,
+    branches: Vec<(Term<T, U>, Vec<bool>)>,
+    covered: Vec<bool>,
+}
+
+impl<T: Value, U: Lattice> Unifier<T, U>
+where
+    Expr<StrVal, U>: From<Term<T, U>>,
+{
+    pub fn new(examples: &Examples, grammar: &Grammar<T, U>, leaves: &[Term<T, U>]) -> Self {
+        let productions: Vec<&Term<T, U>> = grammar
+            .nonterminals()
+            .iter()
+            .flat_map(|nt| &nt.productions)
+            .collect();
+        let has = |pred: fn(&Func<T, U>) -> bool| {
+            productions
+                .iter()
+                .any(|p| matches!(&***p, Expr::Call(f) if pred(f)))
+        };
+        let mut guard_fns: Vec<GuardFn<T, U>> = vec![];
+        if has(|f| matches!(f, Func::PrefixOf(_, _))) {
+            guard_fns.push(Func::PrefixOf);
+        }
+        if has(|f| matches!(f, Func::SuffixOf(_, _))) {
+            guard_fns.push(Func::SuffixOf);
+        }
+        if has(|f| matches!(f, Func::Contains(_, _))) {
+            guard_fns.push(Func::Contains);
+        }
+
+        let mut guards: Vec<(Term<T, U>, Vec<bool>)> = vec![];
+        for guard_fn in guard_fns {
+            for a in leaves {
+                for b in leaves.iter().filter(|b| *b != a) {
+                    let guard = Rc::new(Expr::Call(guard_fn(a.clone(), b.clone())));
+                    let truth = examples
+                        .outputs(&Expr::from(guard.clone()))
+                        .into_iter()
+                        .map(|r| match r {
+                            Ok(StrVal::Bool(b)) => Some(b),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    // a guard is only useful if it splits the examples
+                    if let Some(truth) = truth.filter(|t| t.contains(&true) && t.contains(&false)) {
+                        if guards.iter().all(|(_, t)| *t != truth) {
+                            guards.push((guard, truth));
+                        }
+                    }
+                }
+            }
+        }
+
+        Unifier {
+            guards,
+            branches: vec![],
+            covered: vec![false; examples.len()],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guards.is_empty()
+    }
+
+    /// Records a closed candidate that failed the examples and returns a
+    /// conditional program passing all of them, once the branches seen so far
+    /// make one possible.
+    pub fn add(&mut self, examples: &Examples, prog: &Term<T, U>) -> Option<Term<T, U>> {
+        let passed = examples.check(&Expr::from(prog.clone()));
+        let subsumed = self
+            .branches
+            .iter()
+            .any(|(_, p)| p.iter().zip(&passed).all(|(old, new)| *old || !*new));
+        if subsumed || !passed.contains(&true) {
+            return None;
+        }
+
+        self.covered
+            .iter_mut()
+            .zip(&passed)
+            .for_each(|(c, p)| *c |= *p);
+        self.branches.push((prog.clone(), passed));
+        if self.covered.iter().all(|c| *c) {
+            let remaining: Vec<usize> = (0..self.covered.len()).collect();
+            self.unify(&remaining, MAX_DEPTH)
+        } else {
+            None
+        }
+    }
+
+    fn branch_for(&self, examples: &[usize]) -> Option<&Term<T, U>> {
+        self.branches
+            .iter()
+            .find(|(_, passed)| examples.iter().all(|&e| passed[e]))
+            .map(|(prog, _)| prog)
+    }
+
+    fn unify(&self, remaining: &[usize], depth: usize) -> Option<Term<T, U>> {
+        if let Some(prog) = self.branch_for(remaining) {
+            return Some(prog.clone());
+        }
+        if depth == 0 {
+            return None;
+        }
+        for (guard, truth) in &self.guards {
+            let (on, off): (Vec<usize>, Vec<usize>) = remaining.iter().partition(|&&e| truth[e]);
+            if on.is_empty() || off.is_empty() {
+                continue;
+            }
+            if let Some(then) = self.branch_for(&on) {
+                if let Some(otherwise) = self.unify(&off, depth - 1) {
+                    return Some(Rc::new(Expr::If(guard.clone(), then.clone(), otherwise)));
+                }
+            }
+            if let Some(otherwise) = self.branch_for(&off) {
+                if let Some(then) = self.unify(&on, depth - 1) {
+                    return Some(Rc::new(Expr::If(guard.clone(), then, otherwise.clone())));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TypeLattice, TypeValues};
+
+    #[test]
+    fn test_unify() {
+        let s = |s: &str| StrVal::from(s.to_string());
+        let mut examples = Examples::new();
+        examples.add(vec![s("Dr. Who")], s("Dr. Who"));
+        examples.add(vec![s("Dr. No")], s("Dr. No"));
+        examples.add(vec![s("House")], s("Dr. House"));
+
+        let arg0: Term<TypeValues, TypeLattice> = Rc::new(Expr::Var("arg0".to_string()));
+        let dr = Rc::new(Expr::Const(TypeValues::from_concrete(s("Dr. "))));
+        let strhole = Rc::new(Expr::Hole(TypeLattice::String, None, None));
+        let mut grammar = Grammar::new();
+        let mut nt_bool = crate::grammar::NonTerminal::new("B".to_string(), TypeLattice::Bool);
+        nt_bool.productions = vec![Rc::new(Expr::Call(Func::PrefixOf(
+            strhole.clone(),
+            strhole,
+        )))];
+        grammar.add(nt_bool);
+
+        let mut unifier = Unifier::new(&examples, &grammar, &[arg0.clone(), dr.clone()]);
+        assert!(!unifier.is_empty());
+        assert_eq!(unifier.add(&examples, &arg0), None);
+        // a branch covering nothing new is not kept
+        assert_eq!(unifier.add(&examples, &arg0), None);
+
+        let prefixed = Rc::new(Expr::Call(Func::Append(dr.clone(), arg0.clone())));
+        let prog = unifier.add(&examples, &prefixed).unwrap();
+        assert_eq!(
+            format!("{}", prog),
+            "(if (prefixof \"Dr. \" arg0) arg0 (append \"Dr. \" arg0))"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::concrete::StrVal;
+use crate::syguslang::{Expr, Func};
+use crate::values::{Lattice, Value};
+use std::collections::HashMap;
+
+/// Decides the order in which the worklist expands partial programs,
+/// cheapest first.
+pub trait CostModel<T: Value, U: Lattice> {
+    fn func_weight(&self, func: &Func<T, U>) -> u32;
+    fn hole_penalty(&self) -> u32;
+    fn const_penalty(&self) -> u32;
+
+    fn cost(&self, expr: &Expr<T, U>) -> u32 {
+        match expr {
+            Expr::Const(_) => self.const_penalty(),
+            Expr::Var(_) => 0,
+            Expr::Call(f) => {
+                self.func_weight(f) + f.args().into_iter().map(|a| self.cost(a)).sum::<u32>()
+            }
+            Expr::If(cond, then, otherwise) => {
+                self.cost(cond) + self.cost(then) + self.cost(otherwise)
+            }
+            Expr::Hole(_, Some(f), _) => self.cost(&Expr::Call(f.clone())),
+            Expr::Hole(_, None, _) | Expr::DepHole => self.hole_penalty(),
+            Expr::ConcHole(size) => *size,
+        }
+    }
+}
+
+/// Per-function weights, looked up by the name the function prints with.
+/// Functions without a weight cost their arity, so by default a term costs
+/// its size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Weights {
+    pub funcs: HashMap<String, u32>,
+    pub hole: u32,
+    pub constant: u32,
+}
+
+impl Weights {
+    pub fn new() -> Weights {
+        Self::default()
+    }
+
+    pub fn func(mut self, name: &str, weight: u32) -> Weights {
+        self.funcs.insert(name.to_string(), weight);
+        self
+    }
+
+    pub fn hole_penalty(mut self, penalty: u32) -> Weights {
+        self.hole = penalty;
+        self
+    }
+
+    pub fn const_penalty(mut self, penalty: u32) -> Weights {
+        self.constant = penalty;
+        self
+    }
+}
+
+impl<T: Value, U: Lattice> CostModel<T, U> for Weights {
+    fn func_weight(&self, func: &Func<T, U>) -> u32 {
+        self.funcs
+            .get(func.name())
+            .copied()
+            .unwrap_or(func.args().len() as u32)
+    }
+
+    fn hole_penalty(&self) -> u32 {
+        self.hole
+    }
+
+    fn const_penalty(&self) -> u32 {
+        self.constant
+    }
+}
+
+// Costs for ranking solutions with `Synthesizer::synthesize_n`. Lower is
+// better; tuples compare lexicographically, so costs can be combined.
+
+fn count<U: Lattice, F: Fn(&Expr<StrVal, U>) -> bool>(expr: &Expr<StrVal, U>, pred: &F) -> usize {
+    let here = pred(expr) as usize;
+    match expr {
+        Expr::Call(f) => here + f.args().into_iter().map(|a| count(a, pred)).sum::<usize>(),
+        Expr::If(cond, then, otherwise) => {
+            here + count(cond, pred) + count(then, pred) + count(otherwise, pred)
+        }
+        _ => here,
+    }
+}
+
+pub fn size<U: Lattice>(expr: &Expr<StrVal, U>) -> u32 {
+    expr.size()
+}
+
+pub fn constants<U: Lattice>(expr: &Expr<StrVal, U>) -> usize {
+    count(expr, &|e| matches!(e, Expr::Const(_)))
+}
+
+pub fn string_literals<U: Lattice>(expr: &Expr<StrVal, U>) -> usize {
+    count(expr, &|e| matches!(e, Expr::Const(StrVal::Str(_))))
+}
+
+/// Prefers programs that rely least on literals copied from the examples,
+/// which tend to overfit them, then smaller ones.
+pub fn generality<U: Lattice>(expr: &Expr<StrVal, U>) -> (usize, usize, u32) {
+    (string_literals(expr), constants(expr), size(expr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syguslang::Func;
+    use crate::types::TypeLattice;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_costs() {
+        let arg0 = Rc::new(Expr::Var("arg0".to_string()));
+        let dot = Rc::new(Expr::Const(StrVal::from(".".to_string())));
+        let prog: Expr<StrVal, TypeLattice> = Expr::Call(Func::Substr(
+            Rc::new(Expr::Call(Func::Append(arg0.clone(), dot))),
+            Rc::new(Expr::Const(StrVal::from(0))),
+            Rc::new(Expr::Call(Func::Len(arg0))),
+        ));
+
+        assert_eq!(size(&prog), 6);
+        assert_eq!(constants(&prog), 2);
+        assert_eq!(string_literals(&prog), 1);
+        assert_eq!(generality(&prog), (1, 2, 6));
+
+        assert_eq!(Weights::new().cost(&prog), size(&prog));
+        let weights = Weights::new()
+            .func("substr", 10)
+            .hole_penalty(5)
+            .const_penalty(1);
+        assert_eq!(weights.cost(&prog), 15);
+        let hole: Expr<StrVal, TypeLattice> = Expr::Call(Func::Len(Rc::new(Expr::Hole(
+            TypeLattice::String,
+            None,
+            None,
+        ))));
+        assert_eq!(weights.cost(&hole), 6);
+    }
+}
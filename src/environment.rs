@@ -1,13 +1,20 @@
-use std::collections::{hash_map, HashMap};
+use std::collections::{btree_map, BTreeMap};
 
+// ordered so that iterating over the variables is reproducible
 pub struct Environment<T> {
-    map: HashMap<String, T>,
+    map: BTreeMap<String, T>,
+}
+
+impl<T> Default for Environment<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T> Environment<T> {
     pub fn new() -> Environment<T> {
         Environment {
-            map: HashMap::new(),
+            map: BTreeMap::new(),
         }
     }
 
@@ -19,11 +26,11 @@ impl<T> Environment<T> {
         self.map.get(&var)
     }
 
-    pub fn keys(&self) -> hash_map::Keys<String, T> {
+    pub fn keys(&self) -> btree_map::Keys<'_, String, T> {
         self.map.keys()
     }
 
-    pub fn iter(&self) -> hash_map::Iter<String, T> {
+    pub fn iter(&self) -> btree_map::Iter<'_, String, T> {
         self.map.iter()
     }
 }
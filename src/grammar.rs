@@ -0,0 +1,140 @@
+use crate::environment::Environment;
+use crate::syguslang::Term;
+use crate::values::{Lattice, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonTerminal<T: Value, U: Lattice> {
+    pub name: String,
+    pub sort: U,
+    pub productions: Vec<Term<T, U>>,
+    pub consts: Vec<Term<T, U>>,
+    /// Nonterminals, by index, that are themselves productions of this one.
+    pub includes: Vec<usize>,
+}
+
+impl<T: Value, U: Lattice> NonTerminal<T, U> {
+    pub fn new(name: String, sort: U) -> NonTerminal<T, U> {
+        NonTerminal {
+            name,
+            sort,
+            productions: vec![],
+            consts: vec![],
+            includes: vec![],
+        }
+    }
+}
+
+/// A hole made from a production refers to its nonterminal by index and is
+/// filled from that nonterminal alone. Holes carrying only an abstract value
+/// look nonterminals up by sort, treating those sharing a sort as one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grammar<T: Value, U: Lattice> {
+    nonterminals: Vec<NonTerminal<T, U>>,
+}
+
+impl<T: Value, U: Lattice> Default for Grammar<T, U> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Value, U: Lattice> Grammar<T, U> {
+    pub fn new() -> Grammar<T, U> {
+        Grammar {
+            nonterminals: vec![],
+        }
+    }
+
+    pub fn add(&mut self, nonterminal: NonTerminal<T, U>) {
+        self.nonterminals.push(nonterminal);
+    }
+
+    pub fn nonterminals(&self) -> &[NonTerminal<T, U>] {
+        &self.nonterminals
+    }
+
+    pub fn productions(&self, sort: &U) -> Vec<Term<T, U>> {
+        Self::dedup(
+            self.nonterminals
+                .iter()
+                .filter(|nt| nt.sort == *sort)
+                .flat_map(|nt| nt.productions.iter()),
+        )
+    }
+
+    pub fn consts(&self, sort: &U) -> Vec<Term<T, U>> {
+        Self::dedup(
+            self.nonterminals
+                .iter()
+                .filter(|nt| nt.sort == *sort)
+                .flat_map(|nt| nt.consts.iter()),
+        )
+    }
+
+    /// The nonterminal a search for a program of the given sort starts
+    /// from: the first one of that sort.
+    pub fn start(&self, sort: &U) -> Option<usize> {
+        self.nonterminals.iter().position(|nt| nt.sort == *sort)
+    }
+
+    /// The nonterminal and those it includes, directly or not, in the order
+    /// they are first reached.
+    pub fn included(&self, nonterminal: usize) -> Vec<usize> {
+        let mut reached = vec![nonterminal];
+        let mut next = 0;
+        while let Some(&nt) = reached.get(next) {
+            for &i in &self.nonterminals[nt].includes {
+                if !reached.contains(&i) {
+                    reached.push(i);
+                }
+            }
+            next += 1;
+        }
+        reached
+    }
+
+    /// Everything a hole made from the given nonterminal may be replaced
+    /// with, including what the nonterminals it includes produce.
+    pub fn expand_nonterminal(&self, nonterminal: usize) -> Vec<Term<T, U>> {
+        let included = self.included(nonterminal);
+        let nts = || included.iter().map(|&i| &self.nonterminals[i]);
+        Self::dedup(nts().flat_map(|nt| nt.productions.iter()))
+            .into_iter()
+            .chain(Self::dedup(nts().flat_map(|nt| nt.consts.iter())))
+            .collect()
+    }
+
+    /// All constants and variables of the grammar, across every nonterminal.
+    pub fn leaves(&self) -> Vec<Term<T, U>> {
+        Self::dedup(self.nonterminals.iter().flat_map(|nt| nt.consts.iter()))
+    }
+
+    /// Everything a hole of the given sort may be replaced with: first the
+    /// productions, then the constants and variables.
+    pub fn expand(&self, sort: &U) -> Vec<Term<T, U>> {
+        self.productions(sort)
+            .into_iter()
+            .chain(self.consts(sort))
+            .collect()
+    }
+
+    fn dedup<'a, I: Iterator<Item = &'a Term<T, U>>>(terms: I) -> Vec<Term<T, U>>
+    where
+        T: 'a,
+        U: 'a,
+    {
+        let mut deduped: Vec<Term<T, U>> = vec![];
+        terms.for_each(|t| {
+            if !deduped.contains(t) {
+                deduped.push(t.clone())
+            }
+        });
+        deduped
+    }
+}
+
+/// The grammar a domain searches when the problem does not come with one,
+/// built around the constants and variables in scope.
+pub trait DefaultGrammar<T: Value>: Lattice {
+    fn default_grammar(leaves: &[Term<T, Self>], env: &Environment<T>) -> Grammar<T, Self>;
+}
@@ -1,10 +1,56 @@
 use crate::environment::Environment;
-use crate::syguslang::Term;
+use crate::grammar::Grammar;
+use crate::syguslang::{Expr, Func, Term};
 use crate::values::{Lattice, Value};
+use itertools::Itertools;
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Debug, Display};
+use std::rc::Rc;
 
-pub type EvalResult<T: Value> = Result<T, &'static str>;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The operator cannot be applied to arguments of these types.
+    TypeMismatch {
+        op: &'static str,
+        args: Vec<String>,
+    },
+    /// An integer result that does not fit in 64 bits.
+    Overflow(&'static str),
+    UnboundVariable(String),
+    /// An index or length that is symbolic rather than a known integer.
+    NonConstantIndex(&'static str),
+    /// An operator the DSL has no counterpart for.
+    UnknownOperator(String),
+}
+
+impl EvalError {
+    pub fn mismatch<A: Display>(op: &'static str, args: &[A]) -> EvalError {
+        EvalError::TypeMismatch {
+            op,
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TypeMismatch { op, args } => {
+                write!(f, "{}: cannot apply to ({})", op, args.join(", "))
+            }
+            Self::Overflow(op) => write!(f, "{}: integer overflow", op),
+            Self::UnboundVariable(x) => write!(f, "unbound variable {}", x),
+            Self::NonConstantIndex(op) => write!(f, "{}: index is not a constant", op),
+            Self::UnknownOperator(op) => write!(f, "unknown operator {}", op),
+        }
+    }
+}
+
+impl Error for EvalError {}
+
+pub type EvalResult<T> = Result<T, EvalError>;
 
 pub trait Evaluable<T: Value + Debug> {
     fn eval(&self, env: &Environment<T>) -> EvalResult<T>;
@@ -22,6 +68,114 @@ pub trait SynthesisVisitor<T: Value, U: Lattice> {
     fn visit(
         &self,
         env: &Environment<T>,
+        grammar: &Grammar<T, U>,
         cache: &mut HashMap<u32, Vec<Term<T, U>>>,
     ) -> Vec<Term<T, U>>;
 }
+
+/// The most calls a single expansion of a call fills all of its arguments
+/// into.
+pub const MAX_EXPANSION: usize = 1 << 16;
+
+/// The calls made by replacing each argument with one of its expansions,
+/// in every combination, leftmost slowest. When there would be more than
+/// `MAX_EXPANSION` of them, only the leftmost argument with holes is
+/// replaced, and the others are left for later expansions.
+pub fn expand_call<T: Value, U: Lattice>(
+    f: &Func<T, U>,
+    expansions: Vec<Vec<Term<T, U>>>,
+) -> Vec<Term<T, U>> {
+    let combinations = expansions
+        .iter()
+        .try_fold(1usize, |n, e| n.checked_mul(e.len()));
+    if combinations.is_none_or(|n| n > MAX_EXPANSION) {
+        if let Some(i) = f.args().iter().position(|a| a.has_hole()) {
+            return expansions
+                .into_iter()
+                .nth(i)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|a| {
+                    let mut positions = 0..;
+                    Rc::new(Expr::Call(f.map_args(|arg| match positions.next() {
+                        Some(j) if j == i => a.clone(),
+                        _ => arg.clone(),
+                    })))
+                })
+                .collect();
+        }
+    }
+    expansions
+        .into_iter()
+        .multi_cartesian_product()
+        .map(|args| {
+            let mut args = args.into_iter();
+            Rc::new(Expr::Call(f.map_args(|_| args.next().unwrap())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concrete::StrVal;
+    use crate::syguslang::{Expr, Func};
+    use crate::types::{TypeLattice, TypeValues};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_errors() {
+        let mut env = Environment::new();
+        env.put("arg0".to_string(), StrVal::from("abc".to_string()));
+        let arg0 = || Rc::new(Expr::Var("arg0".to_string()));
+        let int = |i| Rc::new(Expr::Const(StrVal::from(i)));
+
+        // string operations are total, as in SMT-LIB
+        let at: Expr<StrVal, TypeLattice> = Expr::Call(Func::At(arg0(), int(5)));
+        assert_eq!(at.eval(&env), Ok(StrVal::from(String::new())));
+
+        let len: Expr<StrVal, TypeLattice> = Expr::Call(Func::Len(int(1)));
+        let err = len.eval(&env).unwrap_err();
+        assert_eq!(
+            err,
+            EvalError::TypeMismatch {
+                op: "len",
+                args: vec!["Int".to_string()]
+            }
+        );
+        let boxed: Box<dyn Error> = Box::new(err);
+        assert_eq!(boxed.to_string(), "len: cannot apply to (Int)");
+
+        // errors in arguments are reported as they are
+        let nested: Expr<StrVal, TypeLattice> =
+            Expr::Call(Func::Append(Rc::new(Expr::Var("arg1".to_string())), arg0()));
+        assert_eq!(
+            nested.eval(&env),
+            Err(EvalError::UnboundVariable("arg1".to_string()))
+        );
+
+        let digits = Rc::new(Expr::Const(StrVal::from(
+            "99999999999999999999".to_string(),
+        )));
+        let parse: Expr<StrVal, TypeLattice> = Expr::Call(Func::ToInt(digits));
+        assert_eq!(parse.eval(&env), Err(EvalError::Overflow("to-int")));
+        let max = || Rc::new(Expr::Const(StrVal::from(i64::MAX)));
+        let sum: Expr<StrVal, TypeLattice> = Expr::Call(Func::Add(max(), int(1)));
+        assert_eq!(sum.eval(&env), Err(EvalError::Overflow("+")));
+
+        let mut tenv = Environment::new();
+        tenv.put("arg0".to_string(), TypeValues::Abs(TypeLattice::String));
+        let typed: Expr<TypeValues, TypeLattice> = Expr::Call(Func::Substr(
+            Rc::new(Expr::Var("arg0".to_string())),
+            Rc::new(Expr::Var("arg0".to_string())),
+            Rc::new(Expr::Hole(TypeLattice::Integer, None, None)),
+        ));
+        assert_eq!(
+            typed.eval(&tenv),
+            Err(EvalError::TypeMismatch {
+                op: "substr",
+                args: vec!["Str".to_string(), "Str".to_string(), "Int".to_string()]
+            })
+        );
+    }
+}
@@ -1,9 +1,20 @@
-// pub mod r#abstract;
+pub mod r#abstract;
+pub mod cegis;
+pub mod codegen;
 pub mod concrete;
+pub mod conditional;
+pub mod cost;
 pub mod environment;
+pub mod grammar;
 pub mod interpreter;
 pub mod linear;
+pub mod oe;
+pub mod regex;
+pub mod simplify;
+pub mod smt;
+pub mod spec;
 pub mod strlenlat;
+pub mod sygus;
 pub mod syguslang;
 pub mod synthesizer;
 pub mod types;
@@ -1,14 +1,13 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::{Debug, Display};
-use std::ops::{Add, Mul, Sub};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct LinearExpr {
-    c: i32,
-    terms: HashMap<String, i32>,
+    c: i64,
+    terms: BTreeMap<String, i64>,
 }
 
 impl Display for LinearExpr {
@@ -21,9 +20,9 @@ impl Display for LinearExpr {
             .map(|(k, v)| format!("{}{}", v, k))
             .collect::<Vec<String>>()
             .join(" + ");
-        if self.c != 0 && stringified != "" {
+        if self.c != 0 && !stringified.is_empty() {
             write!(f, "{} + {}", stringified, self.c)
-        } else if stringified != "" {
+        } else if !stringified.is_empty() {
             write!(f, "{}", stringified)
         } else {
             write!(f, "{}", self.c)
@@ -42,59 +41,89 @@ impl PartialOrd for LinearExpr {
     }
 }
 
-impl From<i32> for LinearExpr {
-    fn from(item: i32) -> Self {
+impl From<i64> for LinearExpr {
+    fn from(item: i64) -> Self {
         LinearExpr {
             c: item,
-            terms: HashMap::new(),
+            terms: BTreeMap::new(),
         }
     }
 }
 
+impl From<i32> for LinearExpr {
+    fn from(item: i32) -> Self {
+        LinearExpr::from(item as i64)
+    }
+}
+
 impl From<String> for LinearExpr {
     fn from(item: String) -> Self {
-        let mut h = HashMap::new();
+        let mut h = BTreeMap::new();
         h.insert(item, 1);
 
         LinearExpr { c: 0, terms: h }
     }
 }
 
-impl Add for LinearExpr {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        let new_c = self.c + other.c;
-        let mut terms = self.terms.clone();
+impl LinearExpr {
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let c = self.c.checked_add(other.c)?;
+        let mut terms = self.terms;
         for (id, coeff) in other.terms {
-            match terms.get(&id) {
-                None => terms.insert(id, coeff),
-                Some(c) => terms.insert(id, c + coeff),
-            };
+            let sum = terms
+                .get(&id)
+                .map_or(Some(coeff), |c| c.checked_add(coeff))?;
+            terms.insert(id, sum);
         }
-        LinearExpr {
-            c: new_c,
-            terms: terms,
+        terms.retain(|_, coeff| *coeff != 0);
+        Some(LinearExpr { c, terms })
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(LinearExpr::from(-1).checked_mul(other)?)
+    }
+
+    /// `None` if the product overflows, or is not linear because neither
+    /// side is a constant.
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        if rhs.terms.is_empty() {
+            Self::scale(self, rhs.c)
+        } else if self.terms.is_empty() {
+            Self::scale(rhs, self.c)
+        } else {
+            None
         }
     }
-}
 
-impl LinearExpr {
-    fn mul_impl(lhs: Self, rhs: Self) -> Self {
-        let c = lhs.c * rhs.c;
-        let mut terms = HashMap::new();
-        for (id, coeff) in lhs.terms {
-            terms.insert(id, coeff * rhs.c);
+    fn scale(expr: Self, k: i64) -> Option<Self> {
+        let c = expr.c.checked_mul(k)?;
+        let mut terms = BTreeMap::new();
+        for (id, coeff) in expr.terms {
+            let scaled = coeff.checked_mul(k)?;
+            if scaled != 0 {
+                terms.insert(id, scaled);
+            }
         }
+        Some(LinearExpr { c, terms })
+    }
+
+    pub fn constant(&self) -> i64 {
+        self.c
+    }
 
-        LinearExpr { c: c, terms: terms }
+    /// The variables with a non-zero coefficient, in order.
+    pub fn terms(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.terms
+            .iter()
+            .filter(|(_, k)| **k != 0)
+            .map(|(x, k)| (x.as_str(), *k))
     }
 
     pub fn is_const(&self) -> bool {
         self.terms.values().all(|&x| x == 0)
     }
 
-    pub fn as_const(&self) -> Option<i32> {
+    pub fn as_const(&self) -> Option<i64> {
         if self.is_const() {
             Some(self.c)
         } else {
@@ -103,29 +132,7 @@ impl LinearExpr {
     }
 }
 
-impl Mul for LinearExpr {
-    type Output = Self;
-
-    fn mul(self, rhs: LinearExpr) -> Self {
-        if rhs.terms.is_empty() {
-            Self::mul_impl(self, rhs)
-        } else if self.terms.is_empty() {
-            Self::mul_impl(rhs, self)
-        } else {
-            panic!("can multiply into non-linear terms")
-        }
-    }
-}
-
-impl Sub for LinearExpr {
-    type Output = Self;
-
-    fn sub(self, other: Self) -> Self {
-        self + (LinearExpr::from(-1) * other)
-    }
-}
-
-impl TryFrom<LinearExpr> for i32 {
+impl TryFrom<LinearExpr> for i64 {
     type Error = &'static str;
 
     fn try_from(value: LinearExpr) -> Result<Self, Self::Error> {
@@ -151,8 +158,48 @@ mod tests {
     fn test_op() {
         let x = LinearExpr::from("x".to_string());
         let c = LinearExpr::from(2);
-        let res = (LinearExpr::from(2) * (x + c)) - LinearExpr::from(1);
+        let res = LinearExpr::from(2)
+            .checked_mul(x.checked_add(c).unwrap())
+            .and_then(|e| e.checked_sub(LinearExpr::from(1)))
+            .unwrap();
         assert_eq!(res.c, 3);
-        assert_eq!(*res.terms.get(&"x".to_string()).unwrap(), 2);
+        assert_eq!(*res.terms.get("x").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_display() {
+        let x = LinearExpr::from("x".to_string());
+        let y = LinearExpr::from("y".to_string());
+        let res = y
+            .clone()
+            .checked_add(x.clone())
+            .and_then(|e| e.checked_add(LinearExpr::from(3)))
+            .unwrap();
+        assert_eq!(res.to_string(), "1x + 1y + 3");
+        assert_eq!(res.checked_sub(y).unwrap().to_string(), "1x + 3");
+        assert_eq!(x.clone().checked_sub(x), Some(LinearExpr::from(0)));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let x = LinearExpr::from("x".to_string());
+        let big = LinearExpr::from(i64::MAX);
+        assert_eq!(big.clone().checked_add(LinearExpr::from(1)), None);
+        assert_eq!(
+            LinearExpr::from(i64::MIN).checked_sub(LinearExpr::from(1)),
+            None
+        );
+        assert_eq!(
+            big.clone()
+                .checked_mul(x.clone().checked_add(x.clone()).unwrap()),
+            None
+        );
+        // neither side is a constant
+        assert_eq!(x.clone().checked_mul(x), None);
+        assert_eq!(
+            big.checked_sub(LinearExpr::from(1))
+                .and_then(|e| e.as_const()),
+            Some(i64::MAX - 1)
+        );
     }
 }
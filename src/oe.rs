@@ -0,0 +1,311 @@
+use crate::concrete::StrVal;
+use crate::grammar::Grammar;
+use crate::simplify::simplify;
+use crate::spec::Examples;
+use crate::syguslang::{Expr, Func, Term};
+use crate::values::{Lattice, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The values a program computes on every example, with evaluation errors
+/// collapsed to `None`. Programs with equal signatures are interchangeable
+/// as far as the examples can tell.
+pub type Signature = Vec<Option<String>>;
+
+type Verdict<T, U> = (Term<T, U>, bool);
+
+// a node of a term, and the nonterminal it is derived from
+type Position<T, U> = (*const Expr<T, U>, Option<usize>);
+
+pub fn signature<U: Lattice>(examples: &Examples, prog: &Expr<StrVal, U>) -> Signature {
+    examples
+        .outputs(prog)
+        .into_iter()
+        .map(|r| r.ok().map(|v| v.to_string()))
+        .collect()
+}
+
+/// Keeps one representative, the smallest one seen, for each class of
+/// observationally equivalent closed terms derived from the same grammar
+/// nonterminal. `None` stands for the holes that take any nonterminal of
+/// their sort.
+#[derive(Debug)]
+pub struct OeCache<T: Value, U: Lattice> {
+    classes: HashMap<(Option<usize>, Signature), Term<T, U>>,
+    // terms with the same normal form compute the same values, and fail on
+    // the same examples, so each normal form is run on the examples only once
+    signatures: HashMap<String, Signature>,
+    // expansion shares closed subterms between candidates, so verdicts on
+    // closed terms are memoized per node and nonterminal; the stored term
+    // keeps the address from being reused
+    verdicts: HashMap<Position<T, U>, Verdict<T, U>>,
+    // the productions of each nonterminal and those it includes
+    productions: HashMap<usize, Vec<Term<T, U>>>,
+}
+
+impl<T: Value, U: Lattice> Default for OeCache<T, U> {
+    fn default() -> Self {
+        OeCache {
+            classes: HashMap::new(),
+            signatures: HashMap::new(),
+            verdicts: HashMap::new(),
+            productions: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Value, U: Lattice> OeCache<T, U>
+where
+    Expr<StrVal, U>: From<Term<T, U>>,
+{
+    pub fn new() -> OeCache<T, U> {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    /// Records a closed term derived from the nonterminal and reports
+    /// whether it represents its class.
+    pub fn insert(&mut self, examples: &Examples, nt: Option<usize>, term: &Term<T, U>) -> bool {
+        let normal = simplify(&Rc::new(Expr::from(term.clone())));
+        let sig = self
+            .signatures
+            .entry(normal.to_string())
+            .or_insert_with(|| signature(examples, &normal))
+            .clone();
+        let class = (nt, sig);
+        match self.classes.get(&class) {
+            Some(rep) if rep == term => true,
+            Some(rep) if rep.size() <= term.size() => false,
+            _ => {
+                self.classes.insert(class, term.clone());
+                true
+            }
+        }
+    }
+
+    /// A term derived from the nonterminal is redundant when one of its
+    /// closed calls is equivalent to a smaller or earlier call derived from
+    /// the same nonterminal, since replacing it gives a term the search also
+    /// explores. Subterms whose nonterminal the grammar leaves ambiguous are
+    /// never pruned.
+    pub fn is_redundant(
+        &mut self,
+        examples: &Examples,
+        grammar: &Grammar<T, U>,
+        nt: Option<usize>,
+        term: &Term<T, U>,
+    ) -> bool {
+        if !matches!(**term, Expr::Call(_) | Expr::If(_, _, _)) {
+            return false;
+        }
+        if term.has_hole() {
+            return self
+                .derivation(grammar, nt, term)
+                .into_iter()
+                .any(|(nt, part)| self.is_redundant(examples, grammar, nt, part));
+        }
+        let key = (Rc::as_ptr(term), nt);
+        if let Some((_, verdict)) = self.verdicts.get(&key) {
+            return *verdict;
+        }
+        let verdict = self
+            .derivation(grammar, nt, term)
+            .into_iter()
+            .any(|(nt, part)| self.is_redundant(examples, grammar, nt, part))
+            || (matches!(**term, Expr::Call(_)) && !self.insert(examples, nt, term));
+        self.verdicts.insert(key, (term.clone(), verdict));
+        verdict
+    }
+
+    // the subterms of a call or conditional derived from the nonterminal,
+    // each with the nonterminal it is derived from: the holes of the one
+    // production that matches it. Without a nonterminal, these are its
+    // arguments, which take any nonterminal of their sort.
+    fn derivation<'a>(
+        &mut self,
+        grammar: &Grammar<T, U>,
+        nt: Option<usize>,
+        term: &'a Term<T, U>,
+    ) -> Vec<(Option<usize>, &'a Term<T, U>)> {
+        let nt = match nt {
+            Some(nt) => nt,
+            None => {
+                return match &**term {
+                    Expr::Call(f) => f.args().into_iter().map(|a| (None, a)).collect(),
+                    Expr::If(cond, then, otherwise) => {
+                        vec![(None, cond), (None, then), (None, otherwise)]
+                    }
+                    _ => vec![],
+                }
+            }
+        };
+        let productions = self.productions.entry(nt).or_insert_with(|| {
+            grammar
+                .included(nt)
+                .into_iter()
+                .flat_map(|i| grammar.nonterminals()[i].productions.iter().cloned())
+                .collect()
+        });
+        let mut matches = productions.iter().filter_map(|p| {
+            let mut parts = vec![];
+            if matches(p, term, &mut parts) {
+                Some(parts)
+            } else {
+                None
+            }
+        });
+        let parts = matches.next().unwrap_or_default();
+        // productions differing only in their nonterminals can derive the
+        // same term in more than one way
+        let same = |other: &Vec<(Option<usize>, &Term<T, U>)>| {
+            other.len() == parts.len()
+                && other
+                    .iter()
+                    .zip(&parts)
+                    .all(|((i, a), (j, b))| i == j && Rc::ptr_eq(a, b))
+        };
+        if matches.all(|other| same(&other)) {
+            parts
+        } else {
+            vec![]
+        }
+    }
+}
+
+// whether the term is the production with its holes filled, collecting
+// what fills each hole along with the hole's nonterminal
+pub(crate) fn matches<'a, T: Value, U: Lattice>(
+    production: &Term<T, U>,
+    term: &'a Term<T, U>,
+    parts: &mut Vec<(Option<usize>, &'a Term<T, U>)>,
+) -> bool {
+    match (&**production, &**term) {
+        (Expr::Hole(_, _, nt), _) => {
+            parts.push((*nt, term));
+            true
+        }
+        (Expr::Call(f), Expr::Call(g)) => {
+            let same_op = match (f, g) {
+                (Func::InRe(_, r), Func::InRe(_, s)) => r == s,
+                _ => std::mem::discriminant(f) == std::mem::discriminant(g),
+            };
+            same_op
+                && f.args()
+                    .into_iter()
+                    .zip(g.args())
+                    .all(|(p, t)| matches(p, t, parts))
+        }
+        (Expr::If(c, t, o), Expr::If(d, u, v)) => {
+            matches(c, d, parts) && matches(t, u, parts) && matches(o, v, parts)
+        }
+        _ => production == term,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TypeLattice, TypeValues};
+
+    #[test]
+    fn test_redundant() {
+        let mut examples = Examples::new();
+        examples.add(
+            vec![StrVal::from("ab".to_string())],
+            StrVal::from("b".to_string()),
+        );
+        examples.add(
+            vec![StrVal::from("xyz".to_string())],
+            StrVal::from("y".to_string()),
+        );
+
+        let arg0: Term<TypeValues, TypeLattice> = Rc::new(Expr::Var("arg0".to_string()));
+        let int = |i| Rc::new(Expr::Const(TypeValues::from_concrete(StrVal::from(i))));
+        let hole = Rc::new(Expr::Hole(TypeLattice::String, None, None));
+        let at1 = Rc::new(Expr::Call(Func::At(arg0.clone(), int(1))));
+        let substr11 = Rc::new(Expr::Call(Func::Substr(arg0.clone(), int(1), int(1))));
+
+        let grammar: Grammar<TypeValues, TypeLattice> = Grammar::new();
+        let mut oe = OeCache::new();
+        assert!(!oe.is_redundant(&examples, &grammar, None, &at1));
+        assert!(!oe.is_redundant(&examples, &grammar, None, &at1));
+        assert!(oe.is_redundant(&examples, &grammar, None, &substr11));
+        assert!(oe.is_redundant(
+            &examples,
+            &grammar,
+            None,
+            &Rc::new(Expr::Call(Func::Append(substr11, hole.clone())))
+        ));
+        assert!(!oe.is_redundant(
+            &examples,
+            &grammar,
+            None,
+            &Rc::new(Expr::Call(Func::Append(at1, hole)))
+        ));
+        assert_eq!(oe.len(), 1);
+    }
+
+    #[test]
+    fn test_failing_term_keeps_its_class() {
+        let mut examples = Examples::new();
+        examples.add(
+            vec![StrVal::from("ab".to_string())],
+            StrVal::from(String::new()),
+        );
+        examples.add(
+            vec![StrVal::from("xyz".to_string())],
+            StrVal::from(String::new()),
+        );
+
+        let arg0: Term<TypeValues, TypeLattice> = Rc::new(Expr::Var("arg0".to_string()));
+        let int = |i| Rc::new(Expr::Const(TypeValues::from_concrete(StrVal::from(i))));
+        let len = Rc::new(Expr::Call(Func::Len(arg0.clone())));
+        // the offset overflows, but simplifies away along with the length
+        // of zero
+        let overflow = Rc::new(Expr::Call(Func::Add(len.clone(), int(i64::MAX))));
+        let failing = Rc::new(Expr::Call(Func::Substr(arg0.clone(), overflow, int(0))));
+        let doubled = Rc::new(Expr::Call(Func::Append(arg0.clone(), arg0)));
+        let empty = Rc::new(Expr::Call(Func::Substr(doubled, len, int(0))));
+        assert_eq!(failing.size(), empty.size());
+
+        let grammar: Grammar<TypeValues, TypeLattice> = Grammar::new();
+        let mut oe = OeCache::new();
+        oe.is_redundant(&examples, &grammar, None, &failing);
+        assert!(!oe.is_redundant(&examples, &grammar, None, &empty));
+    }
+
+    #[test]
+    fn test_redundant_structure_shares_a_class() {
+        let mut examples = Examples::new();
+        examples.add(
+            vec![StrVal::from("ab".to_string())],
+            StrVal::from("b".to_string()),
+        );
+
+        let arg0: Term<TypeValues, TypeLattice> = Rc::new(Expr::Var("arg0".to_string()));
+        let int = |i| Rc::new(Expr::Const(TypeValues::from_concrete(StrVal::from(i))));
+        let empty = Rc::new(Expr::Const(TypeValues::from_concrete(StrVal::from(
+            String::new(),
+        ))));
+        let at1 = Rc::new(Expr::Call(Func::At(arg0.clone(), int(1))));
+        // (at (append arg0 "") (+ 0 1))
+        let padded = Rc::new(Expr::Call(Func::At(
+            Rc::new(Expr::Call(Func::Append(arg0, empty))),
+            Rc::new(Expr::Call(Func::Add(int(0), int(1)))),
+        )));
+
+        let mut oe: OeCache<TypeValues, TypeLattice> = OeCache::new();
+        assert!(oe.insert(&examples, None, &at1));
+        assert!(!oe.insert(&examples, None, &padded));
+        assert_eq!(oe.len(), 1);
+        // both have the same normal form, which was run only once
+        assert_eq!(oe.signatures.len(), 1);
+    }
+}
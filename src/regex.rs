@@ -0,0 +1,151 @@
+use crate::sygus::SExpr;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Display;
+
+/// Regular expressions of the SMT-LIB string theory, used as the fixed
+/// second argument of `str.in_re`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Regex {
+    None,
+    All,
+    AllChar,
+    Str(String),
+    Range(char, char),
+    Concat(Vec<Regex>),
+    Union(Vec<Regex>),
+    Inter(Vec<Regex>),
+    Star(Box<Regex>),
+    Plus(Box<Regex>),
+    Opt(Box<Regex>),
+    Comp(Box<Regex>),
+    Diff(Box<Regex>, Box<Regex>),
+    Loop(Box<Regex>, u32, u32),
+}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", SExpr::from(self))
+    }
+}
+
+impl Regex {
+    pub fn matches(&self, s: &str) -> bool {
+        let chars: Vec<char> = s.chars().collect();
+        self.ends(&chars, 0).contains(&chars.len())
+    }
+
+    // every position at which a match starting at `from` can end
+    fn ends(&self, s: &[char], from: usize) -> BTreeSet<usize> {
+        match self {
+            Self::None => BTreeSet::new(),
+            Self::All => (from..=s.len()).collect(),
+            Self::AllChar => (from < s.len()).then(|| from + 1).into_iter().collect(),
+            Self::Str(t) => {
+                let t: Vec<char> = t.chars().collect();
+                s[from..]
+                    .starts_with(&t)
+                    .then(|| from + t.len())
+                    .into_iter()
+                    .collect()
+            }
+            Self::Range(lo, hi) => s
+                .get(from)
+                .filter(|c| lo <= c && *c <= hi)
+                .map(|_| from + 1)
+                .into_iter()
+                .collect(),
+            Self::Concat(rs) => rs.iter().fold(BTreeSet::from([from]), |starts, r| {
+                starts.into_iter().flat_map(|p| r.ends(s, p)).collect()
+            }),
+            Self::Union(rs) => rs.iter().flat_map(|r| r.ends(s, from)).collect(),
+            Self::Inter(rs) => rs
+                .iter()
+                .map(|r| r.ends(s, from))
+                .reduce(|a, b| a.intersection(&b).copied().collect())
+                .unwrap_or_else(|| (from..=s.len()).collect()),
+            Self::Star(r) => Self::repeat(r, s, BTreeSet::from([from])),
+            Self::Plus(r) => Self::repeat(r, s, r.ends(s, from)),
+            Self::Opt(r) => {
+                let mut ends = r.ends(s, from);
+                ends.insert(from);
+                ends
+            }
+            Self::Comp(r) => {
+                let ends = r.ends(s, from);
+                (from..=s.len()).filter(|p| !ends.contains(p)).collect()
+            }
+            Self::Diff(r1, r2) => {
+                let excluded = r2.ends(s, from);
+                r1.ends(s, from)
+                    .into_iter()
+                    .filter(|p| !excluded.contains(p))
+                    .collect()
+            }
+            Self::Loop(r, lo, hi) => {
+                let mut ends = BTreeSet::new();
+                let mut current = BTreeSet::from([from]);
+                for n in 0..=*hi {
+                    if n >= *lo {
+                        ends.extend(current.iter().copied());
+                    }
+                    current = current.into_iter().flat_map(|p| r.ends(s, p)).collect();
+                    if current.is_empty() {
+                        break;
+                    }
+                }
+                ends
+            }
+        }
+    }
+
+    // closes `starts` under further matches of `r`
+    fn repeat(r: &Regex, s: &[char], starts: BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut ends = starts.clone();
+        let mut frontier: Vec<usize> = starts.into_iter().collect();
+        while let Some(p) = frontier.pop() {
+            for e in r.ends(s, p) {
+                if ends.insert(e) {
+                    frontier.push(e);
+                }
+            }
+        }
+        ends
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let digit = Regex::Range('0', '9');
+        let number = Regex::Plus(Box::new(digit.clone()));
+        assert!(number.matches("2024"));
+        assert!(!number.matches(""));
+        assert!(!number.matches("20a4"));
+
+        let date = Regex::Concat(vec![
+            Regex::Loop(Box::new(digit.clone()), 2, 2),
+            Regex::Str("/".to_string()),
+            Regex::Loop(Box::new(digit), 1, 2),
+        ]);
+        assert!(date.matches("12/3"));
+        assert!(date.matches("12/31"));
+        assert!(!date.matches("12/312"));
+        assert!(!date.matches("1/31"));
+
+        let no_space = Regex::Comp(Box::new(Regex::Concat(vec![
+            Regex::All,
+            Regex::Str(" ".to_string()),
+            Regex::All,
+        ])));
+        assert!(no_space.matches("ab"));
+        assert!(!no_space.matches("a b"));
+        assert!(Regex::Star(Box::new(Regex::AllChar)).matches(""));
+        assert!(!Regex::None.matches(""));
+        assert!(Regex::Inter(vec![Regex::All, Regex::Opt(Box::new(Regex::AllChar))]).matches("x"));
+        assert_eq!(date.to_string(), "(re.++ ((_ re.loop 2 2) (re.range \"0\" \"9\")) (str.to_re \"/\") ((_ re.loop 1 2) (re.range \"0\" \"9\")))");
+    }
+}
@@ -0,0 +1,317 @@
+use crate::concrete::StrVal;
+use crate::environment::Environment;
+use crate::interpreter::Evaluable;
+use crate::syguslang::{Expr, Func, Term};
+use crate::values::Lattice;
+use std::rc::Rc;
+
+/// Rewrites a program into an equivalent one that is no larger. Calls on
+/// constants are folded with the interpreter and identities of the string,
+/// integer and Boolean operations are applied bottom-up until none matches.
+/// Constants go to the right of `+` and `=`, and nested constant offsets are
+/// summed, so programs that differ only in such details come out the same.
+///
+/// The result fails to evaluate exactly where the program does: a rewrite
+/// only drops a subterm that cannot fail, and a call on constants whose
+/// evaluation fails is left as it is. Programs are taken to be well-typed.
+pub fn simplify<U: Lattice>(term: &Term<StrVal, U>) -> Term<StrVal, U> {
+    let mut term = match &**term {
+        Expr::Call(f) => Rc::new(Expr::Call(f.map_args(|a| simplify(a)))),
+        Expr::If(cond, then, otherwise) => Rc::new(Expr::If(
+            simplify(cond),
+            simplify(then),
+            simplify(otherwise),
+        )),
+        _ => return term.clone(),
+    };
+    while let Some(next) = rewrite(&term) {
+        term = next;
+    }
+    term
+}
+
+fn rewrite<U: Lattice>(term: &Term<StrVal, U>) -> Option<Term<StrVal, U>> {
+    match &**term {
+        Expr::Call(f) => fold(f).or_else(|| rewrite_call(term, f)),
+        Expr::If(cond, then, otherwise) => match &**cond {
+            // both branches are evaluated, so the one not taken must not fail
+            Expr::Const(StrVal::Bool(b)) => {
+                let (taken, dropped) = if *b {
+                    (then, otherwise)
+                } else {
+                    (otherwise, then)
+                };
+                Some(taken.clone()).filter(|_| !can_fail(dropped))
+            }
+            _ if then == otherwise && !can_fail(cond) => Some(then.clone()),
+            Expr::Call(Func::Not(c)) => Some(Rc::new(Expr::If(
+                c.clone(),
+                otherwise.clone(),
+                then.clone(),
+            ))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// a call on constants becomes its value, unless evaluating it fails
+fn fold<U: Lattice>(func: &Func<StrVal, U>) -> Option<Term<StrVal, U>> {
+    if !func.args().iter().all(|a| matches!(***a, Expr::Const(_))) {
+        return None;
+    }
+    let value = func.eval(&Environment::new()).ok()?;
+    Some(Rc::new(Expr::Const(value)))
+}
+
+fn rewrite_call<U: Lattice>(
+    term: &Term<StrVal, U>,
+    func: &Func<StrVal, U>,
+) -> Option<Term<StrVal, U>> {
+    let call = |f| Some(Rc::new(Expr::Call(f)));
+    match func {
+        Func::Add(x, y) | Func::Sub(x, y) if int(y) == Some(0) => Some(x.clone()),
+        Func::Add(x, y) if int(x) == Some(0) => Some(y.clone()),
+        Func::Add(x, y) if is_const(x) && !is_const(y) => call(Func::Add(y.clone(), x.clone())),
+        Func::Add(_, _) | Func::Sub(_, _) => {
+            let (x, k) = offset(term)?;
+            // the inner sum is dropped, so it must not be able to overflow
+            // where the merged one does not
+            let (base, k) = match offset(&x) {
+                Some((y, j)) if j.signum() == k.signum() || fits(&y, j) => (y, j.checked_add(k)?),
+                Some(_) => return None,
+                None => (x, k),
+            };
+            let canonical = match k {
+                0 => base,
+                k if k < 0 => call(Func::Sub(base, constant(StrVal::from(k.checked_neg()?))))?,
+                k => call(Func::Add(base, constant(StrVal::from(k))))?,
+            };
+            Some(canonical).filter(|c| c != term)
+        }
+        Func::Append(s, t) if string(s) == Some("") => Some(t.clone()),
+        Func::Append(s, t) if string(t) == Some("") => Some(s.clone()),
+        Func::Substr(s, i, n) if int(i) == Some(0) && is_len_of(n, s) => Some(s.clone()),
+        Func::Substr(s, i, n) if int(n).is_some_and(|n| n <= 0) && !can_fail(s) && !can_fail(i) => {
+            Some(constant(StrVal::from(String::new())))
+        }
+        Func::Replace(s, t, u) | Func::ReplaceAll(s, t, u) if t == u && !can_fail(t) => {
+            Some(s.clone())
+        }
+        Func::ReplaceAll(s, t, u) if string(t) == Some("") && !can_fail(u) => Some(s.clone()),
+        Func::IndexOf(s, t, i) if string(t) == Some("") && int(i) == Some(0) && !can_fail(s) => {
+            Some(constant(StrVal::from(0)))
+        }
+        // the needle is the first argument of prefixof and suffixof, and
+        // the second of contains
+        Func::PrefixOf(s, t) | Func::SuffixOf(s, t) | Func::Contains(t, s)
+            if (s == t || string(s) == Some("")) && !can_fail(s) && !can_fail(t) =>
+        {
+            Some(constant(StrVal::Bool(true)))
+        }
+        Func::Eq(x, y) | Func::Le(x, y) | Func::StrLe(x, y) if x == y && !can_fail(x) => {
+            Some(constant(StrVal::Bool(true)))
+        }
+        Func::Lt(x, y) | Func::StrLt(x, y) if x == y && !can_fail(x) => {
+            Some(constant(StrVal::Bool(false)))
+        }
+        Func::Eq(x, y) if is_const(x) && !is_const(y) => call(Func::Eq(y.clone(), x.clone())),
+        Func::And(x, y) | Func::Or(x, y) => {
+            // true is the unit of and, false the unit of or; the other
+            // operand is only dropped if it cannot fail
+            let unit = matches!(func, Func::And(_, _));
+            match (boolean(x), boolean(y)) {
+                (Some(b), _) if b == unit => Some(y.clone()),
+                (Some(_), _) if !can_fail(y) => Some(x.clone()),
+                (_, Some(b)) if b == unit => Some(x.clone()),
+                (_, Some(_)) if !can_fail(x) => Some(y.clone()),
+                _ if x == y => Some(x.clone()),
+                _ => None,
+            }
+        }
+        Func::Not(b) => match &**b {
+            Expr::Call(Func::Not(c)) => Some(c.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// whether evaluating a well-typed term can fail: only arithmetic can
+// overflow, and a closed call that fails is never folded away
+fn can_fail<U: Lattice>(term: &Term<StrVal, U>) -> bool {
+    match &**term {
+        Expr::Call(Func::Add(_, _)) | Expr::Call(Func::Sub(_, _)) | Expr::Call(Func::ToInt(_)) => {
+            true
+        }
+        Expr::Call(f) => f.args().into_iter().any(can_fail),
+        Expr::If(cond, then, otherwise) => can_fail(cond) || can_fail(then) || can_fail(otherwise),
+        _ => false,
+    }
+}
+
+// no string is long enough for its length or positions to reach this
+const MAX_POSITION: i64 = 1 << 48;
+
+// whether x + j cannot overflow, which holds for lengths and positions in a
+// string, which lie in [-1, MAX_POSITION], and offsets that are not extreme
+fn fits<U: Lattice>(x: &Term<StrVal, U>, j: i64) -> bool {
+    let position = matches!(
+        &**x,
+        Expr::Call(Func::Len(_)) | Expr::Call(Func::IndexOf(_, _, _)) | Expr::Call(Func::ToCode(_))
+    );
+    position && j.checked_add(MAX_POSITION).is_some() && j.checked_sub(1).is_some()
+}
+
+// x + k and x - k as the pair (x, ±k)
+fn offset<U: Lattice>(term: &Term<StrVal, U>) -> Option<(Term<StrVal, U>, i64)> {
+    match &**term {
+        Expr::Call(Func::Add(x, k)) => Some((x.clone(), int(k)?)),
+        Expr::Call(Func::Sub(x, k)) => Some((x.clone(), int(k)?.checked_neg()?)),
+        _ => None,
+    }
+}
+
+fn is_len_of<U: Lattice>(n: &Term<StrVal, U>, s: &Term<StrVal, U>) -> bool {
+    matches!(&**n, Expr::Call(Func::Len(t)) if t == s)
+}
+
+fn constant<U: Lattice>(value: StrVal) -> Term<StrVal, U> {
+    Rc::new(Expr::Const(value))
+}
+
+fn is_const<U: Lattice>(term: &Term<StrVal, U>) -> bool {
+    matches!(**term, Expr::Const(_))
+}
+
+fn int<U: Lattice>(term: &Term<StrVal, U>) -> Option<i64> {
+    match &**term {
+        Expr::Const(StrVal::Int(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+fn string<U: Lattice>(term: &Term<StrVal, U>) -> Option<&str> {
+    match &**term {
+        Expr::Const(StrVal::Str(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn boolean<U: Lattice>(term: &Term<StrVal, U>) -> Option<bool> {
+    match &**term {
+        Expr::Const(StrVal::Bool(b)) => Some(*b),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TypeLattice;
+
+    type Prog = Term<StrVal, TypeLattice>;
+
+    fn call(f: Func<StrVal, TypeLattice>) -> Prog {
+        Rc::new(Expr::Call(f))
+    }
+
+    fn arg(i: usize) -> Prog {
+        Rc::new(Expr::Var(format!("arg{}", i)))
+    }
+
+    fn int(i: i64) -> Prog {
+        constant(StrVal::from(i))
+    }
+
+    fn string(s: &str) -> Prog {
+        constant(StrVal::from(s.to_string()))
+    }
+
+    fn assert_simplifies(prog: Prog, expected: &str) {
+        let simple = simplify(&prog);
+        assert_eq!(simple.to_string(), expected);
+        assert!(simple.size() <= prog.size());
+    }
+
+    #[test]
+    fn test_identities() {
+        assert_simplifies(call(Func::Add(arg(1), int(0))), "arg1");
+        assert_simplifies(call(Func::Append(string(""), arg(0))), "arg0");
+        assert_simplifies(
+            call(Func::Substr(arg(0), int(0), call(Func::Len(arg(0))))),
+            "arg0",
+        );
+        // only the same string makes the whole of it
+        assert_simplifies(
+            call(Func::Substr(arg(0), int(0), call(Func::Len(arg(1))))),
+            "(substr arg0 0 (len arg1))",
+        );
+        assert_simplifies(call(Func::Contains(arg(0), string(""))), "true");
+        assert_simplifies(
+            call(Func::Not(call(Func::Not(call(Func::IsDigit(arg(0))))))),
+            "(is_digit arg0)",
+        );
+    }
+
+    #[test]
+    fn test_folding() {
+        // (substr arg0 0 (indexof (append "a" "b c") " " (- 1 1)))
+        let space = call(Func::IndexOf(
+            call(Func::Append(string("a"), string("b c"))),
+            string(" "),
+            call(Func::Sub(int(1), int(1))),
+        ));
+        assert_simplifies(
+            call(Func::Substr(arg(0), int(0), space)),
+            "(substr arg0 0 2)",
+        );
+
+        // failing calls are left alone
+        let overflow = call(Func::Add(int(i64::MAX), int(1)));
+        assert_simplifies(overflow.clone(), "(+ 9223372036854775807 1)");
+
+        // and so are the subterms around them, which would hide the failure
+        let cond = || call(Func::Lt(int(1), int(2)));
+        assert_simplifies(
+            Rc::new(Expr::If(cond(), arg(0), overflow.clone())),
+            "(if true arg0 (+ 9223372036854775807 1))",
+        );
+        assert_simplifies(Rc::new(Expr::If(cond(), arg(0), arg(1))), "arg0");
+        assert_simplifies(
+            call(Func::Substr(arg(0), overflow.clone(), int(0))),
+            "(substr arg0 (+ 9223372036854775807 1) 0)",
+        );
+        let sum = call(Func::Add(arg(0), int(1)));
+        assert_simplifies(
+            call(Func::Eq(sum.clone(), sum.clone())),
+            "(= (+ arg0 1) (+ arg0 1))",
+        );
+        assert_simplifies(call(Func::Sub(sum, int(1))), "(- (+ arg0 1) 1)");
+        let never = call(Func::Lt(overflow, int(0)));
+        assert_simplifies(
+            call(Func::And(constant(StrVal::Bool(false)), never)),
+            "(and false (< (+ 9223372036854775807 1) 0))",
+        );
+    }
+
+    #[test]
+    fn test_normal_form() {
+        let len = || call(Func::Len(arg(0)));
+        assert_simplifies(
+            call(Func::Sub(call(Func::Add(int(1), len())), int(4))),
+            "(- (len arg0) 3)",
+        );
+        assert_simplifies(call(Func::Add(len(), int(-3))), "(- (len arg0) 3)");
+        assert_simplifies(
+            call(Func::Sub(call(Func::Sub(len(), int(2))), int(-2))),
+            "(len arg0)",
+        );
+        assert_simplifies(call(Func::Eq(string("a"), arg(0))), "(= arg0 \"a\")");
+        let digit = call(Func::IsDigit(arg(0)));
+        assert_simplifies(
+            Rc::new(Expr::If(call(Func::Not(digit)), arg(0), arg(1))),
+            "(if (is_digit arg0) arg1 arg0)",
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use crate::linear::LinearExpr;
+use crate::strlenlat::StrLenLat;
+use crate::sygus::{ParseResult, SExpr};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::mem::ManuallyDrop;
+use z3::ast::{Ast, Bool, Int};
+use z3::{Config, Context, SatResult, Solver};
+use z3_sys::{
+    Z3_context, Z3_del_config, Z3_del_context, Z3_eval_smtlib2_string, Z3_mk_config, Z3_mk_context,
+    Z3_set_error_handler,
+};
+
+// creating a solver costs far more than the small queries made during search,
+// so each thread keeps one until it exits and scopes every query with
+// push/pop; search also asks the same few questions over and over, so
+// answers are remembered, up to MAX_ANSWERS of them
+thread_local! {
+    static ORACLE: Oracle = Oracle::new();
+    static ANSWERS: RefCell<HashMap<Query, bool>> = RefCell::new(HashMap::new());
+}
+
+// a solver and the context it is made from, which it borrows; the context
+// lives on the heap and is only freed after the solver
+struct Oracle {
+    solver: ManuallyDrop<Solver<'static>>,
+    ctx: *mut Context,
+}
+
+impl Oracle {
+    fn new() -> Oracle {
+        let ctx = Box::into_raw(Box::new(Context::new(&Config::new())));
+        let solver = ManuallyDrop::new(Solver::new(unsafe { &*ctx }));
+        Oracle { solver, ctx }
+    }
+}
+
+impl Drop for Oracle {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.solver);
+            drop(Box::from_raw(self.ctx));
+        }
+    }
+}
+
+const MAX_ANSWERS: usize = 1 << 16;
+
+/// Forgets the answers remembered on this thread. They only depend on the
+/// question, so this is never needed for correctness, but a long-running
+/// process can use it to release the memory between unrelated problems.
+pub fn clear_answers() {
+    ANSWERS.with(|a| a.borrow_mut().clear());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Query {
+    Feasible(LinearExpr),
+    Equivalent(LinearExpr, LinearExpr),
+    Unreachable(LinearExpr, LinearExpr, LinearExpr),
+}
+
+/// Translates linear expressions into Z3 integers. The variables of a linear
+/// expression are lengths of the inputs, so each one is declared once and
+/// bounded below by zero.
+struct Encoder<'ctx> {
+    ctx: &'ctx Context,
+    vars: BTreeMap<String, Int<'ctx>>,
+}
+
+impl<'ctx> Encoder<'ctx> {
+    fn new(ctx: &'ctx Context) -> Self {
+        Encoder {
+            ctx,
+            vars: BTreeMap::new(),
+        }
+    }
+
+    fn int(&mut self, expr: &LinearExpr) -> Int<'ctx> {
+        let ctx = self.ctx;
+        let mut sum = vec![Int::from_i64(ctx, expr.constant())];
+        for (x, k) in expr.terms() {
+            let var = self
+                .vars
+                .entry(x.to_string())
+                .or_insert_with(|| Int::new_const(ctx, x));
+            sum.push(Int::mul(ctx, &[&Int::from_i64(ctx, k), var]));
+        }
+        Int::add(ctx, &sum.iter().collect::<Vec<_>>())
+    }
+
+    fn zero(&self) -> Int<'ctx> {
+        Int::from_i64(self.ctx, 0)
+    }
+
+    fn domain(&self) -> Bool<'ctx> {
+        let zero = self.zero();
+        let bounds: Vec<_> = self.vars.values().map(|v| v.ge(&zero)).collect();
+        Bool::and(self.ctx, &bounds.iter().collect::<Vec<_>>())
+    }
+}
+
+// an unknown answer counts as satisfiable, so nothing is pruned on a guess
+fn sat<F>(query: Query, formula: F) -> bool
+where
+    F: for<'ctx> FnOnce(&mut Encoder<'ctx>) -> Bool<'ctx>,
+{
+    if let Some(answer) = ANSWERS.with(|a| a.borrow().get(&query).copied()) {
+        return answer;
+    }
+    let answer = ORACLE.with(|oracle| {
+        // terms only live for this query, not as long as the solver
+        let solver: &Solver = &oracle.solver;
+        let mut enc = Encoder::new(solver.get_context());
+        let formula = formula(&mut enc);
+        solver.push();
+        solver.assert(&enc.domain());
+        solver.assert(&formula);
+        let answer = solver.check() != SatResult::Unsat;
+        solver.pop(1);
+        answer
+    });
+    ANSWERS.with(|a| {
+        let mut answers = a.borrow_mut();
+        if answers.len() >= MAX_ANSWERS {
+            answers.clear();
+        }
+        answers.insert(query, answer);
+    });
+    answer
+}
+
+/// Whether some input lengths make `lat` a valid string length.
+pub fn feasible(lat: &StrLenLat) -> bool {
+    match lat {
+        StrLenLat::Top => true,
+        StrLenLat::Bot => false,
+        StrLenLat::Len(l) => match l.as_const() {
+            Some(c) => c >= 0,
+            None => sat(Query::Feasible(l.clone()), |enc| enc.int(l).ge(&enc.zero())),
+        },
+    }
+}
+
+/// Whether two lengths agree on all inputs.
+pub fn equivalent(l1: &LinearExpr, l2: &LinearExpr) -> bool {
+    !sat(Query::Equivalent(l1.clone(), l2.clone()), |enc| {
+        let (l1, l2) = (enc.int(l1), enc.int(l2));
+        l1._eq(&l2).not()
+    })
+}
+
+/// The length the hole in `append(prefix, □)` needs for the result to have
+/// length `target`, or `None` if no input makes that possible.
+pub fn append_inv(target: &StrLenLat, prefix: &StrLenLat) -> Option<StrLenLat> {
+    let hole = target.clone().checked_sub(prefix.clone())?;
+    Some(hole).filter(feasible)
+}
+
+/// The length the hole in `substr(□, start, len)` needs for the result to
+/// have length `target`. Whenever the target is a length, some string must
+/// produce it; if the substring is not always `len` long it has to run off
+/// the end of the string, which fixes the length at `start + target`.
+pub fn substr_inv(target: &StrLenLat, start: &LinearExpr, len: &LinearExpr) -> Option<StrLenLat> {
+    let t = match target {
+        StrLenLat::Top => return Some(StrLenLat::Top),
+        StrLenLat::Bot => return None,
+        StrLenLat::Len(t) => t,
+    };
+    let query = Query::Unreachable(t.clone(), start.clone(), len.clone());
+    // (str.substr s i n) is empty when i < 0 or n <= 0, and otherwise takes
+    // any length from 0 to n as the length of s varies
+    let unreachable = sat(query, |enc| {
+        let (t, i, n) = (enc.int(t), enc.int(start), enc.int(len));
+        let zero = enc.zero();
+        let reaches = Bool::or(
+            enc.ctx,
+            &[
+                &t._eq(&zero),
+                &Bool::and(enc.ctx, &[&i.ge(&zero), &t.le(&n)]),
+            ],
+        );
+        Bool::and(enc.ctx, &[&t.ge(&zero), &reaches.not()])
+    });
+    if unreachable {
+        None
+    } else if equivalent(t, len) {
+        Some(StrLenLat::Top)
+    } else {
+        start.clone().checked_add(t.clone()).map(StrLenLat::Len)
+    }
+}
+
+/// A Z3 context driven with SMT-LIB commands. The typed API has no string
+/// operations beyond concatenation and a few predicates, so queries over
+/// the string theory are written as scripts instead.
+pub struct Session {
+    ctx: Z3_context,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Session {
+        unsafe {
+            let cfg = Z3_mk_config();
+            let ctx = Z3_mk_context(cfg);
+            Z3_del_config(cfg);
+            // errors show up in the responses rather than aborting
+            Z3_set_error_handler(ctx, None);
+            Session { ctx }
+        }
+    }
+
+    /// Runs the commands and returns the responses Z3 prints, in order.
+    /// Declarations persist across calls.
+    pub fn run(&mut self, commands: &[SExpr]) -> ParseResult<Vec<SExpr>> {
+        let script = commands
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let script = CString::new(script).map_err(|e| e.to_string())?;
+        let output = unsafe {
+            CStr::from_ptr(Z3_eval_smtlib2_string(self.ctx, script.as_ptr()))
+                .to_string_lossy()
+                .into_owned()
+        };
+        let responses = SExpr::parse_all(&output)?;
+        for r in &responses {
+            if let Some([SExpr::Atom(e), msg]) = r.as_list() {
+                if e == "error" {
+                    return Err(format!("z3: {}", msg));
+                }
+            }
+        }
+        Ok(responses)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        unsafe { Z3_del_context(self.ctx) }
+    }
+}
+
+/// Decodes the `\u{...}` and `\uXXXX` escapes Z3 uses when printing string
+/// values.
+pub fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(idx) = rest.find("\\u") {
+        out.push_str(&rest[..idx]);
+        let after = &rest[idx + 2..];
+        let (hex, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => (after.get(..4).unwrap_or(""), 4),
+        };
+        match u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+            Some(c) if len > 0 => {
+                out.push(c);
+                rest = &after[len..];
+            }
+            _ => {
+                out.push_str("\\u");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn x() -> LinearExpr {
+        LinearExpr::from("x".to_string())
+    }
+
+    // x - k
+    fn x_minus(k: i64) -> LinearExpr {
+        x().checked_sub(LinearExpr::from(k)).unwrap()
+    }
+
+    #[test]
+    fn test_clear_answers() {
+        let lat = StrLenLat::from(x_minus(1));
+        assert!(feasible(&lat));
+        assert_eq!(ANSWERS.with(|a| a.borrow().len()), 1);
+        clear_answers();
+        assert_eq!(ANSWERS.with(|a| a.borrow().len()), 0);
+        // the solver outlives the answers
+        assert!(feasible(&lat));
+    }
+
+    #[test]
+    fn test_feasible() {
+        assert!(feasible(&StrLenLat::from(x_minus(3))));
+        assert!(feasible(&StrLenLat::Top));
+        assert!(!feasible(&StrLenLat::Bot));
+        assert!(!feasible(&StrLenLat::from(-1)));
+        assert!(!feasible(&StrLenLat::from(
+            LinearExpr::from(-1)
+                .checked_mul(x())
+                .and_then(|e| e.checked_sub(LinearExpr::from(1)))
+                .unwrap()
+        )));
+        assert!(equivalent(
+            &x().checked_add(x()).unwrap(),
+            &LinearExpr::from(2).checked_mul(x()).unwrap()
+        ));
+        assert!(!equivalent(&x(), &LinearExpr::from(3)));
+    }
+
+    #[test]
+    fn test_append_inv() {
+        let target = StrLenLat::from(x_minus(3));
+        assert_eq!(
+            append_inv(&target, &StrLenLat::from(2)),
+            Some(StrLenLat::from(x_minus(5)))
+        );
+        assert_eq!(append_inv(&target, &StrLenLat::from(x())), None);
+        assert_eq!(append_inv(&StrLenLat::from(2), &StrLenLat::from(3)), None);
+        assert_eq!(
+            append_inv(&StrLenLat::Top, &StrLenLat::from(3)),
+            Some(StrLenLat::Top)
+        );
+    }
+
+    #[test]
+    fn test_substr_inv() {
+        let target = StrLenLat::from(x_minus(3));
+        let zero = LinearExpr::from(0);
+        assert_eq!(
+            substr_inv(&target, &zero, &x_minus(3)),
+            Some(StrLenLat::Top)
+        );
+        // a substring of at most 5 characters cannot be x - 3 long for all x
+        assert_eq!(substr_inv(&target, &zero, &LinearExpr::from(5)), None);
+        // running off the end of the string fixes its length
+        assert_eq!(
+            substr_inv(
+                &StrLenLat::from(3),
+                &LinearExpr::from(1),
+                &LinearExpr::from(5)
+            ),
+            Some(StrLenLat::from(4))
+        );
+        assert_eq!(
+            substr_inv(&StrLenLat::from(3), &zero, &LinearExpr::from(-1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new();
+        let script = SExpr::parse_all(
+            r#"(declare-const s String)
+               (assert (= (str.len s) 2))
+               (assert (str.prefixof "a" s))
+               (assert (= (str.at s 1) (str.from_code 233)))
+               (check-sat)
+               (get-value (s))"#,
+        )
+        .unwrap();
+        let responses = session.run(&script).unwrap();
+        assert_eq!(responses[0], SExpr::Atom("sat".to_string()));
+        match responses[1].as_list() {
+            Some([SExpr::List(binding)]) => match binding.as_slice() {
+                [_, SExpr::Str(s)] => assert_eq!(unescape(s), "aé"),
+                _ => panic!("unexpected binding {:?}", binding),
+            },
+            _ => panic!("unexpected response {}", responses[1]),
+        }
+        let bad = SExpr::parse_all("(assert (str.len 1))").unwrap();
+        assert!(session.run(&bad).is_err());
+        assert_eq!(unescape("a\\u{62}\\u0063\\ud"), "abc\\ud");
+    }
+}
@@ -0,0 +1,149 @@
+use crate::concrete::StrVal;
+use crate::environment::Environment;
+use crate::interpreter::{EvalResult, Evaluable};
+use crate::syguslang::Expr;
+use crate::values::Lattice;
+
+pub type CandidateFn = Box<dyn Fn(&[StrVal]) -> EvalResult<StrVal>>;
+pub type TestFn = Box<dyn Fn(CandidateFn) -> bool>;
+
+/// Binds the i-th input to `argi`, the naming used for synthesized programs.
+pub fn bind_args(args: &[StrVal]) -> Environment<StrVal> {
+    let mut env = Environment::new();
+    args.iter()
+        .enumerate()
+        .for_each(|(idx, val)| env.put(format!("arg{}", idx), val.clone()));
+    env
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    pub inputs: Vec<StrVal>,
+    pub output: StrVal,
+}
+
+impl Example {
+    pub fn new(inputs: Vec<StrVal>, output: StrVal) -> Example {
+        Example { inputs, output }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Examples {
+    examples: Vec<Example>,
+}
+
+impl From<Vec<Example>> for Examples {
+    fn from(examples: Vec<Example>) -> Self {
+        Examples { examples }
+    }
+}
+
+impl Examples {
+    pub fn new() -> Examples {
+        Self::default()
+    }
+
+    pub fn add(&mut self, inputs: Vec<StrVal>, output: StrVal) {
+        self.examples.push(Example::new(inputs, output));
+    }
+
+    pub fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Example> {
+        self.examples.iter()
+    }
+
+    /// What the program computes on each example's inputs.
+    pub fn outputs<U: Lattice>(&self, prog: &Expr<StrVal, U>) -> Vec<EvalResult<StrVal>> {
+        self.examples
+            .iter()
+            .map(|e| prog.eval(&bind_args(&e.inputs)))
+            .collect()
+    }
+
+    /// Pass/fail of the program on every example, in order.
+    pub fn check<U: Lattice>(&self, prog: &Expr<StrVal, U>) -> Vec<bool> {
+        self.examples
+            .iter()
+            .map(|e| prog.eval(&bind_args(&e.inputs)).as_ref() == Ok(&e.output))
+            .collect()
+    }
+
+    pub fn passes<U: Lattice>(&self, prog: &Expr<StrVal, U>) -> bool {
+        self.examples
+            .iter()
+            .all(|e| prog.eval(&bind_args(&e.inputs)).as_ref() == Ok(&e.output))
+    }
+}
+
+pub enum Spec {
+    Test(TestFn),
+    Examples(Examples),
+}
+
+impl From<Examples> for Spec {
+    fn from(examples: Examples) -> Self {
+        Spec::Examples(examples)
+    }
+}
+
+impl From<TestFn> for Spec {
+    fn from(test: TestFn) -> Self {
+        Spec::Test(test)
+    }
+}
+
+impl Spec {
+    pub fn examples(&self) -> Option<&Examples> {
+        match self {
+            Spec::Examples(e) => Some(e),
+            Spec::Test(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syguslang::Func;
+    use crate::types::TypeLattice;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_check() {
+        let mut examples = Examples::new();
+        examples.add(
+            vec![StrVal::from("ab".to_string())],
+            StrVal::from("a".to_string()),
+        );
+        examples.add(
+            vec![StrVal::from("cd".to_string())],
+            StrVal::from("d".to_string()),
+        );
+        examples.add(vec![StrVal::from(3)], StrVal::from("3".to_string()));
+
+        let prog: Expr<StrVal, TypeLattice> = Expr::Call(Func::At(
+            Rc::new(Expr::Var("arg0".to_string())),
+            Rc::new(Expr::Const(StrVal::from(0))),
+        ));
+
+        assert_eq!(examples.len(), 3);
+        assert_eq!(examples.check(&prog), vec![true, false, false]);
+        assert!(!examples.passes(&prog));
+        assert_eq!(
+            examples.outputs(&prog)[..2],
+            [
+                Ok(StrVal::from("a".to_string())),
+                Ok(StrVal::from("c".to_string()))
+            ]
+        );
+        assert!(examples.outputs(&prog)[2].is_err());
+    }
+}
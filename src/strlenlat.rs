@@ -3,7 +3,6 @@ use crate::values::Lattice;
 use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Display;
-use std::ops::{Add, Sub};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum StrLenLat {
@@ -13,6 +12,22 @@ pub enum StrLenLat {
 }
 
 impl Lattice for StrLenLat {
+    fn meet(&self, other: &Self) -> Self {
+        match (self, other) {
+            (StrLenLat::Top, l) | (l, StrLenLat::Top) => l.clone(),
+            (StrLenLat::Len(l1), StrLenLat::Len(l2)) if l1 == l2 => self.clone(),
+            _ => StrLenLat::Bot,
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (StrLenLat::Bot, l) | (l, StrLenLat::Bot) => l.clone(),
+            (StrLenLat::Len(l1), StrLenLat::Len(l2)) if l1 == l2 => self.clone(),
+            _ => StrLenLat::Top,
+        }
+    }
+
     fn top() -> Self {
         Self::Top
     }
@@ -50,30 +65,26 @@ impl From<String> for StrLenLat {
     }
 }
 
-impl Sub for StrLenLat {
-    type Output = Self;
-
-    fn sub(self, other: Self) -> Self {
+impl StrLenLat {
+    /// `None` if the constant or a coefficient of the sum overflows.
+    pub fn checked_add(self, other: Self) -> Option<Self> {
         match (self, other) {
-            (StrLenLat::Top, _) => StrLenLat::Top,
-            (StrLenLat::Len(_), StrLenLat::Top) => StrLenLat::Top,
-            (StrLenLat::Len(l1), StrLenLat::Len(l2)) => StrLenLat::Len(l1 - l2),
-            (StrLenLat::Len(_), StrLenLat::Bot) => StrLenLat::Bot,
-            (StrLenLat::Bot, _) => StrLenLat::Bot,
+            (StrLenLat::Top, _) => Some(StrLenLat::Top),
+            (StrLenLat::Len(_), StrLenLat::Top) => Some(StrLenLat::Top),
+            (StrLenLat::Len(l1), StrLenLat::Len(l2)) => l1.checked_add(l2).map(StrLenLat::Len),
+            (StrLenLat::Len(_), StrLenLat::Bot) => Some(StrLenLat::Bot),
+            (StrLenLat::Bot, _) => Some(StrLenLat::Bot),
         }
     }
-}
-
-impl Add for StrLenLat {
-    type Output = Self;
 
-    fn add(self, other: Self) -> Self {
+    /// `None` if the constant or a coefficient of the difference overflows.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
         match (self, other) {
-            (StrLenLat::Top, _) => StrLenLat::Top,
-            (StrLenLat::Len(_), StrLenLat::Top) => StrLenLat::Top,
-            (StrLenLat::Len(l1), StrLenLat::Len(l2)) => StrLenLat::Len(l1 + l2),
-            (StrLenLat::Len(_), StrLenLat::Bot) => StrLenLat::Bot,
-            (StrLenLat::Bot, _) => StrLenLat::Bot,
+            (StrLenLat::Top, _) => Some(StrLenLat::Top),
+            (StrLenLat::Len(_), StrLenLat::Top) => Some(StrLenLat::Top),
+            (StrLenLat::Len(l1), StrLenLat::Len(l2)) => l1.checked_sub(l2).map(StrLenLat::Len),
+            (StrLenLat::Len(_), StrLenLat::Bot) => Some(StrLenLat::Bot),
+            (StrLenLat::Bot, _) => Some(StrLenLat::Bot),
         }
     }
 }
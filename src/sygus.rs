@@ -0,0 +1,1111 @@
+use crate::concrete::StrVal;
+use crate::environment::Environment;
+use crate::grammar::{Grammar, NonTerminal};
+use crate::interpreter::{EvalError, EvalResult, Evaluable};
+use crate::regex::Regex;
+use crate::spec::{Example, Examples};
+use crate::syguslang::{Expr, Func, Term};
+use crate::types::{TypeLattice, TypeValues};
+use crate::values::{Lattice, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::Chars;
+
+pub type ParseResult<T> = Result<T, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SExpr {
+    Atom(String),
+    Str(String),
+    Int(i64),
+    List(Vec<SExpr>),
+}
+
+impl Display for SExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Atom(a) => write!(f, "{}", a),
+            Self::Str(s) => write!(f, "\"{}\"", s.replace('"', "\"\"")),
+            Self::Int(i) if *i < 0 => write!(f, "(- {})", i.unsigned_abs()),
+            Self::Int(i) => write!(f, "{}", i),
+            Self::List(items) => write!(
+                f,
+                "({})",
+                items
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
+
+impl SExpr {
+    pub fn parse_all(src: &str) -> ParseResult<Vec<SExpr>> {
+        let mut chars = src.chars().peekable();
+        let mut exprs = vec![];
+        loop {
+            Self::skip_whitespace(&mut chars);
+            match chars.peek() {
+                None => return Ok(exprs),
+                Some(')') => return Err("unbalanced ')'".to_string()),
+                Some(_) => exprs.push(Self::parse_one(&mut chars)?),
+            }
+        }
+    }
+
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while let Some(&c) = chars.peek() {
+            if c == ';' {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            } else if c.is_whitespace() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_one(chars: &mut Peekable<Chars>) -> ParseResult<SExpr> {
+        Self::skip_whitespace(chars);
+        match chars.next() {
+            None => Err("unexpected end of input".to_string()),
+            Some('(') => {
+                let mut items = vec![];
+                loop {
+                    Self::skip_whitespace(chars);
+                    match chars.peek() {
+                        None => return Err("unclosed '('".to_string()),
+                        Some(')') => {
+                            chars.next();
+                            let list = Self::List(items);
+                            return match list.as_list() {
+                                // -i64::MIN has no 64-bit counterpart
+                                Some([Self::Atom(op), Self::Int(i)])
+                                    if op == "-" && i.checked_neg().is_none() =>
+                                {
+                                    Err(format!("integer literal out of range: {}", list))
+                                }
+                                _ => Ok(list),
+                            };
+                        }
+                        Some(_) => items.push(Self::parse_one(chars)?),
+                    }
+                }
+            }
+            Some(')') => Err("unexpected ')'".to_string()),
+            Some('"') => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err("unterminated string literal".to_string()),
+                        // SMT-LIB 2.6 escapes a quote by doubling it
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            s.push('"');
+                        }
+                        Some('"') => return Ok(Self::Str(s)),
+                        Some(c) => s.push(c),
+                    }
+                }
+            }
+            Some(c) => {
+                let mut atom = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' || c == ';' {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                Ok(atom
+                    .parse::<i64>()
+                    .map_or_else(|_| Self::Atom(atom), Self::Int))
+            }
+        }
+    }
+
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            Self::Atom(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[SExpr]> {
+        match self {
+            Self::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Interprets a literal term (string, integer, boolean or a negated
+    /// integer written as `(- n)`) as a concrete value.
+    pub fn as_literal(&self) -> Option<StrVal> {
+        match self {
+            Self::Str(s) => Some(StrVal::from(s.clone())),
+            Self::Int(i) => Some(StrVal::from(*i)),
+            Self::Atom(a) if a == "true" => Some(StrVal::Bool(true)),
+            Self::Atom(a) if a == "false" => Some(StrVal::Bool(false)),
+            Self::List(items) => match items.as_slice() {
+                [Self::Atom(op), Self::Int(i)] if op == "-" => i.checked_neg().map(StrVal::from),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl From<&StrVal> for SExpr {
+    fn from(value: &StrVal) -> Self {
+        match value {
+            StrVal::Str(s) => SExpr::Str(s.clone()),
+            StrVal::Int(i) => SExpr::Int(*i),
+            StrVal::Linear(e) => SExpr::Atom(e.to_string()),
+            StrVal::Bool(b) => SExpr::Atom(b.to_string()),
+        }
+    }
+}
+
+impl From<&Regex> for SExpr {
+    fn from(value: &Regex) -> Self {
+        let app = |op: &str, args: Vec<SExpr>| {
+            SExpr::List(
+                std::iter::once(SExpr::Atom(op.to_string()))
+                    .chain(args)
+                    .collect(),
+            )
+        };
+        let all = |rs: &[Regex]| rs.iter().map(SExpr::from).collect();
+        match value {
+            Regex::None => SExpr::Atom("re.none".to_string()),
+            Regex::All => SExpr::Atom("re.all".to_string()),
+            Regex::AllChar => SExpr::Atom("re.allchar".to_string()),
+            Regex::Str(s) => app("str.to_re", vec![SExpr::Str(s.clone())]),
+            Regex::Range(lo, hi) => app(
+                "re.range",
+                vec![SExpr::Str(lo.to_string()), SExpr::Str(hi.to_string())],
+            ),
+            Regex::Concat(rs) => app("re.++", all(rs)),
+            Regex::Union(rs) => app("re.union", all(rs)),
+            Regex::Inter(rs) => app("re.inter", all(rs)),
+            Regex::Star(r) => app("re.*", vec![SExpr::from(&**r)]),
+            Regex::Plus(r) => app("re.+", vec![SExpr::from(&**r)]),
+            Regex::Opt(r) => app("re.opt", vec![SExpr::from(&**r)]),
+            Regex::Comp(r) => app("re.comp", vec![SExpr::from(&**r)]),
+            Regex::Diff(r1, r2) => app("re.diff", vec![SExpr::from(&**r1), SExpr::from(&**r2)]),
+            Regex::Loop(r, lo, hi) => SExpr::List(vec![
+                SExpr::List(vec![
+                    SExpr::Atom("_".to_string()),
+                    SExpr::Atom("re.loop".to_string()),
+                    SExpr::Int(*lo as i64),
+                    SExpr::Int(*hi as i64),
+                ]),
+                SExpr::from(&**r),
+            ]),
+        }
+    }
+}
+
+/// Reads a closed regular expression. Regexes built from variables or
+/// nonterminals are not supported.
+pub fn parse_regex(re: &SExpr) -> ParseResult<Regex> {
+    let unsupported = || format!("unsupported regular expression {}", re);
+    let single = |s: &SExpr| match s {
+        SExpr::Str(s) if s.chars().count() == 1 => s.chars().next(),
+        _ => None,
+    };
+    let (op, args) = match re {
+        SExpr::Atom(a) => {
+            return match a.as_str() {
+                "re.none" | "re.nostr" => Ok(Regex::None),
+                "re.all" => Ok(Regex::All),
+                "re.allchar" => Ok(Regex::AllChar),
+                _ => Err(unsupported()),
+            }
+        }
+        SExpr::List(items) => items.split_first().ok_or_else(unsupported)?,
+        _ => return Err(unsupported()),
+    };
+    let boxed = |i: usize| {
+        args.get(i)
+            .map(parse_regex)
+            .ok_or_else(unsupported)?
+            .map(Box::new)
+    };
+    let all = || {
+        args.iter()
+            .map(parse_regex)
+            .collect::<ParseResult<Vec<_>>>()
+    };
+    let count = |n: &i64| u32::try_from(*n).map_err(|_| unsupported());
+    match (op, args) {
+        (SExpr::List(indexed), [r]) => match indexed.as_slice() {
+            [SExpr::Atom(u), SExpr::Atom(l), SExpr::Int(lo), SExpr::Int(hi)]
+                if u == "_" && l == "re.loop" =>
+            {
+                Ok(Regex::Loop(
+                    Box::new(parse_regex(r)?),
+                    count(lo)?,
+                    count(hi)?,
+                ))
+            }
+            [SExpr::Atom(u), SExpr::Atom(p), SExpr::Int(n)] if u == "_" && p == "re.^" => {
+                Ok(Regex::Loop(Box::new(parse_regex(r)?), count(n)?, count(n)?))
+            }
+            _ => Err(unsupported()),
+        },
+        (SExpr::Atom(op), args) => match (op.as_str(), args) {
+            ("str.to_re", [SExpr::Str(s)]) | ("str.to.re", [SExpr::Str(s)]) => {
+                Ok(Regex::Str(s.clone()))
+            }
+            // a range whose bounds are not single characters is empty
+            ("re.range", [lo, hi]) => Ok(single(lo)
+                .zip(single(hi))
+                .map_or(Regex::None, |(lo, hi)| Regex::Range(lo, hi))),
+            ("re.++", _) => Ok(Regex::Concat(all()?)),
+            ("re.union", _) => Ok(Regex::Union(all()?)),
+            ("re.inter", _) => Ok(Regex::Inter(all()?)),
+            ("re.*", [_]) => Ok(Regex::Star(boxed(0)?)),
+            ("re.+", [_]) => Ok(Regex::Plus(boxed(0)?)),
+            ("re.opt", [_]) => Ok(Regex::Opt(boxed(0)?)),
+            ("re.comp", [_]) => Ok(Regex::Comp(boxed(0)?)),
+            ("re.diff", [_, _]) => Ok(Regex::Diff(boxed(0)?, boxed(1)?)),
+            _ => Err(unsupported()),
+        },
+        _ => Err(unsupported()),
+    }
+}
+
+pub fn parse_sort(sort: &SExpr) -> ParseResult<TypeLattice> {
+    match sort.as_atom() {
+        Some("String") => Ok(TypeLattice::String),
+        Some("Int") => Ok(TypeLattice::Integer),
+        Some("Bool") => Ok(TypeLattice::Bool),
+        _ => Err(format!("unsupported sort {}", sort)),
+    }
+}
+
+pub fn sort_name(sort: &TypeLattice) -> &'static str {
+    match sort {
+        TypeLattice::String => "String",
+        TypeLattice::Integer => "Int",
+        TypeLattice::Bool => "Bool",
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub sort: TypeLattice,
+    pub productions: Vec<SExpr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynthFun {
+    pub name: String,
+    pub params: Vec<(String, TypeLattice)>,
+    pub ret: TypeLattice,
+    pub grammar: Vec<Rule>,
+}
+
+// the parameters and body of a define-fun
+struct Macro {
+    params: Vec<String>,
+    body: SExpr,
+}
+
+// replaces every call of a macro with its body, the parameters bound to the
+// (inlined) arguments
+fn inline(expr: &SExpr, macros: &HashMap<String, Macro>) -> SExpr {
+    let (name, args) = match expr {
+        SExpr::Atom(name) => (name, &[][..]),
+        SExpr::List(items) => match items.split_first() {
+            Some((SExpr::Atom(name), args)) if macros.contains_key(name) => (name, args),
+            _ => return SExpr::List(items.iter().map(|i| inline(i, macros)).collect()),
+        },
+        _ => return expr.clone(),
+    };
+    match macros.get(name) {
+        Some(m) if m.params.len() == args.len() => {
+            let bindings: HashMap<&str, SExpr> = m
+                .params
+                .iter()
+                .map(String::as_str)
+                .zip(args.iter().map(|a| inline(a, macros)))
+                .collect();
+            substitute(&m.body, &bindings)
+        }
+        _ => expr.clone(),
+    }
+}
+
+fn substitute(expr: &SExpr, bindings: &HashMap<&str, SExpr>) -> SExpr {
+    match expr {
+        SExpr::Atom(x) => bindings
+            .get(x.as_str())
+            .cloned()
+            .unwrap_or_else(|| expr.clone()),
+        SExpr::List(items) => SExpr::List(items.iter().map(|i| substitute(i, bindings)).collect()),
+        _ => expr.clone(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub logic: String,
+    pub synth_fun: SynthFun,
+    pub vars: Vec<(String, TypeLattice)>,
+    pub constraints: Vec<SExpr>,
+}
+
+impl Problem {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> ParseResult<Problem> {
+        let src = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        Self::parse(&src)
+    }
+
+    /// `define-fun` macros are inlined into the constraints and grammar
+    /// productions that follow them; `set-option` and `set-info` are ignored.
+    pub fn parse(src: &str) -> ParseResult<Problem> {
+        let mut logic = None;
+        let mut synth_fun = None;
+        let mut vars = vec![];
+        let mut constraints = vec![];
+        let mut macros = HashMap::new();
+
+        for cmd in SExpr::parse_all(src)? {
+            let items = cmd
+                .as_list()
+                .ok_or_else(|| format!("expected a command, found {}", cmd))?;
+            match items.first().and_then(|c| c.as_atom()) {
+                Some("set-logic") => match items {
+                    [_, SExpr::Atom(l)] => logic = Some(l.clone()),
+                    _ => return Err(format!("malformed set-logic: {}", cmd)),
+                },
+                Some("synth-fun") => {
+                    if synth_fun.is_some() {
+                        return Err("only a single synth-fun is supported".to_string());
+                    }
+                    synth_fun = Some(Self::parse_synth_fun(items, &macros)?)
+                }
+                Some("declare-var") => match items {
+                    [_, SExpr::Atom(name), sort] => vars.push((name.clone(), parse_sort(sort)?)),
+                    _ => return Err(format!("malformed declare-var: {}", cmd)),
+                },
+                // a nullary declare-fun is a universally quantified variable
+                Some("declare-fun") => match items {
+                    [_, SExpr::Atom(name), SExpr::List(params), sort] if params.is_empty() => {
+                        vars.push((name.clone(), parse_sort(sort)?))
+                    }
+                    _ => return Err(format!("unsupported declare-fun: {}", cmd)),
+                },
+                Some("define-fun") => match items {
+                    [_, SExpr::Atom(name), SExpr::List(params), _, body] => {
+                        let params = params
+                            .iter()
+                            .map(|p| match p.as_list() {
+                                Some([SExpr::Atom(n), _]) => Ok(n.clone()),
+                                _ => Err(format!("malformed parameter {}", p)),
+                            })
+                            .collect::<ParseResult<Vec<_>>>()?;
+                        let body = inline(body, &macros);
+                        macros.insert(name.clone(), Macro { params, body });
+                    }
+                    _ => return Err(format!("malformed define-fun: {}", cmd)),
+                },
+                Some("constraint") => match items {
+                    [_, c] => constraints.push(inline(c, &macros)),
+                    _ => return Err(format!("malformed constraint: {}", cmd)),
+                },
+                Some("set-option") | Some("set-info") | Some("check-synth") => {}
+                _ => return Err(format!("unsupported command {}", cmd)),
+            }
+        }
+
+        Ok(Problem {
+            logic: logic.ok_or("missing set-logic")?,
+            synth_fun: synth_fun.ok_or("missing synth-fun")?,
+            vars,
+            constraints,
+        })
+    }
+
+    fn parse_synth_fun(items: &[SExpr], macros: &HashMap<String, Macro>) -> ParseResult<SynthFun> {
+        let (name, params, ret, grammar) = match items {
+            [_, SExpr::Atom(name), SExpr::List(params), ret] => (name, params, ret, None),
+            [_, SExpr::Atom(name), SExpr::List(params), ret, SExpr::List(grammar)] => {
+                (name, params, ret, Some(grammar))
+            }
+            // SyGuS 2 lists the nonterminals before the grammar
+            [_, SExpr::Atom(_), SExpr::List(_), _, SExpr::List(_), SExpr::List(_)] => {
+                return Err(
+                    "unsupported synth-fun: grammars with predeclared nonterminals".to_string(),
+                )
+            }
+            _ => return Err("malformed synth-fun".to_string()),
+        };
+
+        let params = params
+            .iter()
+            .map(|p| match p.as_list() {
+                Some([SExpr::Atom(n), sort]) => Ok((n.clone(), parse_sort(sort)?)),
+                _ => Err(format!("malformed parameter {}", p)),
+            })
+            .collect::<ParseResult<Vec<_>>>()?;
+
+        let grammar = grammar.map_or_else(
+            || Ok(vec![]),
+            |g| g.iter().map(|nt| Self::parse_rule(nt, macros)).collect(),
+        )?;
+
+        Ok(SynthFun {
+            name: name.clone(),
+            params,
+            ret: parse_sort(ret)?,
+            grammar,
+        })
+    }
+
+    fn parse_rule(nt: &SExpr, macros: &HashMap<String, Macro>) -> ParseResult<Rule> {
+        match nt.as_list() {
+            Some([SExpr::Atom(name), sort, SExpr::List(productions)]) => {
+                // any constant or any variable of a sort, rather than a list
+                let open = productions.iter().find(|p| match p.as_list() {
+                    Some([SExpr::Atom(k), _]) => k == "Constant" || k == "Variable",
+                    _ => false,
+                });
+                if let Some(p) = open {
+                    return Err(format!("unsupported grammar production {}", p));
+                }
+                Ok(Rule {
+                    name: name.clone(),
+                    sort: parse_sort(sort)?,
+                    productions: productions.iter().map(|p| inline(p, macros)).collect(),
+                })
+            }
+            _ => Err(format!("malformed grammar rule {}", nt)),
+        }
+    }
+
+    /// Every literal that appears as a production in the grammar, in order of
+    /// first appearance.
+    pub fn consts(&self) -> Vec<StrVal> {
+        let mut consts: Vec<StrVal> = vec![];
+        self.synth_fun
+            .grammar
+            .iter()
+            .flat_map(|nt| nt.productions.iter())
+            .filter_map(|p| p.as_literal())
+            .for_each(|c| {
+                if !consts.contains(&c) {
+                    consts.push(c)
+                }
+            });
+        consts
+    }
+
+    /// The synthesizer binds the i-th argument of a candidate to `argi`, so
+    /// the parameters are renamed positionally. Use `param_name` to map them
+    /// back to the names used in the problem.
+    pub fn env(&self) -> Environment<TypeValues> {
+        let mut env = Environment::new();
+        self.synth_fun
+            .params
+            .iter()
+            .enumerate()
+            .for_each(|(idx, (_, sort))| {
+                env.put(format!("arg{}", idx), TypeValues::Abs(sort.clone()))
+            });
+        env
+    }
+
+    pub fn param_name(&self, var: &str) -> Option<&str> {
+        var.strip_prefix("arg")
+            .and_then(|idx| idx.parse::<usize>().ok())
+            .and_then(|idx| self.synth_fun.params.get(idx))
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn target(&self) -> TypeLattice {
+        self.synth_fun.ret.clone()
+    }
+
+    /// Whether the start symbol produces an `ite`, which is what allows the
+    /// search to split on cases.
+    pub fn has_conditionals(&self) -> bool {
+        let grammar = self.grammar();
+        grammar.start(&self.target()).is_some_and(|start| {
+            grammar
+                .expand_nonterminal(start)
+                .iter()
+                .any(|p| matches!(**p, Expr::If(_, _, _)))
+        })
+    }
+
+    /// Input/output examples from constraints of the form `(= (f in...) out)`.
+    /// Constraints of any other shape are left in `constraints` untouched.
+    pub fn examples(&self) -> Examples {
+        self.constraints
+            .iter()
+            .filter_map(|c| self.as_example(c))
+            .map(|(inputs, output)| Example::new(inputs, output))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    pub fn is_example(&self, constraint: &SExpr) -> bool {
+        self.as_example(constraint).is_some()
+    }
+
+    /// Evaluates a constraint with the declared variables bound by `point`
+    /// and the function being synthesized computed by `f`.
+    pub fn eval_constraint(
+        &self,
+        constraint: &SExpr,
+        point: &Environment<StrVal>,
+        f: &dyn Fn(&[StrVal]) -> EvalResult<StrVal>,
+    ) -> EvalResult<StrVal> {
+        if let Some(c) = constraint.as_literal() {
+            return Ok(c);
+        }
+        let eval = |c| self.eval_constraint(c, point, f);
+        let items = match constraint {
+            SExpr::Atom(x) => {
+                return point
+                    .get(x.clone())
+                    .cloned()
+                    .ok_or_else(|| EvalError::UnboundVariable(x.clone()))
+            }
+            SExpr::List(items) => items,
+            _ => return Err(EvalError::mismatch("constraint", &[constraint])),
+        };
+        let (op, args) = match items.split_first() {
+            Some((SExpr::Atom(op), args)) => (op.as_str(), args),
+            _ => return Err(EvalError::mismatch("constraint", &[constraint])),
+        };
+        match (op, args) {
+            // only the branch taken is evaluated
+            ("ite", [cond, then, otherwise]) => match eval(cond)? {
+                StrVal::Bool(true) => eval(then),
+                StrVal::Bool(false) => eval(otherwise),
+                c => Err(EvalError::mismatch("ite", &[c])),
+            },
+            ("str.in_re", [arg, re]) | ("str.in.re", [arg, re]) => {
+                let re = parse_regex(re).map_err(|_| EvalError::UnknownOperator(re.to_string()))?;
+                let arg = Rc::new(Expr::Const(eval(arg)?));
+                Expr::<StrVal, TypeLattice>::Call(Func::InRe(arg, re)).eval(&Environment::new())
+            }
+            _ => {
+                let args = args.iter().map(eval).collect::<EvalResult<Vec<_>>>()?;
+                if op == self.synth_fun.name {
+                    return f(&args);
+                }
+                let args = args.into_iter().map(|a| Rc::new(Expr::Const(a))).collect();
+                match Self::func_from_op::<StrVal, TypeLattice>(op, args) {
+                    Some(func) => Expr::Call(func).eval(&Environment::new()),
+                    None => Err(EvalError::UnknownOperator(op.to_string())),
+                }
+            }
+        }
+    }
+
+    fn as_example(&self, constraint: &SExpr) -> Option<(Vec<StrVal>, StrVal)> {
+        match constraint.as_list()? {
+            [SExpr::Atom(eq), lhs, rhs] if eq == "=" => self
+                .as_application(lhs)
+                .zip(rhs.as_literal())
+                .or_else(|| self.as_application(rhs).zip(lhs.as_literal())),
+            _ => None,
+        }
+    }
+
+    fn as_application(&self, call: &SExpr) -> Option<Vec<StrVal>> {
+        match call.as_list()?.split_first()? {
+            (SExpr::Atom(f), args)
+                if *f == self.synth_fun.name && args.len() == self.synth_fun.params.len() =>
+            {
+                args.iter().map(|a| a.as_literal()).collect()
+            }
+            _ => None,
+        }
+    }
+
+    /// Translates the synth-fun grammar into the synthesizer's grammar,
+    /// nonterminal for nonterminal, so the first one is where the search
+    /// starts. A production that is just another nonterminal becomes an
+    /// include; productions using an operator the DSL does not support are
+    /// dropped.
+    pub fn grammar(&self) -> Grammar<TypeValues, TypeLattice> {
+        let mut grammar = Grammar::new();
+        for nt in &self.synth_fun.grammar {
+            let mut nonterminal = NonTerminal::new(nt.name.clone(), nt.sort.clone());
+            for p in &nt.productions {
+                if let Some(t) = self.production(p) {
+                    match &*t {
+                        Expr::Const(_) | Expr::Var(_) => nonterminal.consts.push(t),
+                        Expr::Hole(_, _, nt) => nonterminal.includes.extend(*nt),
+                        _ => nonterminal.productions.push(t),
+                    }
+                }
+            }
+            grammar.add(nonterminal);
+        }
+        grammar
+    }
+
+    fn production(&self, p: &SExpr) -> Option<Term<TypeValues, TypeLattice>> {
+        if let Some(c) = p.as_literal() {
+            return Some(Rc::new(Expr::Const(TypeValues::from_concrete(c))));
+        }
+        match p {
+            SExpr::Atom(a) => self
+                .synth_fun
+                .params
+                .iter()
+                .position(|(name, _)| name == a)
+                .map(|idx| Rc::new(Expr::Var(format!("arg{}", idx))))
+                .or_else(|| {
+                    let grammar = &self.synth_fun.grammar;
+                    grammar
+                        .iter()
+                        .position(|nt| nt.name == *a)
+                        .map(|i| Rc::new(Expr::Hole(grammar[i].sort.clone(), None, Some(i))))
+                }),
+            SExpr::List(items) => {
+                let (op, args) = items.split_first()?;
+                if let (Some("str.in_re") | Some("str.in.re"), [arg, re]) = (op.as_atom(), args) {
+                    let func = Func::InRe(self.production(arg)?, parse_regex(re).ok()?);
+                    return Some(Rc::new(Expr::Call(func)));
+                }
+                let mut args = args
+                    .iter()
+                    .map(|a| self.production(a))
+                    .collect::<Option<Vec<_>>>()?;
+                match (op.as_atom()?, args.len()) {
+                    ("ite", 3) => {
+                        let otherwise = args.pop()?;
+                        let then = args.pop()?;
+                        Some(Rc::new(Expr::If(args.pop()?, then, otherwise)))
+                    }
+                    (op, _) => Self::func_from_op(op, args).map(|f| Rc::new(Expr::Call(f))),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn func_from_op<T: Value, U: Lattice>(op: &str, args: Vec<Term<T, U>>) -> Option<Func<T, U>> {
+        let mut args = args.into_iter();
+        let func = match (op, args.len()) {
+            ("str.++", 2) => Func::Append(args.next()?, args.next()?),
+            ("str.replace", 3) => Func::Replace(args.next()?, args.next()?, args.next()?),
+            ("str.substr", 3) => Func::Substr(args.next()?, args.next()?, args.next()?),
+            ("+", 2) => Func::Add(args.next()?, args.next()?),
+            ("-", 2) => Func::Sub(args.next()?, args.next()?),
+            ("str.len", 1) => Func::Len(args.next()?),
+            ("str.at", 2) => Func::At(args.next()?, args.next()?),
+            ("int.to.str", 1) | ("str.from_int", 1) => Func::ToStr(args.next()?),
+            ("str.to.int", 1) | ("str.to_int", 1) => Func::ToInt(args.next()?),
+            ("str.indexof", 3) => Func::IndexOf(args.next()?, args.next()?, args.next()?),
+            ("str.prefixof", 2) => Func::PrefixOf(args.next()?, args.next()?),
+            ("str.suffixof", 2) => Func::SuffixOf(args.next()?, args.next()?),
+            ("str.contains", 2) => Func::Contains(args.next()?, args.next()?),
+            ("str.replace_all", 3) | ("str.replaceall", 3) => {
+                Func::ReplaceAll(args.next()?, args.next()?, args.next()?)
+            }
+            ("str.<", 2) => Func::StrLt(args.next()?, args.next()?),
+            ("str.<=", 2) => Func::StrLe(args.next()?, args.next()?),
+            ("str.is_digit", 1) => Func::IsDigit(args.next()?),
+            ("str.from_code", 1) => Func::FromCode(args.next()?),
+            ("str.to_code", 1) => Func::ToCode(args.next()?),
+            ("<", 2) => Func::Lt(args.next()?, args.next()?),
+            ("<=", 2) => Func::Le(args.next()?, args.next()?),
+            ("=", 2) => Func::Eq(args.next()?, args.next()?),
+            ("and", 2) => Func::And(args.next()?, args.next()?),
+            ("or", 2) => Func::Or(args.next()?, args.next()?),
+            // n-ary connectives nest to the right
+            ("and", n) | ("or", n) if n > 2 => {
+                let first = args.next()?;
+                let rest = Self::func_from_op(op, args.collect())?;
+                Self::func_from_op(op, vec![first, Rc::new(Expr::Call(rest))])?
+            }
+            ("not", 1) => Func::Not(args.next()?),
+            _ => return None,
+        };
+        Some(func)
+    }
+
+    /// Renders a solution as a `define-fun` for the synthesized function,
+    /// restoring the parameter names of the problem.
+    pub fn define_fun<U: Lattice>(&self, body: &Expr<StrVal, U>) -> String {
+        let params = self
+            .synth_fun
+            .params
+            .iter()
+            .map(|(name, sort)| format!("({} {})", name, sort_name(sort)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            "(define-fun {} ({}) {} {})",
+            self.synth_fun.name,
+            params,
+            sort_name(&self.synth_fun.ret),
+            self.to_sexpr(body)
+        )
+    }
+
+    pub fn to_sexpr<U: Lattice>(&self, expr: &Expr<StrVal, U>) -> SExpr {
+        match expr {
+            Expr::Const(c) => SExpr::from(c),
+            Expr::Var(x) => SExpr::Atom(self.param_name(x).unwrap_or(x).to_string()),
+            Expr::Call(f) => self.call_to_sexpr(f),
+            Expr::If(cond, then, otherwise) => SExpr::List(vec![
+                SExpr::Atom("ite".to_string()),
+                self.to_sexpr(cond),
+                self.to_sexpr(then),
+                self.to_sexpr(otherwise),
+            ]),
+            _ => unreachable!(),
+        }
+    }
+
+    fn call_to_sexpr<U: Lattice>(&self, func: &Func<StrVal, U>) -> SExpr {
+        let (op, args) = match func {
+            Func::Append(arg1, arg2) => ("str.++", vec![arg1, arg2]),
+            Func::Replace(arg1, arg2, arg3) => ("str.replace", vec![arg1, arg2, arg3]),
+            Func::Substr(arg1, arg2, arg3) => ("str.substr", vec![arg1, arg2, arg3]),
+            Func::Add(arg1, arg2) => ("+", vec![arg1, arg2]),
+            Func::Sub(arg1, arg2) => ("-", vec![arg1, arg2]),
+            Func::Len(arg) => ("str.len", vec![arg]),
+            Func::At(arg1, arg2) => ("str.at", vec![arg1, arg2]),
+            Func::ToStr(arg) => ("int.to.str", vec![arg]),
+            Func::ToInt(arg) => ("str.to.int", vec![arg]),
+            Func::IndexOf(arg1, arg2, arg3) => ("str.indexof", vec![arg1, arg2, arg3]),
+            Func::PrefixOf(arg1, arg2) => ("str.prefixof", vec![arg1, arg2]),
+            Func::SuffixOf(arg1, arg2) => ("str.suffixof", vec![arg1, arg2]),
+            Func::Contains(arg1, arg2) => ("str.contains", vec![arg1, arg2]),
+            Func::ReplaceAll(arg1, arg2, arg3) => ("str.replace_all", vec![arg1, arg2, arg3]),
+            Func::StrLt(arg1, arg2) => ("str.<", vec![arg1, arg2]),
+            Func::StrLe(arg1, arg2) => ("str.<=", vec![arg1, arg2]),
+            Func::IsDigit(arg) => ("str.is_digit", vec![arg]),
+            Func::FromCode(arg) => ("str.from_code", vec![arg]),
+            Func::ToCode(arg) => ("str.to_code", vec![arg]),
+            Func::InRe(arg, re) => {
+                return SExpr::List(vec![
+                    SExpr::Atom("str.in_re".to_string()),
+                    self.to_sexpr(arg),
+                    SExpr::from(re),
+                ])
+            }
+            Func::Lt(arg1, arg2) => ("<", vec![arg1, arg2]),
+            Func::Le(arg1, arg2) => ("<=", vec![arg1, arg2]),
+            Func::Eq(arg1, arg2) => ("=", vec![arg1, arg2]),
+            Func::And(arg1, arg2) => ("and", vec![arg1, arg2]),
+            Func::Or(arg1, arg2) => ("or", vec![arg1, arg2]),
+            Func::Not(arg) => ("not", vec![arg]),
+        };
+        SExpr::List(
+            std::iter::once(SExpr::Atom(op.to_string()))
+                .chain(args.into_iter().map(|a| self.to_sexpr(a)))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::SynthesisVisitor;
+
+    const PROBLEM: &str = r#"
+        (set-logic SLIA)
+        ; a comment
+        (synth-fun f ((first String) (n Int)) String
+            ((Start String (ntString))
+             (ntString String (first " " "say ""hi"""
+                               (str.++ ntString ntString)))
+             (ntInt Int (0 (- 1) n (str.len ntString)))))
+        (declare-var first String)
+        (declare-var n Int)
+        (constraint (= (f "a" 1) "a a"))
+        (constraint (= "b b" (f "b" 2)))
+        (constraint (= (str.len (f first n)) (+ n 1)))
+        (check-synth)
+    "#;
+
+    #[test]
+    fn test_sexpr() {
+        let exprs = SExpr::parse_all("(a \"b \"\"c\"\"\" -3) ; trailing").unwrap();
+        assert_eq!(
+            exprs,
+            vec![SExpr::List(vec![
+                SExpr::Atom("a".to_string()),
+                SExpr::Str("b \"c\"".to_string()),
+                SExpr::Int(-3),
+            ])]
+        );
+        assert!(SExpr::parse_all("(a (b)").is_err());
+        assert!(SExpr::parse_all("a)").is_err());
+    }
+
+    #[test]
+    fn test_problem() {
+        let problem = Problem::parse(PROBLEM).unwrap();
+        assert_eq!(problem.logic, "SLIA");
+        assert_eq!(problem.synth_fun.name, "f");
+        assert_eq!(problem.target(), TypeLattice::String);
+        assert_eq!(problem.synth_fun.grammar.len(), 3);
+        assert_eq!(problem.vars.len(), 2);
+        assert_eq!(problem.constraints.len(), 3);
+        assert_eq!(
+            problem.consts(),
+            vec![
+                StrVal::from(" ".to_string()),
+                StrVal::from("say \"hi\"".to_string()),
+                StrVal::from(0),
+                StrVal::from(-1),
+            ]
+        );
+        assert_eq!(
+            problem.examples(),
+            Examples::from(vec![
+                Example::new(
+                    vec![StrVal::from("a".to_string()), StrVal::from(1)],
+                    StrVal::from("a a".to_string())
+                ),
+                Example::new(
+                    vec![StrVal::from("b".to_string()), StrVal::from(2)],
+                    StrVal::from("b b".to_string())
+                ),
+            ])
+        );
+        assert_eq!(problem.param_name("arg1"), Some("n"));
+        assert_eq!(problem.param_name("arg2"), None);
+        assert_eq!(
+            problem.env().get("arg1".to_string()),
+            Some(&TypeValues::Abs(TypeLattice::Integer))
+        );
+    }
+
+    #[test]
+    fn test_grammar() {
+        let problem = Problem::parse(PROBLEM).unwrap();
+        let grammar = problem.grammar();
+        let env = problem.env();
+        assert_eq!(grammar.start(&TypeLattice::String), Some(0));
+        assert_eq!(grammar.nonterminals()[0].includes, vec![1]);
+        assert_eq!(
+            grammar.expand_nonterminal(2)[0].to_string(),
+            "(len (□: Str))"
+        );
+
+        let hole: Term<TypeValues, TypeLattice> =
+            Rc::new(Expr::Hole(TypeLattice::Integer, None, None));
+        let expanded = hole
+            .visit(&env, &grammar, &mut HashMap::new())
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(expanded, vec!["(len (□: Str))", "0", "-1", "arg1"]);
+
+        let hole: Term<TypeValues, TypeLattice> =
+            Rc::new(Expr::Hole(TypeLattice::String, None, None));
+        let expanded = hole
+            .visit(&env, &grammar, &mut HashMap::new())
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            expanded,
+            vec![
+                "(append (□: Str) (□: Str))",
+                "arg0",
+                "\" \"",
+                "\"say \"hi\"\"",
+            ]
+        );
+
+        let hole: Term<TypeValues, TypeLattice> =
+            Rc::new(Expr::Hole(TypeLattice::Bool, None, None));
+        assert!(hole.visit(&env, &grammar, &mut HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_eval_constraint() {
+        let problem = Problem::parse(PROBLEM).unwrap();
+        let mut point = Environment::new();
+        point.put("first".to_string(), StrVal::from("ab".to_string()));
+        point.put("n".to_string(), StrVal::from(2));
+        let echo = |args: &[StrVal]| Ok(args[0].clone());
+        let results = problem
+            .constraints
+            .iter()
+            .map(|c| problem.eval_constraint(c, &point, &echo))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                Ok(StrVal::Bool(false)),
+                Ok(StrVal::Bool(false)),
+                Ok(StrVal::Bool(false))
+            ]
+        );
+        let pad = |args: &[StrVal]| match args {
+            [StrVal::Str(s), StrVal::Int(n)] => {
+                Ok(StrVal::from(format!("{:1$}", s, *n as usize + 1)))
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            problem.eval_constraint(&problem.constraints[2], &point, &pad),
+            Ok(StrVal::Bool(true))
+        );
+        assert!(!problem.is_example(&problem.constraints[2]));
+
+        let unknown = SExpr::parse_all("(str.rev first)").unwrap();
+        assert_eq!(
+            problem.eval_constraint(&unknown[0], &point, &echo),
+            Err(EvalError::UnknownOperator("str.rev".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_define_fun() {
+        let problem = Problem::parse(PROBLEM).unwrap();
+        let body: Expr<StrVal, TypeLattice> = Expr::Call(Func::Append(
+            Rc::new(Expr::Var("arg0".to_string())),
+            Rc::new(Expr::Call(Func::Substr(
+                Rc::new(Expr::Const(StrVal::from("say \"hi\"".to_string()))),
+                Rc::new(Expr::Var("arg1".to_string())),
+                Rc::new(Expr::Const(StrVal::from(-1))),
+            ))),
+        ));
+        assert_eq!(
+            problem.define_fun(&body),
+            "(define-fun f ((first String) (n Int)) String \
+             (str.++ first (str.substr \"say \"\"hi\"\"\" n (- 1))))"
+        );
+    }
+
+    #[test]
+    fn test_extended_ops() {
+        let problem = Problem::parse(
+            r#"
+            (set-logic SLIA)
+            (synth-fun f ((s String)) Bool
+                ((Start Bool ((str.in_re ntString (re.+ (re.range "0" "9")))
+                              (str.in_re ntString ((_ re.loop 1 2) (str.to_re "ab")))
+                              (str.in_re ntString (re.++ ntString re.all))
+                              (str.< ntString ntString)
+                              (<= ntInt ntInt)))
+                 (ntString String (s (str.replace_all ntString ntString ntString)))
+                 (ntInt Int ((str.to_code ntString)))))
+            "#,
+        )
+        .unwrap();
+        let grammar = problem.grammar();
+        let rendered = grammar
+            .nonterminals()
+            .iter()
+            .flat_map(|nt| &nt.productions)
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+        // a regex built from a nonterminal cannot be read and is dropped
+        assert_eq!(
+            rendered,
+            vec![
+                "(in_re (□: Str) (re.+ (re.range \"0\" \"9\")))",
+                "(in_re (□: Str) ((_ re.loop 1 2) (str.to_re \"ab\")))",
+                "(str.< (□: Str) (□: Str))",
+                "(<= (□: Int) (□: Int))",
+                "(replace_all (□: Str) (□: Str) (□: Str))",
+                "(to_code (□: Str))",
+            ]
+        );
+
+        let body: Expr<StrVal, TypeLattice> = Expr::Call(Func::InRe(
+            Rc::new(Expr::Var("arg0".to_string())),
+            Regex::Star(Box::new(Regex::AllChar)),
+        ));
+        assert_eq!(
+            problem.define_fun(&body),
+            "(define-fun f ((s String)) Bool (str.in_re s (re.* re.allchar)))"
+        );
+    }
+
+    #[test]
+    fn test_headers_and_macros() {
+        let problem = Problem::parse(
+            r#"
+            (set-logic SLIA)
+            (set-option :produce-models true)
+            (set-info :source |hand-written|)
+            (define-fun dot () String ".")
+            (define-fun initial ((s String)) String (str.++ (str.at s 0) dot))
+            (synth-fun f ((name String)) String
+                ((Start String (name (initial Start)))))
+            (declare-fun name () String)
+            (constraint (= (f "Ada") (initial "Ada")))
+            (constraint (= (str.len (f name)) 2))
+            (check-synth)
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            problem.vars,
+            vec![("name".to_string(), TypeLattice::String)]
+        );
+        assert_eq!(
+            problem.synth_fun.grammar[0].productions[1].to_string(),
+            "(str.++ (str.at Start 0) \".\")"
+        );
+        assert_eq!(
+            problem.constraints[0].to_string(),
+            "(= (f \"Ada\") (str.++ (str.at \"Ada\" 0) \".\"))"
+        );
+        let initial = |_: &[StrVal]| Ok(StrVal::from("A.".to_string()));
+        assert_eq!(
+            problem.eval_constraint(&problem.constraints[0], &Environment::new(), &initial),
+            Ok(StrVal::Bool(true))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Problem::parse("(set-logic SLIA)").is_err());
+        assert!(Problem::parse("(set-logic SLIA) (synth-fun f () Real)").is_err());
+        assert!(Problem::parse("(set-logic SLIA) (frobnicate)").is_err());
+        assert!(Problem::parse("(set-logic SLIA) (declare-fun g (Int) Int)").is_err());
+        assert!(SExpr::parse_all("(- -9223372036854775808)").is_err());
+        assert_eq!(
+            SExpr::parse_all("(- 9223372036854775807)").unwrap()[0].as_literal(),
+            Some(StrVal::from(-i64::MAX))
+        );
+
+        let unsupported = |src: &str| {
+            Problem::parse(&format!("(set-logic SLIA) {}", src))
+                .unwrap_err()
+                .starts_with("unsupported")
+        };
+        assert!(unsupported(
+            "(synth-fun f ((x String)) String ((Start String)) ((Start String (x))))"
+        ));
+        assert!(unsupported(
+            "(synth-fun f ((x String)) String ((Start String (x (Constant String)))))"
+        ));
+    }
+}
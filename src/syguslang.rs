@@ -1,3 +1,4 @@
+use crate::regex::Regex;
 use crate::values::{Lattice, Value};
 use std::fmt;
 use std::fmt::Display;
@@ -20,6 +21,19 @@ pub enum Func<T: Value, U: Lattice> {
     PrefixOf(Term<T, U>, Term<T, U>),
     SuffixOf(Term<T, U>, Term<T, U>),
     Contains(Term<T, U>, Term<T, U>),
+    ReplaceAll(Term<T, U>, Term<T, U>, Term<T, U>),
+    StrLt(Term<T, U>, Term<T, U>),
+    StrLe(Term<T, U>, Term<T, U>),
+    IsDigit(Term<T, U>),
+    FromCode(Term<T, U>),
+    ToCode(Term<T, U>),
+    InRe(Term<T, U>, Regex),
+    Lt(Term<T, U>, Term<T, U>),
+    Le(Term<T, U>, Term<T, U>),
+    Eq(Term<T, U>, Term<T, U>),
+    And(Term<T, U>, Term<T, U>),
+    Or(Term<T, U>, Term<T, U>),
+    Not(Term<T, U>),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -28,7 +42,10 @@ pub enum Expr<T: Value, U: Lattice> {
     Var(String),
     Call(Func<T, U>),
     If(Term<T, U>, Term<T, U>, Term<T, U>),
-    Hole(U, Option<Func<T, U>>),
+    // the abstract value to fill, the partial call the hole stands for if
+    // any, and the grammar nonterminal it comes from, which alone may fill
+    // it; a hole without one takes any nonterminal of its sort
+    Hole(U, Option<Func<T, U>>, Option<usize>),
     ConcHole(u32),
     DepHole,
 }
@@ -40,7 +57,7 @@ impl<T: Value + Display, U: Lattice> Display for Expr<T, U> {
             Self::Var(var) => write!(f, "{}", var),
             Self::Call(func) => write!(f, "{}", func),
             Self::If(cond, then, otherwise) => write!(f, "(if {} {} {})", cond, then, otherwise),
-            Self::Hole(lat, _) => write!(f, "(□: {})", lat),
+            Self::Hole(lat, _, _) => write!(f, "(□: {})", lat),
             Self::DepHole => write!(f, "□"),
             Self::ConcHole(size) => write!(f, "□({})", size),
         }
@@ -63,6 +80,17 @@ impl<T: Value + Display, U: Lattice> Display for Func<T, U> {
             Self::PrefixOf(arg1, arg2) => write!(f, "(prefixof {} {})", arg1, arg2),
             Self::SuffixOf(arg1, arg2) => write!(f, "(suffixof {} {})", arg1, arg2),
             Self::Contains(arg1, arg2) => write!(f, "(contains {} {})", arg1, arg2),
+            Self::InRe(arg, re) => write!(f, "(in_re {} {})", arg, re),
+            func => write!(
+                f,
+                "({} {})",
+                func.name(),
+                func.args()
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
         }
     }
 }
@@ -70,7 +98,7 @@ impl<T: Value + Display, U: Lattice> Display for Func<T, U> {
 impl<T: Value, U: Lattice> Expr<T, U> {
     pub fn has_hole(&self) -> bool {
         match self {
-            Self::Hole(_, _) => true,
+            Self::Hole(_, _, _) => true,
             Self::ConcHole(_) => unreachable!(),
             Self::DepHole => unreachable!(),
             Self::Const(_) => false,
@@ -86,7 +114,7 @@ impl<T: Value, U: Lattice> Expr<T, U> {
         match self {
             Self::Call(f) => f.size(),
             Self::If(cond, then, otherwise) => cond.size() + then.size() + otherwise.size(),
-            Self::Hole(_, Some(f)) => f.size(),
+            Self::Hole(_, Some(f), _) => f.size(),
             Self::ConcHole(s) => *s,
             _ => 0,
         }
@@ -94,43 +122,111 @@ impl<T: Value, U: Lattice> Expr<T, U> {
 }
 
 impl<T: Value, U: Lattice> Func<T, U> {
-    fn has_hole(&self) -> bool {
+    pub fn args(&self) -> Vec<&Term<T, U>> {
         match self {
-            Self::Append(arg1, arg2) => arg1.has_hole() || arg2.has_hole(),
-            Self::Replace(arg1, arg2, arg3) => {
-                arg1.has_hole() || arg2.has_hole() || arg3.has_hole()
-            }
-            Self::Substr(arg1, arg2, arg3) => arg1.has_hole() || arg2.has_hole() || arg3.has_hole(),
-            Self::Add(arg1, arg2) => arg1.has_hole() || arg2.has_hole(),
-            Self::Sub(arg1, arg2) => arg1.has_hole() || arg2.has_hole(),
-            Self::Len(arg) => arg.has_hole(),
-            Self::At(arg1, arg2) => arg1.has_hole() || arg2.has_hole(),
-            Self::ToStr(arg) => arg.has_hole(),
-            Self::ToInt(arg) => arg.has_hole(),
-            Self::IndexOf(arg1, arg2, arg3) => {
-                arg1.has_hole() || arg2.has_hole() || arg3.has_hole()
-            }
-            Self::PrefixOf(arg1, arg2) => arg1.has_hole() || arg2.has_hole(),
-            Self::SuffixOf(arg1, arg2) => arg1.has_hole() || arg2.has_hole(),
-            Self::Contains(arg1, arg2) => arg1.has_hole() || arg2.has_hole(),
+            Self::Append(arg1, arg2)
+            | Self::Add(arg1, arg2)
+            | Self::Sub(arg1, arg2)
+            | Self::At(arg1, arg2)
+            | Self::PrefixOf(arg1, arg2)
+            | Self::SuffixOf(arg1, arg2)
+            | Self::Contains(arg1, arg2)
+            | Self::StrLt(arg1, arg2)
+            | Self::StrLe(arg1, arg2)
+            | Self::Lt(arg1, arg2)
+            | Self::Le(arg1, arg2)
+            | Self::Eq(arg1, arg2)
+            | Self::And(arg1, arg2)
+            | Self::Or(arg1, arg2) => vec![arg1, arg2],
+            Self::Replace(arg1, arg2, arg3)
+            | Self::Substr(arg1, arg2, arg3)
+            | Self::IndexOf(arg1, arg2, arg3)
+            | Self::ReplaceAll(arg1, arg2, arg3) => vec![arg1, arg2, arg3],
+            Self::Len(arg)
+            | Self::ToStr(arg)
+            | Self::ToInt(arg)
+            | Self::IsDigit(arg)
+            | Self::FromCode(arg)
+            | Self::ToCode(arg)
+            | Self::Not(arg)
+            | Self::InRe(arg, _) => vec![arg],
         }
     }
 
-    fn size(&self) -> u32 {
+    /// The name the call is printed with.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Append(_, _) => "append",
+            Self::Replace(_, _, _) => "replace",
+            Self::Substr(_, _, _) => "substr",
+            Self::Add(_, _) => "+",
+            Self::Sub(_, _) => "-",
+            Self::Len(_) => "len",
+            Self::At(_, _) => "at",
+            Self::ToStr(_) => "to-str",
+            Self::ToInt(_) => "to-int",
+            Self::IndexOf(_, _, _) => "indexof",
+            Self::PrefixOf(_, _) => "prefixof",
+            Self::SuffixOf(_, _) => "suffixof",
+            Self::Contains(_, _) => "contains",
+            Self::ReplaceAll(_, _, _) => "replace_all",
+            Self::StrLt(_, _) => "str.<",
+            Self::StrLe(_, _) => "str.<=",
+            Self::IsDigit(_) => "is_digit",
+            Self::FromCode(_) => "from_code",
+            Self::ToCode(_) => "to_code",
+            Self::InRe(_, _) => "in_re",
+            Self::Lt(_, _) => "<",
+            Self::Le(_, _) => "<=",
+            Self::Eq(_, _) => "=",
+            Self::And(_, _) => "and",
+            Self::Or(_, _) => "or",
+            Self::Not(_) => "not",
+        }
+    }
+
+    /// Rebuilds the call with every argument transformed by `f`, possibly
+    /// into another value domain.
+    pub fn map_args<V: Value, W: Lattice, F: FnMut(&Term<T, U>) -> Term<V, W>>(
+        &self,
+        mut f: F,
+    ) -> Func<V, W> {
         match self {
-            Self::Append(arg1, arg2) => arg1.size() + arg2.size() + 2,
-            Self::Replace(arg1, arg2, arg3) => arg1.size() + arg2.size() + arg3.size() + 3,
-            Self::Substr(arg1, arg2, arg3) => arg1.size() + arg2.size() + arg3.size() + 3,
-            Self::Add(arg1, arg2) => arg1.size() + arg2.size() + 2,
-            Self::Sub(arg1, arg2) => arg1.size() + arg2.size() + 2,
-            Self::Len(arg) => arg.size() + 1,
-            Self::At(arg1, arg2) => arg1.size() + arg2.size() + 2,
-            Self::ToStr(arg) => arg.size() + 1,
-            Self::ToInt(arg) => arg.size() + 1,
-            Self::IndexOf(arg1, arg2, arg3) => arg1.size() + arg2.size() + arg3.size() + 3,
-            Self::PrefixOf(arg1, arg2) => arg1.size() + arg2.size() + 2,
-            Self::SuffixOf(arg1, arg2) => arg1.size() + arg2.size() + 2,
-            Self::Contains(arg1, arg2) => arg1.size() + arg2.size() + 2,
+            Self::Append(arg1, arg2) => Func::Append(f(arg1), f(arg2)),
+            Self::Replace(arg1, arg2, arg3) => Func::Replace(f(arg1), f(arg2), f(arg3)),
+            Self::Substr(arg1, arg2, arg3) => Func::Substr(f(arg1), f(arg2), f(arg3)),
+            Self::Add(arg1, arg2) => Func::Add(f(arg1), f(arg2)),
+            Self::Sub(arg1, arg2) => Func::Sub(f(arg1), f(arg2)),
+            Self::Len(arg) => Func::Len(f(arg)),
+            Self::At(arg1, arg2) => Func::At(f(arg1), f(arg2)),
+            Self::ToStr(arg) => Func::ToStr(f(arg)),
+            Self::ToInt(arg) => Func::ToInt(f(arg)),
+            Self::IndexOf(arg1, arg2, arg3) => Func::IndexOf(f(arg1), f(arg2), f(arg3)),
+            Self::PrefixOf(arg1, arg2) => Func::PrefixOf(f(arg1), f(arg2)),
+            Self::SuffixOf(arg1, arg2) => Func::SuffixOf(f(arg1), f(arg2)),
+            Self::Contains(arg1, arg2) => Func::Contains(f(arg1), f(arg2)),
+            Self::ReplaceAll(arg1, arg2, arg3) => Func::ReplaceAll(f(arg1), f(arg2), f(arg3)),
+            Self::StrLt(arg1, arg2) => Func::StrLt(f(arg1), f(arg2)),
+            Self::StrLe(arg1, arg2) => Func::StrLe(f(arg1), f(arg2)),
+            Self::IsDigit(arg) => Func::IsDigit(f(arg)),
+            Self::FromCode(arg) => Func::FromCode(f(arg)),
+            Self::ToCode(arg) => Func::ToCode(f(arg)),
+            Self::InRe(arg, re) => Func::InRe(f(arg), re.clone()),
+            Self::Lt(arg1, arg2) => Func::Lt(f(arg1), f(arg2)),
+            Self::Le(arg1, arg2) => Func::Le(f(arg1), f(arg2)),
+            Self::Eq(arg1, arg2) => Func::Eq(f(arg1), f(arg2)),
+            Self::And(arg1, arg2) => Func::And(f(arg1), f(arg2)),
+            Self::Or(arg1, arg2) => Func::Or(f(arg1), f(arg2)),
+            Self::Not(arg) => Func::Not(f(arg)),
         }
     }
+
+    fn has_hole(&self) -> bool {
+        self.args().into_iter().any(|a| a.has_hole())
+    }
+
+    fn size(&self) -> u32 {
+        let args = self.args();
+        args.len() as u32 + args.into_iter().map(|a| a.size()).sum::<u32>()
+    }
 }
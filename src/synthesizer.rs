@@ -1,107 +1,795 @@
 use crate::concrete::StrVal;
+use crate::conditional::Unifier;
+use crate::cost::{CostModel, Weights};
 use crate::environment::Environment;
+use crate::grammar::{DefaultGrammar, Grammar, NonTerminal};
 use crate::interpreter::EvalResult;
 use crate::interpreter::Evaluable;
 use crate::interpreter::SynthesisVisitor;
+use crate::oe::{matches, OeCache};
+use crate::spec::{bind_args, Spec};
 use crate::syguslang::Expr;
 use crate::syguslang::Term;
-use crate::types::{TypeLattice, TypeValues};
 use crate::values::Lattice;
+use crate::values::MixedValue;
 use crate::values::Value;
 use itertools::{Either, Itertools};
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::AddAssign;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-type LatticeValues = TypeValues;
-type MyLattice = TypeLattice;
+/// How `Synthesizer` enumerates programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Fills the holes of partial programs, cheapest program first, with
+    /// whatever the domain's visitor allows.
+    #[default]
+    TopDown,
+    /// Builds closed programs from the grammar's productions and smaller
+    /// closed programs, smallest first. The search ends as
+    /// `Outcome::Unsupported` when the grammar has no productions.
+    BottomUp,
+}
 
-pub struct Context {
-    conc_exprs: HashMap<u32, Vec<Term<LatticeValues, MyLattice>>>,
-    max_size: u32,
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    pub engine: Engine,
+    pub max_size: u32,
+    pub max_nodes: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<Arc<AtomicBool>>,
+    pub observational_equivalence: bool,
+    pub conditionals: bool,
 }
 
-impl Context {
-    pub fn new(consts: &Vec<StrVal>, env: &Environment<LatticeValues>) -> Context {
-        let exprs0 = consts
-            .into_iter()
-            .map(|x| Rc::new(Expr::Const(LatticeValues::from_concrete(x.clone()))))
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            engine: Engine::default(),
+            max_size: 15,
+            max_nodes: None,
+            deadline: None,
+            cancel: None,
+            observational_equivalence: false,
+            conditionals: false,
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn new() -> SearchConfig {
+        Self::default()
+    }
+
+    pub fn engine(mut self, engine: Engine) -> SearchConfig {
+        self.engine = engine;
+        self
+    }
+
+    pub fn max_size(mut self, max_size: u32) -> SearchConfig {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: usize) -> SearchConfig {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Instant) -> SearchConfig {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn timeout(self, timeout: Duration) -> SearchConfig {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// The search stops with `Outcome::Cancelled` once the flag is set,
+    /// which makes it possible to abort it from another thread.
+    pub fn cancel(mut self, flag: Arc<AtomicBool>) -> SearchConfig {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Prunes candidates containing a closed subterm that behaves like an
+    /// already seen one on every example. Only applies to `Spec::Examples`.
+    pub fn observational_equivalence(mut self, enabled: bool) -> SearchConfig {
+        self.observational_equivalence = enabled;
+        self
+    }
+
+    /// Combines candidates that each pass part of the examples into an `If`
+    /// chain once guards separating them are found. Only applies to
+    /// `Spec::Examples`.
+    pub fn conditionals(mut self, enabled: bool) -> SearchConfig {
+        self.conditionals = enabled;
+        self
+    }
+
+    /// The time left before the deadline, if there is one.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// How a search ends once the flag is set or the deadline has passed.
+    pub fn halted<T: Value, U: Lattice>(&self) -> Option<Outcome<T, U>> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|c| c.load(AtomicOrdering::Relaxed))
+        {
+            Some(Outcome::Cancelled)
+        } else if self.remaining() == Some(Duration::ZERO) {
+            Some(Outcome::Timeout)
+        } else {
+            None
+        }
+    }
+
+    fn interrupted<T: Value, U: Lattice>(&self, explored: usize) -> Option<Outcome<T, U>> {
+        if let Some(outcome) = self.halted() {
+            Some(outcome)
+        } else if self.max_nodes.is_some_and(|n| explored >= n) {
+            Some(Outcome::NodeLimit { explored })
+        } else {
+            None
+        }
+    }
+}
+
+/// Search state shared by successive `synthesize` calls: the grammar,
+/// the closed terms built so far, grouped by size, the limits and the cost
+/// model ordering the worklist.
+pub struct Context<T: Value, U: Lattice> {
+    conc_exprs: HashMap<u32, Vec<Term<T, U>>>,
+    grammar: Grammar<T, U>,
+    config: SearchConfig,
+    cost_model: Box<dyn CostModel<T, U>>,
+}
+
+impl<U: DefaultGrammar<MixedValue<StrVal, U>>> Context<MixedValue<StrVal, U>, U> {
+    pub fn new(
+        consts: &[StrVal],
+        env: &Environment<MixedValue<StrVal, U>>,
+    ) -> Context<MixedValue<StrVal, U>, U> {
+        let exprs0: Vec<_> = consts
+            .iter()
+            .map(|x| Rc::new(Expr::Const(MixedValue::from_concrete(x.clone()))))
             .chain(env.keys().map(|x| Rc::new(Expr::Var(x.clone()))))
             .collect();
+        let grammar = U::default_grammar(&exprs0, env);
         let mut expr_map = HashMap::new();
         expr_map.insert(0, exprs0);
         Context {
             conc_exprs: expr_map,
-            max_size: 15,
+            grammar,
+            config: SearchConfig::default(),
+            cost_model: Box::new(Weights::default()),
+        }
+    }
+}
+
+impl<T: Value, U: Lattice> Context<T, U> {
+    pub fn with_grammar(grammar: Grammar<T, U>) -> Context<T, U> {
+        let mut expr_map = HashMap::new();
+        expr_map.insert(0, grammar.leaves());
+        Context {
+            conc_exprs: expr_map,
+            grammar,
+            config: SearchConfig::default(),
+            cost_model: Box::new(Weights::default()),
+        }
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: SearchConfig) {
+        self.config = config;
+    }
+
+    pub fn set_cost_model<C: CostModel<T, U> + 'static>(&mut self, cost_model: C) {
+        self.cost_model = Box::new(cost_model);
+    }
+}
+
+// worklist entry; the heap pops the cheapest term first and, among equally
+// cheap ones, the one pushed first, so the search order is reproducible
+struct Prioritized<T: Value, U: Lattice> {
+    cost: u32,
+    seq: usize,
+    term: Term<T, U>,
+}
+
+impl<T: Value, U: Lattice> Prioritized<T, U> {
+    fn new(cost_model: &dyn CostModel<T, U>, seq: usize, term: Term<T, U>) -> Prioritized<T, U> {
+        Prioritized {
+            cost: cost_model.cost(&term),
+            seq,
+            term,
         }
     }
 }
 
-impl<T: Value + Eq, U: Lattice + Eq> Ord for Expr<T, U> {
+impl<T: Value, U: Lattice> PartialEq for Prioritized<T, U> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost && self.seq == other.seq
+    }
+}
+
+impl<T: Value, U: Lattice> Eq for Prioritized<T, U> {}
+
+impl<T: Value, U: Lattice> Ord for Prioritized<T, U> {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.size().cmp(&self.size())
+        other
+            .cost
+            .cmp(&self.cost)
+            .then_with(|| other.seq.cmp(&self.seq))
     }
 }
 
-impl<T: Value + Eq, U: Lattice + Eq> PartialOrd for Expr<T, U> {
+impl<T: Value, U: Lattice> PartialOrd for Prioritized<T, U> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-pub struct Synthesizer;
+#[derive(Debug, Clone)]
+pub enum Outcome<T: Value, U: Lattice> {
+    Solved(Vec<Term<T, U>>),
+    Exhausted {
+        max_size: u32,
+        explored: usize,
+    },
+    /// The search stopped after exploring `max_nodes` items, before its
+    /// space was exhausted.
+    NodeLimit {
+        explored: usize,
+    },
+    Timeout,
+    Cancelled,
+    /// The engine cannot search this context, for the reason given.
+    Unsupported(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Worklist items popped and expanded, or for the bottom-up engine,
+    /// programs built.
+    pub explored: usize,
+    /// Candidates produced by expanding those items, or programs built.
+    pub generated: usize,
+    /// Complete programs checked against the specification.
+    pub tested: usize,
+    /// Candidates discarded as observationally equivalent to another.
+    pub pruned: usize,
+    pub elapsed: Duration,
+}
 
-impl Synthesizer {
-    fn wrap_func(expr: Term<StrVal, MyLattice>) -> impl Fn(&[StrVal]) -> EvalResult<StrVal> {
-        move |args: &[StrVal]| {
-            let mut env = Environment::new();
-            args.into_iter()
-                .enumerate()
-                .for_each(|(idx, val)| env.put(format!("arg{}", idx), val.clone()));
-            expr.eval(&env)
+// totals over several searches, such as the rounds of a CEGIS loop
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.explored += other.explored;
+        self.generated += other.generated;
+        self.tested += other.tested;
+        self.pruned += other.pruned;
+        self.elapsed += other.elapsed;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SynthResult<T: Value, U: Lattice> {
+    pub outcome: Outcome<T, U>,
+    pub stats: SearchStats,
+}
+
+impl<T: Value, U: Lattice> SynthResult<T, U> {
+    pub fn is_solved(&self) -> bool {
+        matches!(self.outcome, Outcome::Solved(_))
+    }
+
+    pub fn solutions(&self) -> &[Term<T, U>] {
+        match &self.outcome {
+            Outcome::Solved(progs) => progs,
+            _ => &[],
+        }
+    }
+}
+
+/// Top-down enumeration over any abstract domain: holes are annotated with
+/// an element of `U`, and the domain's visitor decides what may fill them.
+pub struct Synthesizer<T: Value, U: Lattice> {
+    _domain: PhantomData<(T, U)>,
+}
+
+impl<T, U> Synthesizer<T, U>
+where
+    T: Value + Eq + Debug + 'static,
+    U: Lattice + Eq + TryFrom<T> + 'static,
+    Term<T, U>: SynthesisVisitor<T, U> + Evaluable<T>,
+    Expr<StrVal, U>: From<Term<T, U>>,
+{
+    fn wrap_func(expr: Term<StrVal, U>) -> impl Fn(&[StrVal]) -> EvalResult<StrVal> {
+        move |args: &[StrVal]| expr.eval(&bind_args(args))
+    }
+
+    fn satisfies(prog: &Term<T, U>, spec: &Spec) -> bool {
+        let prog = Rc::new(Expr::from(prog.clone()));
+        match spec {
+            Spec::Test(test) => test(Box::new(Self::wrap_func(prog))),
+            Spec::Examples(examples) => examples.passes(&prog),
         }
     }
 
     pub fn synthesize(
-        ctx: &mut Context,
-        target: MyLattice,
-        env: &Environment<LatticeValues>,
-        test: Box<dyn Fn(Box<dyn Fn(&[StrVal]) -> EvalResult<StrVal>>) -> bool>,
-    ) -> Vec<Term<LatticeValues, MyLattice>> {
-        let start = Rc::new(Expr::Hole(target.clone(), None));
+        ctx: &mut Context<T, U>,
+        target: U,
+        env: &Environment<T>,
+        spec: Spec,
+    ) -> SynthResult<T, U> {
+        Self::search(ctx, target, env, &spec, 1)
+    }
+
+    /// Keeps searching until `n` distinct solutions are found or the search
+    /// stops, and returns the solutions cheapest first according to `cost`
+    /// (see the `cost` module for ready-made ones). The result is only
+    /// `Solved` with fewer than `n` solutions if the search ran out.
+    pub fn synthesize_n<K: Ord, F: Fn(&Expr<StrVal, U>) -> K>(
+        ctx: &mut Context<T, U>,
+        target: U,
+        env: &Environment<T>,
+        spec: Spec,
+        n: usize,
+        cost: F,
+    ) -> SynthResult<T, U> {
+        let mut result = Self::search(ctx, target, env, &spec, n);
+        if let Outcome::Solved(progs) = &mut result.outcome {
+            progs.sort_by_cached_key(|p| cost(&Expr::from(p.clone())));
+            progs.truncate(n);
+        }
+        result
+    }
+
+    fn search(
+        ctx: &mut Context<T, U>,
+        target: U,
+        env: &Environment<T>,
+        spec: &Spec,
+        wanted: usize,
+    ) -> SynthResult<T, U> {
+        match ctx.config.engine {
+            Engine::TopDown => Self::top_down(ctx, target, env, spec, wanted),
+            Engine::BottomUp => Self::bottom_up(ctx, target, env, spec, wanted),
+        }
+    }
+
+    fn top_down(
+        ctx: &mut Context<T, U>,
+        target: U,
+        env: &Environment<T>,
+        spec: &Spec,
+        wanted: usize,
+    ) -> SynthResult<T, U> {
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        let nt = ctx.grammar.start(&target);
+        let start = Rc::new(Expr::Hole(target, None, nt));
         let mut work_list = BinaryHeap::new();
-        work_list.push(start);
-
-        while !work_list.is_empty() {
-            let work_item = work_list.pop().unwrap();
-            // println!("{}", work_item);
-            let expanded = work_item.visit(env, &mut ctx.conc_exprs);
-
-            let (concrete, with_holes): (Vec<_>, Vec<_>) =
-                expanded.into_iter().partition_map(|x| match x.has_hole() {
-                    true => Either::Right(x),
-                    false => Either::Left(x),
-                });
-
-            let correct: Vec<Term<LatticeValues, MyLattice>> = concrete
-                .into_iter()
-                .filter(|p| {
-                    let func = Self::wrap_func(Rc::new(Expr::from(p.clone())));
-                    test(Box::new(func))
-                })
-                .collect();
-
-            if correct.is_empty() {
+        let mut pushed = 0;
+        work_list.push(Prioritized::new(&*ctx.cost_model, pushed, start));
+        let mut oe = OeCache::new();
+        let oe_examples = spec
+            .examples()
+            .filter(|_| ctx.config.observational_equivalence);
+        let mut unifier = spec
+            .examples()
+            .filter(|_| ctx.config.conditionals)
+            .map(|examples| {
+                (
+                    examples,
+                    Unifier::new(examples, &ctx.grammar, &ctx.conc_exprs[&0]),
+                )
+            })
+            .filter(|(_, unifier)| !unifier.is_empty());
+        let mut solutions: Vec<Term<T, U>> = vec![];
+        let mut seen = HashSet::new();
+
+        while let Some(Prioritized {
+            term: work_item, ..
+        }) = work_list.pop()
+        {
+            if let Some(outcome) = ctx.config.interrupted(stats.explored) {
+                stats.elapsed = started.elapsed();
+                return SynthResult {
+                    outcome: Self::found(solutions).unwrap_or(outcome),
+                    stats,
+                };
+            }
+            stats.explored += 1;
+            let expanded = work_item.visit(env, &ctx.grammar, &mut ctx.conc_exprs);
+            stats.generated += expanded.len();
+
+            let mut with_holes = vec![];
+            let mut correct = vec![];
+            let mut incorrect = vec![];
+            for p in expanded {
+                // filling every hole at once can give many candidates, each
+                // of them run on the examples
+                if let Some(outcome) = ctx.config.halted() {
+                    stats.elapsed = started.elapsed();
+                    return SynthResult {
+                        outcome: Self::found(solutions).unwrap_or(outcome),
+                        stats,
+                    };
+                }
+                if let Some(examples) = oe_examples {
+                    if oe.is_redundant(examples, &ctx.grammar, nt, &p) {
+                        stats.pruned += 1;
+                        continue;
+                    }
+                }
+                if p.has_hole() {
+                    with_holes.push(p);
+                } else {
+                    stats.tested += 1;
+                    if Self::satisfies(&p, spec) {
+                        correct.push(p);
+                    } else {
+                        incorrect.push(p);
+                    }
+                }
+            }
+            if let Some((examples, unifier)) = unifier.as_mut().filter(|_| correct.is_empty()) {
+                correct.extend(incorrect.iter().find_map(|p| unifier.add(examples, p)));
+            }
+            // the same program can be reached along different expansions
+            solutions.extend(correct.into_iter().filter(|p| seen.insert(p.to_string())));
+
+            if solutions.len() < wanted {
                 with_holes
                     .into_iter()
-                    .filter(|p| p.size() <= ctx.max_size)
-                    .for_each(|p| work_list.push(p));
+                    .filter(|p| p.size() <= ctx.config.max_size)
+                    .for_each(|p| {
+                        pushed += 1;
+                        work_list.push(Prioritized::new(&*ctx.cost_model, pushed, p))
+                    });
             } else {
-                return correct;
+                stats.elapsed = started.elapsed();
+                return SynthResult {
+                    outcome: Outcome::Solved(solutions),
+                    stats,
+                };
             }
         }
 
-        panic!("no solutions found")
+        stats.elapsed = started.elapsed();
+        SynthResult {
+            outcome: Self::found(solutions).unwrap_or(Outcome::Exhausted {
+                max_size: ctx.config.max_size,
+                explored: stats.explored,
+            }),
+            stats,
+        }
+    }
+
+    // level n holds, for each nonterminal, the closed programs of size n
+    // made from its own productions and constants, where a conditional counts
+    // as 1 so that every level is finite. A hole made from a nonterminal is
+    // filled from lower levels of that nonterminal and those it includes,
+    // any other hole with programs whose type fits it.
+    // Level 0 comes from the context's closed terms. Without observational
+    // equivalence the levels do not depend on the examples, so complete ones
+    // are kept in the context by size for later searches to reuse.
+    fn bottom_up(
+        ctx: &mut Context<T, U>,
+        target: U,
+        env: &Environment<T>,
+        spec: &Spec,
+        wanted: usize,
+    ) -> SynthResult<T, U> {
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        let nonterminals = ctx.grammar.nonterminals();
+        // domains whose visitor builds the calls, rather than the grammar,
+        // would only ever get their leaves
+        if nonterminals.iter().all(|nt| nt.productions.is_empty()) {
+            return SynthResult {
+                outcome: Outcome::Unsupported(
+                    "the bottom-up engine needs a grammar with productions".to_string(),
+                ),
+                stats,
+            };
+        }
+        let included: Vec<Vec<usize>> = (0..nonterminals.len())
+            .map(|i| ctx.grammar.included(i))
+            .collect();
+        // only programs the start symbol produces are candidate solutions
+        let start = ctx
+            .grammar
+            .start(&target)
+            .map_or(vec![], |s| included[s].clone());
+        let mut oe = OeCache::new();
+        let oe_examples = spec
+            .examples()
+            .filter(|_| ctx.config.observational_equivalence);
+        let mut unifier = spec
+            .examples()
+            .filter(|_| ctx.config.conditionals)
+            .map(|examples| {
+                (
+                    examples,
+                    Unifier::new(examples, &ctx.grammar, &ctx.conc_exprs[&0]),
+                )
+            })
+            .filter(|(_, unifier)| !unifier.is_empty());
+
+        // every program kept so far, with its type
+        let mut levels: Vec<Level<T, U>> = vec![];
+        let mut solutions: Vec<Term<T, U>> = vec![];
+        let mut seen = HashSet::new();
+
+        // the programs of the levels reused so far, by what they print as,
+        // with their type and nonterminals
+        let mut known = HashMap::new();
+
+        for size in 0..=ctx.config.max_size {
+            let mut level = vec![];
+            let cached = match size {
+                0 => Some(
+                    nonterminals
+                        .iter()
+                        .map(|nt| {
+                            ctx.conc_exprs[&0]
+                                .iter()
+                                .filter(|leaf| nt.consts.contains(leaf))
+                                .cloned()
+                                .collect()
+                        })
+                        .collect(),
+                ),
+                _ if oe_examples.is_none() => ctx
+                    .conc_exprs
+                    .get(&size)
+                    .map(|progs| Self::classify(nonterminals, &included, progs, &known)),
+                _ => None,
+            };
+            for (i, nt) in nonterminals.iter().enumerate() {
+                let candidates = match &cached {
+                    Some(cached) => Either::Left(cached[i].iter().cloned()),
+                    None => Either::Right(Self::level(&nt.productions, &included, &levels, size)),
+                };
+                let mut own = vec![];
+                for prog in candidates {
+                    if let Some(outcome) = ctx.config.interrupted(stats.explored) {
+                        stats.elapsed = started.elapsed();
+                        return SynthResult {
+                            outcome: Self::found(solutions).unwrap_or(outcome),
+                            stats,
+                        };
+                    }
+                    stats.explored += 1;
+                    stats.generated += 1;
+                    let sort = match prog.eval(env).ok().and_then(|v| U::try_from(v).ok()) {
+                        Some(sort) => sort,
+                        None => continue,
+                    };
+                    if let Some(examples) = oe_examples {
+                        if !oe.insert(examples, Some(i), &prog) {
+                            stats.pruned += 1;
+                            continue;
+                        }
+                    }
+
+                    if start.contains(&i) && sort <= target {
+                        stats.tested += 1;
+                        if Self::satisfies(&prog, spec) {
+                            if seen.insert(prog.to_string()) {
+                                solutions.push(prog.clone());
+                            }
+                        } else if let Some((examples, unifier)) = unifier.as_mut() {
+                            solutions.extend(
+                                unifier
+                                    .add(examples, &prog)
+                                    .filter(|p| seen.insert(p.to_string())),
+                            );
+                        }
+                        if solutions.len() >= wanted {
+                            stats.elapsed = started.elapsed();
+                            return SynthResult {
+                                outcome: Outcome::Solved(solutions),
+                                stats,
+                            };
+                        }
+                    }
+                    own.push((sort, prog));
+                }
+                level.push(own);
+            }
+            if oe_examples.is_none() {
+                if ctx.conc_exprs.contains_key(&(size + 1)) {
+                    for (i, own) in level.iter().enumerate() {
+                        for (sort, prog) in own {
+                            known
+                                .entry(prog.to_string())
+                                .or_insert_with(|| (sort.clone(), vec![]))
+                                .1
+                                .push(i);
+                        }
+                    }
+                }
+                if size > 0 && cached.is_none() {
+                    let progs = level.iter().flatten().map(|(_, prog)| prog.clone());
+                    ctx.conc_exprs.insert(size, progs.collect());
+                }
+            }
+            levels.push(level);
+        }
+
+        stats.elapsed = started.elapsed();
+        SynthResult {
+            outcome: Self::found(solutions).unwrap_or(Outcome::Exhausted {
+                max_size: ctx.config.max_size,
+                explored: stats.explored,
+            }),
+            stats,
+        }
+    }
+
+    // the programs of the given size one nonterminal's productions make from
+    // the levels below it
+    fn level<'a>(
+        productions: &'a [Term<T, U>],
+        included: &'a [Vec<usize>],
+        levels: &'a [Level<T, U>],
+        size: u32,
+    ) -> impl Iterator<Item = Term<T, U>> + 'a {
+        productions.iter().flat_map(move |p| {
+            let holes = holes(p);
+            let own = p.size().max(1);
+            let splits = match size.checked_sub(own) {
+                Some(rest) => splits(rest, holes.len()),
+                None => vec![],
+            };
+            splits.into_iter().flat_map(move |sizes| {
+                let args: Vec<Vec<Term<T, U>>> = holes
+                    .iter()
+                    .zip(sizes)
+                    .map(|((sort, nt), s)| {
+                        let level = &levels[s as usize];
+                        match nt {
+                            Some(nt) => included[*nt]
+                                .iter()
+                                .flat_map(|&i| &level[i])
+                                .map(|(_, prog)| prog.clone())
+                                .collect(),
+                            None => level
+                                .iter()
+                                .flatten()
+                                .filter(|(t, _)| t <= sort)
+                                .map(|(_, prog)| prog.clone())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+                if args.is_empty() {
+                    Either::Left(std::iter::once(p.clone()))
+                } else {
+                    Either::Right(
+                        args.into_iter()
+                            .multi_cartesian_product()
+                            .map(move |args| fill(p, &mut args.into_iter())),
+                    )
+                }
+            })
+        })
+    }
+
+    // the distinct programs of a level kept by an earlier search, sorted
+    // into the nonterminals whose own productions derive them from the
+    // programs below
+    fn classify(
+        nonterminals: &[NonTerminal<T, U>],
+        included: &[Vec<usize>],
+        progs: &[Term<T, U>],
+        known: &HashMap<String, (U, Vec<usize>)>,
+    ) -> Vec<Vec<Term<T, U>>> {
+        let derives = |production: &Term<T, U>, prog: &Term<T, U>| {
+            let mut parts = vec![];
+            matches(production, prog, &mut parts)
+                && holes(production)
+                    .iter()
+                    .zip(&parts)
+                    .all(
+                        |((sort, nt), (_, part))| match known.get(&part.to_string()) {
+                            Some((t, nts)) => match nt {
+                                Some(nt) => nts.iter().any(|i| included[*nt].contains(i)),
+                                None => t <= sort,
+                            },
+                            None => false,
+                        },
+                    )
+        };
+        let mut seen = HashSet::new();
+        let mut classified = vec![vec![]; nonterminals.len()];
+        for prog in progs.iter().filter(|p| seen.insert(p.to_string())) {
+            for (i, nt) in nonterminals.iter().enumerate() {
+                if nt.productions.iter().any(|p| derives(p, prog)) {
+                    classified[i].push(prog.clone());
+                }
+            }
+        }
+        classified
+    }
+
+    fn found(solutions: Vec<Term<T, U>>) -> Option<Outcome<T, U>> {
+        Some(solutions)
+            .filter(|s| !s.is_empty())
+            .map(Outcome::Solved)
+    }
+}
+
+// the programs of one size built by the bottom-up engine, by nonterminal,
+// each with its type
+type Level<T, U> = Vec<Vec<(U, Term<T, U>)>>;
+
+// the types and nonterminals of the holes of a production, in the order
+// `fill` fills them
+fn holes<T: Value, U: Lattice>(term: &Term<T, U>) -> Vec<(U, Option<usize>)> {
+    match &**term {
+        Expr::Hole(sort, _, nt) => vec![(sort.clone(), *nt)],
+        Expr::Call(f) => f.args().into_iter().flat_map(holes).collect(),
+        Expr::If(cond, then, otherwise) => holes(cond)
+            .into_iter()
+            .chain(holes(then))
+            .chain(holes(otherwise))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn fill<T: Value, U: Lattice, I: Iterator<Item = Term<T, U>>>(
+    term: &Term<T, U>,
+    args: &mut I,
+) -> Term<T, U> {
+    match &**term {
+        Expr::Hole(_, _, _) => args.next().unwrap(),
+        Expr::Call(f) => Rc::new(Expr::Call(f.map_args(|a| fill(a, args)))),
+        Expr::If(cond, then, otherwise) => {
+            let cond = fill(cond, args);
+            let then = fill(then, args);
+            Rc::new(Expr::If(cond, then, fill(otherwise, args)))
+        }
+        _ => term.clone(),
+    }
+}
+
+// every way of writing `total` as an ordered sum of `parts` sizes
+fn splits(total: u32, parts: usize) -> Vec<Vec<u32>> {
+    match parts {
+        0 if total == 0 => vec![vec![]],
+        0 => vec![],
+        1 => vec![vec![total]],
+        _ => (0..=total)
+            .flat_map(|first| {
+                splits(total - first, parts - 1)
+                    .into_iter()
+                    .map(move |mut rest| {
+                        rest.insert(0, first);
+                        rest
+                    })
+            })
+            .collect(),
     }
 }
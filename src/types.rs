@@ -1,12 +1,12 @@
 use crate::concrete::StrVal;
 use crate::environment::Environment;
-use crate::interpreter::EvalResult;
+use crate::grammar::{DefaultGrammar, Grammar, NonTerminal};
 use crate::interpreter::Evaluable;
 use crate::interpreter::SynthesisVisitor;
+use crate::interpreter::{expand_call, EvalError, EvalResult};
 use crate::syguslang::{Expr, Func, Term};
 use crate::values::Lattice;
 use crate::values::MixedValue;
-use itertools::Itertools;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -24,6 +24,22 @@ pub enum TypeLattice {
 }
 
 impl Lattice for TypeLattice {
+    fn meet(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Top, t) | (t, Self::Top) => t.clone(),
+            (t1, t2) if t1 == t2 => t1.clone(),
+            _ => Self::Bot,
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Bot, t) | (t, Self::Bot) => t.clone(),
+            (t1, t2) if t1 == t2 => t1.clone(),
+            _ => Self::Top,
+        }
+    }
+
     fn top() -> Self {
         Self::Top
     }
@@ -64,14 +80,14 @@ impl PartialOrd for TypeLattice {
 pub type TypeValues = MixedValue<StrVal, TypeLattice>;
 
 impl TryFrom<TypeValues> for TypeLattice {
-    type Error = &'static str;
+    type Error = EvalError;
 
     fn try_from(value: TypeValues) -> Result<Self, Self::Error> {
         match value {
             TypeValues::Abs(t) => Ok(t),
             TypeValues::Conc(v) => match v {
                 StrVal::Bool(_) => Ok(TypeLattice::Bool),
-                StrVal::Int(_) => Ok(TypeLattice::Integer),
+                StrVal::Int(_) | StrVal::Linear(_) => Ok(TypeLattice::Integer),
                 StrVal::Str(_) => Ok(TypeLattice::String),
             },
         }
@@ -84,11 +100,22 @@ impl Evaluable<TypeValues> for Expr<TypeValues, TypeLattice> {
             Self::Const(v) => Ok(v.clone()),
             Self::Var(x) => env
                 .get(x.clone())
-                .map(|v| v.clone())
-                .ok_or_else(|| "variable not found"),
+                .cloned()
+                .ok_or_else(|| EvalError::UnboundVariable(x.clone())),
             Self::Call(call) => call.eval(env),
-            Self::If(cond, then, otherwise) => unimplemented!(),
-            Self::Hole(abs, _) => Ok(TypeValues::from_abstract(abs.clone())),
+            Self::If(cond, then, otherwise) => {
+                let cond_evaled = TypeLattice::try_from(cond.eval(env)?)?;
+                let then_evaled = TypeLattice::try_from(then.eval(env)?)?;
+                let otherwise_evaled = TypeLattice::try_from(otherwise.eval(env)?)?;
+                match (cond_evaled, then_evaled.join(&otherwise_evaled)) {
+                    (TypeLattice::Bool, joined) if !joined.is_top() => Ok(TypeValues::Abs(joined)),
+                    (c, _) => Err(EvalError::mismatch(
+                        "if",
+                        &[c, then_evaled, otherwise_evaled],
+                    )),
+                }
+            }
+            Self::Hole(abs, _, _) => Ok(TypeValues::from_abstract(abs.clone())),
             _ => unreachable!(),
         }
     }
@@ -96,150 +123,52 @@ impl Evaluable<TypeValues> for Expr<TypeValues, TypeLattice> {
 
 impl Evaluable<TypeValues> for Func<TypeValues, TypeLattice> {
     fn eval(&self, env: &Environment<TypeValues>) -> EvalResult<TypeValues> {
+        let args = self
+            .args()
+            .into_iter()
+            .map(|a| a.eval(env).and_then(TypeLattice::try_from))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (params, ret) = self.signature();
+        let accepted = match self {
+            Func::Eq(_, _) => args[0] == args[1] && !args[0].is_top() && !args[0].is_bot(),
+            _ => args == params,
+        };
+        if accepted {
+            Ok(TypeValues::Abs(ret))
+        } else {
+            Err(EvalError::mismatch(self.name(), &args))
+        }
+    }
+}
+
+impl Func<TypeValues, TypeLattice> {
+    /// Parameter types and result type of the function. `=` compares two
+    /// values of any one sort, which is written as ⊤.
+    pub fn signature(&self) -> (Vec<TypeLattice>, TypeLattice) {
+        use TypeLattice::{Bool, Integer, String, Top};
         match self {
-            Func::Append(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::String)),
-                    _ => Err("append: type error"),
-                }
-            }
-            Func::Replace(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::String)),
-                    _ => Err("replace: type error"),
-                }
-            }
-            Func::Substr(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::String)),
-                    _ => Err("substr: type error"),
-                }
-            }
-            Func::Add(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    _ => Err("add: type error"),
-                }
-            }
-            Func::Sub(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    _ => Err("sub: type error"),
-                }
-            }
-            Func::Len(arg1) => {
-                let arg1_evaled = arg1.eval(env);
-                match arg1_evaled {
-                    Ok(TypeValues::Abs(TypeLattice::String)) => {
-                        Ok(TypeValues::Abs(TypeLattice::Integer))
-                    }
-                    _ => Err("len: type error"),
-                }
-            }
-            Func::At(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::String)),
-                    _ => Err("at: type error"),
-                }
-            }
-            Func::ToStr(arg1) => {
-                let arg1_evaled = arg1.eval(env);
-                match arg1_evaled {
-                    Ok(TypeValues::Abs(TypeLattice::Integer)) => {
-                        Ok(TypeValues::Abs(TypeLattice::String))
-                    }
-                    _ => Err("tostr: type error"),
-                }
-            }
-            Func::ToInt(arg1) => {
-                let arg1_evaled = arg1.eval(env);
-                match arg1_evaled {
-                    Ok(TypeValues::Abs(TypeLattice::String)) => {
-                        Ok(TypeValues::Abs(TypeLattice::Integer))
-                    }
-                    _ => Err("toint: type error"),
-                }
-            }
-            Func::IndexOf(arg1, arg2, arg3) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                let arg3_evaled = arg3.eval(env);
-                match (arg1_evaled, arg2_evaled, arg3_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::Integer)),
-                    _ => Err("indexof: type error"),
-                }
-            }
-            Func::PrefixOf(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::Bool)),
-                    _ => Err("prefixof: type error"),
-                }
-            }
-            Func::SuffixOf(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::Bool)),
-                    _ => Err("suffixof: type error"),
-                }
-            }
-            Func::Contains(arg1, arg2) => {
-                let arg1_evaled = arg1.eval(env);
-                let arg2_evaled = arg2.eval(env);
-                match (arg1_evaled, arg2_evaled) {
-                    (
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                        Ok(TypeValues::Abs(TypeLattice::String)),
-                    ) => Ok(TypeValues::Abs(TypeLattice::Bool)),
-                    _ => Err("contains: type error"),
-                }
-            }
+            Func::Append(_, _) => (vec![String, String], String),
+            Func::Replace(_, _, _) => (vec![String, String, String], String),
+            Func::Substr(_, _, _) => (vec![String, Integer, Integer], String),
+            Func::Add(_, _) | Func::Sub(_, _) => (vec![Integer, Integer], Integer),
+            Func::Len(_) => (vec![String], Integer),
+            Func::At(_, _) => (vec![String, Integer], String),
+            Func::ToStr(_) => (vec![Integer], String),
+            Func::ToInt(_) => (vec![String], Integer),
+            Func::IndexOf(_, _, _) => (vec![String, String, Integer], Integer),
+            Func::PrefixOf(_, _)
+            | Func::SuffixOf(_, _)
+            | Func::Contains(_, _)
+            | Func::StrLt(_, _)
+            | Func::StrLe(_, _) => (vec![String, String], Bool),
+            Func::ReplaceAll(_, _, _) => (vec![String, String, String], String),
+            Func::IsDigit(_) | Func::InRe(_, _) => (vec![String], Bool),
+            Func::FromCode(_) => (vec![Integer], String),
+            Func::ToCode(_) => (vec![String], Integer),
+            Func::Lt(_, _) | Func::Le(_, _) => (vec![Integer, Integer], Bool),
+            Func::Eq(_, _) => (vec![Top, Top], Bool),
+            Func::And(_, _) | Func::Or(_, _) => (vec![Bool, Bool], Bool),
+            Func::Not(_) => (vec![Bool], Bool),
         }
     }
 }
@@ -255,17 +184,35 @@ impl SynthesisVisitor<TypeValues, TypeLattice> for Term<TypeValues, TypeLattice>
     fn visit(
         &self,
         env: &Environment<TypeValues>,
+        grammar: &Grammar<TypeValues, TypeLattice>,
         cache: &mut HashMap<u32, Vec<Term<TypeValues, TypeLattice>>>,
     ) -> Vec<Term<TypeValues, TypeLattice>> {
         let inner: &Expr<TypeValues, TypeLattice> = self;
         match inner {
-            Expr::If(cond, then, otherwise) => unreachable!(),
-            Expr::Call(f) => f.visit(env, cache),
-            Expr::Hole(abs, expr) => Expr::visit_hole(abs, expr, env, cache),
+            // one part at a time, the branches would otherwise multiply out
+            Expr::If(cond, then, otherwise) if cond.has_hole() => cond
+                .visit(env, grammar, cache)
+                .into_iter()
+                .map(|c| Rc::new(Expr::If(c, then.clone(), otherwise.clone())))
+                .collect(),
+            Expr::If(cond, then, otherwise) if then.has_hole() => then
+                .visit(env, grammar, cache)
+                .into_iter()
+                .map(|t| Rc::new(Expr::If(cond.clone(), t, otherwise.clone())))
+                .collect(),
+            Expr::If(cond, then, otherwise) => otherwise
+                .visit(env, grammar, cache)
+                .into_iter()
+                .map(|o| Rc::new(Expr::If(cond.clone(), then.clone(), o)))
+                .collect(),
+            Expr::Call(_) if !self.has_hole() => vec![self.clone()],
+            Expr::Call(f) => f.visit(env, grammar, cache),
+            Expr::Hole(_, _, Some(nt)) => grammar.expand_nonterminal(*nt),
+            Expr::Hole(abs, expr, None) => Expr::visit_hole(abs, expr, grammar),
             Expr::ConcHole(_) => unreachable!(),
             Expr::DepHole => unreachable!(),
-            Expr::Const(c) => vec![self.clone()],
-            Expr::Var(v) => vec![self.clone()],
+            Expr::Const(_c) => vec![self.clone()],
+            Expr::Var(_v) => vec![self.clone()],
         }
     }
 }
@@ -274,164 +221,168 @@ impl SynthesisVisitor<TypeValues, TypeLattice> for Func<TypeValues, TypeLattice>
     fn visit(
         &self,
         env: &Environment<TypeValues>,
+        grammar: &Grammar<TypeValues, TypeLattice>,
         cache: &mut HashMap<u32, Vec<Term<TypeValues, TypeLattice>>>,
     ) -> Vec<Term<TypeValues, TypeLattice>> {
-        match self {
-            Func::Append(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Rc::new(Expr::Call(Func::Append(a1, a2))))
-                .collect(),
-            Func::Replace(arg1, arg2, arg3) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .cartesian_product(arg3.visit(env, cache).into_iter())
-                .map(|((a1, a2), a3)| Rc::new(Expr::Call(Func::Replace(a1, a2, a3))))
-                .collect(),
-            Func::Substr(arg1, arg2, arg3) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .cartesian_product(arg3.visit(env, cache).into_iter())
-                .map(|((a1, a2), a3)| Rc::new(Expr::Call(Func::Substr(a1, a2, a3))))
-                .collect(),
-            Func::Add(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Rc::new(Expr::Call(Func::Add(a1, a2))))
-                .collect(),
-            Func::Sub(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Rc::new(Expr::Call(Func::Sub(a1, a2))))
-                .collect(),
-            Func::Len(arg) => arg
-                .visit(env, cache)
-                .into_iter()
-                .map(|a| Rc::new(Expr::Call(Func::Len(a))))
-                .collect(),
-            Func::At(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Rc::new(Expr::Call(Func::At(a1, a2))))
-                .collect(),
-            Func::ToStr(arg) => arg
-                .visit(env, cache)
-                .into_iter()
-                .map(|a| Rc::new(Expr::Call(Func::ToStr(a))))
-                .collect(),
-            Func::ToInt(arg) => arg
-                .visit(env, cache)
-                .into_iter()
-                .map(|a| Rc::new(Expr::Call(Func::ToInt(a))))
-                .collect(),
-            Func::IndexOf(arg1, arg2, arg3) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .cartesian_product(arg3.visit(env, cache).into_iter())
-                .map(|((a1, a2), a3)| Rc::new(Expr::Call(Func::IndexOf(a1, a2, a3))))
-                .collect(),
-            Func::PrefixOf(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Rc::new(Expr::Call(Func::PrefixOf(a1, a2))))
-                .collect(),
-            Func::SuffixOf(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Rc::new(Expr::Call(Func::SuffixOf(a1, a2))))
-                .collect(),
-            Func::Contains(arg1, arg2) => arg1
-                .visit(env, cache)
-                .into_iter()
-                .cartesian_product(arg2.visit(env, cache).into_iter())
-                .map(|(a1, a2)| Rc::new(Expr::Call(Func::Contains(a1, a2))))
-                .collect(),
-        }
+        let expansions = self
+            .args()
+            .into_iter()
+            .map(|a| a.visit(env, grammar, cache))
+            .collect();
+        expand_call(self, expansions)
     }
 }
 
 impl Expr<TypeValues, TypeLattice> {
     fn visit_hole(
         target: &TypeLattice,
-        expr: &Option<Func<TypeValues, TypeLattice>>,
-        env: &Environment<TypeValues>,
-        cache: &mut HashMap<u32, Vec<Term<TypeValues, TypeLattice>>>,
+        _expr: &Option<Func<TypeValues, TypeLattice>>,
+        grammar: &Grammar<TypeValues, TypeLattice>,
     ) -> Vec<Term<TypeValues, TypeLattice>> {
-        // assert_eq!(expr, None as &Option<Func<TypeValues, TypeLattice>>);
-        let strhole = Rc::new(Expr::Hole(TypeLattice::String, None));
-        let inthole = Rc::new(Expr::Hole(TypeLattice::Integer, None));
         match target {
             TypeLattice::Bot => vec![],
-            TypeLattice::String => vec![
-                Rc::new(Expr::Call(Func::Append(strhole.clone(), strhole.clone()))),
-                Rc::new(Expr::Call(Func::Replace(
-                    strhole.clone(),
-                    strhole.clone(),
-                    strhole.clone(),
-                ))),
-                Rc::new(Expr::Call(Func::Substr(
-                    strhole.clone(),
-                    inthole.clone(),
-                    inthole.clone(),
-                ))),
-                Rc::new(Expr::Call(Func::At(strhole.clone(), inthole.clone()))),
-                Rc::new(Expr::Call(Func::ToStr(inthole.clone()))),
-            ]
-            .into_iter()
-            .chain(cache[&0].clone().into_iter().filter(|e| {
-                e.eval(env)
-                    .map(|v| TypeLattice::try_from(v).unwrap())
-                    .map_or_else(|_| false, |v| v <= TypeLattice::String)
-            }))
-            .collect(),
-            TypeLattice::Integer => vec![
-                Rc::new(Expr::Call(Func::Add(inthole.clone(), inthole.clone()))),
-                Rc::new(Expr::Call(Func::Sub(inthole.clone(), inthole.clone()))),
-                Rc::new(Expr::Call(Func::Len(strhole.clone()))),
-                Rc::new(Expr::Call(Func::ToInt(strhole.clone()))),
-                Rc::new(Expr::Call(Func::IndexOf(
-                    strhole.clone(),
-                    strhole.clone(),
-                    inthole.clone(),
-                ))),
-            ]
-            .into_iter()
-            .chain(cache[&0].clone().into_iter().filter(|e| {
-                e.eval(env)
-                    .map(|v| TypeLattice::try_from(v).unwrap())
-                    .map_or_else(|_| false, |v| v <= TypeLattice::Integer)
-            }))
-            .collect(),
-            TypeLattice::Bool => vec![
-                Rc::new(Expr::Call(Func::PrefixOf(strhole.clone(), strhole.clone()))),
-                Rc::new(Expr::Call(Func::SuffixOf(strhole.clone(), strhole.clone()))),
-                Rc::new(Expr::Call(Func::Contains(strhole.clone(), strhole.clone()))),
-            ]
-            .into_iter()
-            .chain(cache[&0].clone().into_iter().filter(|e| {
-                e.eval(env)
-                    .map(|v| TypeLattice::try_from(v).unwrap())
-                    .map_or_else(|_| false, |v| v <= TypeLattice::Bool)
-            }))
-            .collect(),
-            TypeLattice::Top => Self::visit_hole(&TypeLattice::String, expr, env, cache)
+            TypeLattice::Top => grammar
+                .expand(&TypeLattice::String)
                 .into_iter()
-                .chain(Self::visit_hole(&TypeLattice::Integer, expr, env, cache).into_iter())
-                .chain(Self::visit_hole(&TypeLattice::Bool, expr, env, cache).into_iter())
+                .chain(grammar.expand(&TypeLattice::Integer))
+                .chain(grammar.expand(&TypeLattice::Bool))
                 .collect(),
+            t => grammar.expand(t),
         }
     }
 }
 
+impl Grammar<TypeValues, TypeLattice> {
+    /// The string grammar of the 2017 SLIA core, with the given constants and
+    /// variables sorted into the nonterminal matching their type.
+    pub fn slia(
+        leaves: &[Term<TypeValues, TypeLattice>],
+        env: &Environment<TypeValues>,
+    ) -> Grammar<TypeValues, TypeLattice> {
+        Self::sorted(Self::slia_core(), leaves, env)
+    }
+
+    /// Every component of the DSL: the SLIA core, plus `replace_all`, code
+    /// points, comparisons, equality, the Boolean connectives and the Bool
+    /// constants. Its larger search space makes it opt-in.
+    pub fn slia_full(
+        leaves: &[Term<TypeValues, TypeLattice>],
+        env: &Environment<TypeValues>,
+    ) -> Grammar<TypeValues, TypeLattice> {
+        let strhole = Rc::new(Expr::Hole(TypeLattice::String, None, None));
+        let inthole = Rc::new(Expr::Hole(TypeLattice::Integer, None, None));
+        let boolhole = Rc::new(Expr::Hole(TypeLattice::Bool, None, None));
+
+        let [mut nt_string, mut nt_int, mut nt_bool] = Self::slia_core();
+        nt_string.productions.extend([
+            Rc::new(Expr::Call(Func::ReplaceAll(
+                strhole.clone(),
+                strhole.clone(),
+                strhole.clone(),
+            ))),
+            Rc::new(Expr::Call(Func::FromCode(inthole.clone()))),
+        ]);
+        nt_int
+            .productions
+            .push(Rc::new(Expr::Call(Func::ToCode(strhole.clone()))));
+        nt_bool.productions.extend([
+            Rc::new(Expr::Call(Func::StrLt(strhole.clone(), strhole.clone()))),
+            Rc::new(Expr::Call(Func::StrLe(strhole.clone(), strhole.clone()))),
+            Rc::new(Expr::Call(Func::IsDigit(strhole.clone()))),
+            Rc::new(Expr::Call(Func::Lt(inthole.clone(), inthole.clone()))),
+            Rc::new(Expr::Call(Func::Le(inthole.clone(), inthole.clone()))),
+            Rc::new(Expr::Call(Func::Eq(inthole.clone(), inthole))),
+            Rc::new(Expr::Call(Func::Eq(strhole.clone(), strhole))),
+            Rc::new(Expr::Call(Func::And(boolhole.clone(), boolhole.clone()))),
+            Rc::new(Expr::Call(Func::Or(boolhole.clone(), boolhole.clone()))),
+            Rc::new(Expr::Call(Func::Not(boolhole))),
+        ]);
+
+        // the Bool constants are always available, whatever the caller supplies
+        let bools = [true, false]
+            .iter()
+            .map(|b| Rc::new(Expr::Const(TypeValues::from_concrete(StrVal::Bool(*b)))))
+            .filter(|b| !leaves.contains(b));
+        let leaves: Vec<_> = leaves.iter().cloned().chain(bools).collect();
+        Self::sorted([nt_string, nt_int, nt_bool], &leaves, env)
+    }
+
+    fn slia_core() -> [NonTerminal<TypeValues, TypeLattice>; 3] {
+        let strhole = Rc::new(Expr::Hole(TypeLattice::String, None, None));
+        let inthole = Rc::new(Expr::Hole(TypeLattice::Integer, None, None));
+
+        let mut nt_string = NonTerminal::new("ntString".to_string(), TypeLattice::String);
+        nt_string.productions = vec![
+            Rc::new(Expr::Call(Func::Append(strhole.clone(), strhole.clone()))),
+            Rc::new(Expr::Call(Func::Replace(
+                strhole.clone(),
+                strhole.clone(),
+                strhole.clone(),
+            ))),
+            Rc::new(Expr::Call(Func::Substr(
+                strhole.clone(),
+                inthole.clone(),
+                inthole.clone(),
+            ))),
+            Rc::new(Expr::Call(Func::At(strhole.clone(), inthole.clone()))),
+            Rc::new(Expr::Call(Func::ToStr(inthole.clone()))),
+        ];
+
+        let mut nt_int = NonTerminal::new("ntInt".to_string(), TypeLattice::Integer);
+        nt_int.productions = vec![
+            Rc::new(Expr::Call(Func::Add(inthole.clone(), inthole.clone()))),
+            Rc::new(Expr::Call(Func::Sub(inthole.clone(), inthole.clone()))),
+            Rc::new(Expr::Call(Func::Len(strhole.clone()))),
+            Rc::new(Expr::Call(Func::ToInt(strhole.clone()))),
+            Rc::new(Expr::Call(Func::IndexOf(
+                strhole.clone(),
+                strhole.clone(),
+                inthole,
+            ))),
+        ];
+
+        let mut nt_bool = NonTerminal::new("ntBool".to_string(), TypeLattice::Bool);
+        nt_bool.productions = vec![
+            Rc::new(Expr::Call(Func::PrefixOf(strhole.clone(), strhole.clone()))),
+            Rc::new(Expr::Call(Func::SuffixOf(strhole.clone(), strhole.clone()))),
+            Rc::new(Expr::Call(Func::Contains(strhole.clone(), strhole))),
+        ];
+
+        [nt_string, nt_int, nt_bool]
+    }
+
+    // the nonterminals, each with the leaves whose type fits its sort
+    fn sorted(
+        nonterminals: [NonTerminal<TypeValues, TypeLattice>; 3],
+        leaves: &[Term<TypeValues, TypeLattice>],
+        env: &Environment<TypeValues>,
+    ) -> Grammar<TypeValues, TypeLattice> {
+        let mut grammar = Grammar::new();
+        for mut nt in nonterminals {
+            nt.consts = leaves
+                .iter()
+                .filter(|e| {
+                    e.eval(env)
+                        .map(|v| TypeLattice::try_from(v).unwrap())
+                        .map_or_else(|_| false, |v| v <= nt.sort)
+                })
+                .cloned()
+                .collect();
+            grammar.add(nt);
+        }
+        grammar
+    }
+}
+
+impl DefaultGrammar<TypeValues> for TypeLattice {
+    fn default_grammar(
+        leaves: &[Term<TypeValues, TypeLattice>],
+        env: &Environment<TypeValues>,
+    ) -> Grammar<TypeValues, TypeLattice> {
+        Grammar::slia(leaves, env)
+    }
+}
+
 impl From<Term<TypeValues, TypeLattice>> for Expr<StrVal, TypeLattice> {
     fn from(value: Term<TypeValues, TypeLattice>) -> Self {
         let inner: &Expr<TypeValues, TypeLattice> = &value;
@@ -440,7 +391,11 @@ impl From<Term<TypeValues, TypeLattice>> for Expr<StrVal, TypeLattice> {
             Expr::Const(_) => unreachable!(),
             Expr::Var(x) => Expr::Var(x.clone()),
             Expr::Call(f) => Expr::Call(Func::from(f.clone())),
-            Expr::If(_, _, _) => unimplemented!(),
+            Expr::If(cond, then, otherwise) => Expr::If(
+                Rc::new(Expr::from(cond.clone())),
+                Rc::new(Expr::from(then.clone())),
+                Rc::new(Expr::from(otherwise.clone())),
+            ),
             _ => unreachable!(),
         }
     }
@@ -448,44 +403,6 @@ impl From<Term<TypeValues, TypeLattice>> for Expr<StrVal, TypeLattice> {
 
 impl From<Func<TypeValues, TypeLattice>> for Func<StrVal, TypeLattice> {
     fn from(value: Func<TypeValues, TypeLattice>) -> Self {
-        match value {
-            Func::Append(arg1, arg2) => {
-                Func::Append(Rc::new(Expr::from(arg1)), Rc::new(Expr::from(arg2)))
-            }
-            Func::Replace(arg1, arg2, arg3) => Func::Replace(
-                Rc::new(Expr::from(arg1)),
-                Rc::new(Expr::from(arg2)),
-                Rc::new(Expr::from(arg3)),
-            ),
-            Func::Substr(arg1, arg2, arg3) => Func::Substr(
-                Rc::new(Expr::from(arg1)),
-                Rc::new(Expr::from(arg2)),
-                Rc::new(Expr::from(arg3)),
-            ),
-            Func::Add(arg1, arg2) => {
-                Func::Add(Rc::new(Expr::from(arg1)), Rc::new(Expr::from(arg2)))
-            }
-            Func::Sub(arg1, arg2) => {
-                Func::Sub(Rc::new(Expr::from(arg1)), Rc::new(Expr::from(arg2)))
-            }
-            Func::Len(arg1) => Func::Len(Rc::new(Expr::from(arg1))),
-            Func::At(arg1, arg2) => Func::At(Rc::new(Expr::from(arg1)), Rc::new(Expr::from(arg2))),
-            Func::ToStr(arg1) => Func::ToStr(Rc::new(Expr::from(arg1))),
-            Func::ToInt(arg1) => Func::ToInt(Rc::new(Expr::from(arg1))),
-            Func::IndexOf(arg1, arg2, arg3) => Func::IndexOf(
-                Rc::new(Expr::from(arg1)),
-                Rc::new(Expr::from(arg2)),
-                Rc::new(Expr::from(arg3)),
-            ),
-            Func::PrefixOf(arg1, arg2) => {
-                Func::PrefixOf(Rc::new(Expr::from(arg1)), Rc::new(Expr::from(arg2)))
-            }
-            Func::SuffixOf(arg1, arg2) => {
-                Func::SuffixOf(Rc::new(Expr::from(arg1)), Rc::new(Expr::from(arg2)))
-            }
-            Func::Contains(arg1, arg2) => {
-                Func::Contains(Rc::new(Expr::from(arg1)), Rc::new(Expr::from(arg2)))
-            }
-        }
+        value.map_args(|a| Rc::new(Expr::from(a.clone())))
     }
 }
@@ -4,10 +4,18 @@ use std::fmt;
 use std::fmt::Display;
 
 pub trait Lattice: PartialOrd + PartialEq + Clone + Display {
-    // fn meet(&self, other: &Self) -> Self;
-    // fn join(&self, other: &Self) -> Self;
+    fn meet(&self, other: &Self) -> Self;
+    fn join(&self, other: &Self) -> Self;
     fn top() -> Self;
     fn bot() -> Self;
+
+    fn is_top(&self) -> bool {
+        *self == Self::top()
+    }
+
+    fn is_bot(&self) -> bool {
+        *self == Self::bot()
+    }
 }
 
 pub trait Value: Clone + Display + PartialEq {
@@ -26,10 +34,7 @@ pub enum MixedValue<T: Value, U: Lattice> {
 
 impl<T: Value, U: Lattice> Value for MixedValue<T, U> {
     fn is_abstract(&self) -> bool {
-        match self {
-            Self::Abs(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Abs(_))
     }
 }
 
@@ -1,10 +1,11 @@
 use absynthe::concrete::StrVal;
 use absynthe::environment::Environment;
-use absynthe::interpreter::EvalResult;
 use absynthe::linear::LinearExpr;
-use absynthe::types::{TypeLattice, TypeValues};
-// use absynthe::strlenlat::StrLenLat;
+use absynthe::r#abstract::StrValAbs;
+use absynthe::spec::{CandidateFn, Spec};
+use absynthe::strlenlat::StrLenLat;
 use absynthe::synthesizer::{Context, Synthesizer};
+use absynthe::types::{TypeLattice, TypeValues};
 
 #[test]
 fn bikes() {
@@ -21,11 +22,53 @@ fn bikes() {
     let mut env = Environment::new();
     env.put("arg0".to_string(), TypeValues::Abs(TypeLattice::String));
 
-    // let target = LinearExpr::from("x".to_string()) - LinearExpr::from(3);
+    let mut ctx = Context::new(&consts, &env);
+
+    let test = |f: CandidateFn| {
+        f(&[StrVal::from("Ducati100".to_string())]) == Ok(StrVal::from("Ducati".to_string()))
+            && f(&[StrVal::from("Honda125".to_string())]) == Ok(StrVal::from("Honda".to_string()))
+            && f(&[StrVal::from("Ducati250".to_string())]) == Ok(StrVal::from("Ducati".to_string()))
+            && f(&[StrVal::from("Honda250".to_string())]) == Ok(StrVal::from("Honda".to_string()))
+            && f(&[StrVal::from("Honda550".to_string())]) == Ok(StrVal::from("Honda".to_string()))
+            && f(&[StrVal::from("Ducati125".to_string())]) == Ok(StrVal::from("Ducati".to_string()))
+    };
+
+    let result = Synthesizer::synthesize(
+        &mut ctx,
+        TypeLattice::String,
+        &env,
+        Spec::Test(Box::new(test)),
+    );
+    let prog = &result.solutions()[0];
+
+    assert_eq!("(substr arg0 0 (- (len arg0) 3))", format!("{}", prog));
+}
+
+#[test]
+fn bikes_strlen() {
+    let consts = vec![
+        StrVal::from(0),
+        StrVal::from(1),
+        StrVal::from(2),
+        StrVal::from(3),
+        StrVal::from(4),
+        StrVal::from(5),
+        StrVal::from(" ".to_string()),
+    ];
+
+    let mut env = Environment::new();
+    env.put(
+        "arg0".to_string(),
+        StrValAbs::Abs(StrLenLat::from("x".to_string())),
+    );
+
+    let target = LinearExpr::from("x".to_string())
+        .checked_sub(LinearExpr::from(3))
+        .unwrap();
 
     let mut ctx = Context::new(&consts, &env);
 
-    let test = |f: Box<dyn Fn(&[StrVal]) -> EvalResult<StrVal>>| {
+    let test = |f: CandidateFn| {
         f(&[StrVal::from("Ducati100".to_string())]) == Ok(StrVal::from("Ducati".to_string()))
             && f(&[StrVal::from("Honda125".to_string())]) == Ok(StrVal::from("Honda".to_string()))
             && f(&[StrVal::from("Ducati250".to_string())]) == Ok(StrVal::from("Ducati".to_string()))
@@ -34,7 +77,13 @@ fn bikes() {
             && f(&[StrVal::from("Ducati125".to_string())]) == Ok(StrVal::from("Ducati".to_string()))
     };
 
-    let prog = &Synthesizer::synthesize(&mut ctx, TypeLattice::String, &env, Box::new(test))[0];
+    let result = Synthesizer::synthesize(
+        &mut ctx,
+        StrLenLat::from(target),
+        &env,
+        Spec::Test(Box::new(test)),
+    );
+    let prog = &result.solutions()[0];
 
     assert_eq!("(substr arg0 0 (- (len arg0) 3))", format!("{}", prog));
 }
@@ -0,0 +1,96 @@
+use absynthe::cegis::{counterexample, Cegis, Check};
+use absynthe::concrete::StrVal;
+use absynthe::sygus::Problem;
+use absynthe::syguslang::{Expr, Func};
+use absynthe::synthesizer::{Context, Outcome, SearchConfig};
+use absynthe::types::TypeLattice;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const DOTTED: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String (ntString))
+         (ntString String (name " " "." (str.++ ntString ntString)))))
+    (declare-var name String)
+    (constraint (= (str.len (f name)) (+ (str.len name) 1)))
+    (constraint (str.suffixof "." (f name)))
+    (check-synth)
+"#;
+
+// z3 cannot settle whether a long string of digits can spell three times
+// the length of its triple in reasonable time
+const HARD: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) Int
+        ((Start Int ((str.len ntString)))
+         (ntString String (name (str.++ ntString ntString)))))
+    (declare-var name String)
+    (constraint (or (<= (str.len name) 40) (not (= (str.to_int name) (* 3 (f name))))))
+    (check-synth)
+"#;
+
+#[test]
+fn relational_spec() {
+    let problem = Problem::parse(DOTTED).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+
+    let result = Cegis::synthesize(&mut ctx, problem.target(), &env, &problem).unwrap();
+
+    assert_eq!(
+        problem.define_fun(&Expr::from(result.solutions()[0].clone())),
+        "(define-fun f ((name String)) String (str.++ name \".\"))"
+    );
+}
+
+#[test]
+fn verifier() {
+    let problem = Problem::parse(DOTTED).unwrap();
+    let name = || Rc::new(Expr::Var("arg0".to_string()));
+    let dot = || Rc::new(Expr::Const(StrVal::from(".".to_string())));
+
+    let dot_first: Expr<StrVal, TypeLattice> = Expr::Call(Func::Append(dot(), name()));
+    match counterexample(&problem, &dot_first, None) {
+        Ok(Check::Counterexample(point)) => match &point[..] {
+            [StrVal::Str(s)] => assert!(!s.is_empty() && !s.ends_with('.')),
+            _ => panic!("unexpected counterexample {:?}", point),
+        },
+        check => panic!("expected a counterexample, got {:?}", check),
+    }
+
+    let dot_last: Expr<StrVal, TypeLattice> = Expr::Call(Func::Append(name(), dot()));
+    assert_eq!(
+        counterexample(&problem, &dot_last, None),
+        Ok(Check::Verified)
+    );
+}
+
+#[test]
+fn verifier_timeout() {
+    let problem = Problem::parse(HARD).unwrap();
+    let name = || Rc::new(Expr::Var("arg0".to_string()));
+    let twice = Rc::new(Expr::Call(Func::Append(name(), name())));
+    let thrice: Expr<StrVal, TypeLattice> =
+        Expr::Call(Func::Len(Rc::new(Expr::Call(Func::Append(twice, name())))));
+
+    let check = counterexample(&problem, &thrice, Some(Duration::from_millis(50)));
+    assert_eq!(check, Ok(Check::Timeout));
+    // no time left at all
+    let check = counterexample(&problem, &thrice, Some(Duration::ZERO));
+    assert_eq!(check, Ok(Check::Timeout));
+}
+
+#[test]
+fn cegis_timeout() {
+    let problem = Problem::parse(HARD).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    let started = Instant::now();
+    ctx.set_config(SearchConfig::new().timeout(Duration::from_millis(300)));
+
+    let result = Cegis::synthesize(&mut ctx, problem.target(), &env, &problem).unwrap();
+
+    assert!(matches!(result.outcome, Outcome::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
@@ -0,0 +1,68 @@
+use std::process::Command;
+
+#[test]
+fn solve_bikes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_absynthe"))
+        .args(["solve", "sygus-strings/bikes.sl"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "(define-fun f ((name String)) String (str.substr name 0 (- (str.len name) 3)))\n"
+    );
+}
+
+#[test]
+fn solve_timeout() {
+    let output = Command::new(env!("CARGO_BIN_EXE_absynthe"))
+        .args(["solve", "sygus-strings/dr-name.sl", "--timeout", "1"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn solve_missing_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_absynthe"))
+        .args(["solve", "sygus-strings/does-not-exist.sl"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn bench_reports() {
+    let dir = std::env::temp_dir().join(format!("absynthe-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy("sygus-strings/bikes.sl", dir.join("bikes.sl")).unwrap();
+    std::fs::write(dir.join("notes.txt"), "not a problem").unwrap();
+    let (csv, md) = (dir.join("out.csv"), dir.join("out.md"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .arg(&dir)
+        .args(["--timeout", "30", "--engine", "bottom-up", "--oe"])
+        .arg("--csv")
+        .arg(&csv)
+        .arg("--markdown")
+        .arg(&md)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let csv = std::fs::read_to_string(csv).unwrap();
+    let rows: Vec<_> = csv.lines().collect();
+    assert_eq!(rows[0], "problem,status,time_s,explored,size,solution");
+    assert_eq!(rows.len(), 2);
+    assert!(rows[1].starts_with("bikes.sl,solved,"));
+    assert!(rows[1].ends_with(
+        ",6,(define-fun f ((name String)) String (str.substr name 0 (- (str.len name) 3)))"
+    ));
+    let md = std::fs::read_to_string(md).unwrap();
+    assert!(md.starts_with("Solved 1 of 1 with the bottom-up engine"));
+    assert!(md.contains("| bikes.sl | solved |"));
+}
@@ -0,0 +1,334 @@
+use absynthe::codegen::{emit, EmitError, Language};
+use absynthe::concrete::StrVal;
+use absynthe::interpreter::Evaluable;
+use absynthe::linear::LinearExpr;
+use absynthe::regex::Regex;
+use absynthe::spec::bind_args;
+use absynthe::syguslang::{Expr, Func, Term};
+use absynthe::types::TypeLattice;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::rc::Rc;
+
+type Prog = Term<StrVal, TypeLattice>;
+
+fn call(f: Func<StrVal, TypeLattice>) -> Prog {
+    Rc::new(Expr::Call(f))
+}
+
+fn lit<V: Into<StrVal>>(v: V) -> Prog {
+    Rc::new(Expr::Const(v.into()))
+}
+
+// (ite (str.in_re s (re.+ (re.range "0" "9")))
+//      (str.from_int (+ (str.to_int s) k))
+//      (ite (= s "")
+//           "empty"
+//           (str.++ (str.++ (str.substr s (str.indexof s " " 0) (+ k 3))
+//                           (str.replace_all (str.at s k) "l" "L"))
+//                   (str.from_code (+ (str.to_code (str.at s 0)) 1)))))
+fn program() -> Prog {
+    let s = || Rc::new(Expr::Var("arg0".to_string()));
+    let k = || Rc::new(Expr::Var("arg1".to_string()));
+    let digits = Regex::Plus(Box::new(Regex::Range('0', '9')));
+    let number = call(Func::ToStr(call(Func::Add(call(Func::ToInt(s())), k()))));
+    let word = call(Func::Substr(
+        s(),
+        call(Func::IndexOf(s(), lit(" ".to_string()), lit(0))),
+        call(Func::Add(k(), lit(3))),
+    ));
+    let shouted = call(Func::ReplaceAll(
+        call(Func::At(s(), k())),
+        lit("l".to_string()),
+        lit("L".to_string()),
+    ));
+    let next = call(Func::FromCode(call(Func::Add(
+        call(Func::ToCode(call(Func::At(s(), lit(0))))),
+        lit(1),
+    ))));
+    let rest = Rc::new(Expr::If(
+        call(Func::Eq(s(), lit(String::new()))),
+        lit("empty".to_string()),
+        call(Func::Append(call(Func::Append(word, shouted)), next)),
+    ));
+    Rc::new(Expr::If(call(Func::InRe(s(), digits)), number, rest))
+}
+
+const INPUTS: &[(&str, i64)] = &[
+    ("41", 1),
+    ("Ada Lovelace", 1),
+    ("Ada", 10),
+    ("héllo wörld", 1),
+    ("", -1),
+    ("007", -10),
+];
+
+fn expected() -> String {
+    let prog = program();
+    INPUTS
+        .iter()
+        .map(|(s, k)| {
+            let env = bind_args(&[StrVal::from(s.to_string()), StrVal::from(*k)]);
+            match prog.eval(&env) {
+                Ok(StrVal::Str(out)) => format!("{}\n", out),
+                out => panic!("unexpected output {:?}", out),
+            }
+        })
+        .collect()
+}
+
+fn generate(lang: Language) -> String {
+    let params = [
+        ("s".to_string(), TypeLattice::String),
+        ("k".to_string(), TypeLattice::Integer),
+    ];
+    emit(lang, "f", &params, &TypeLattice::String, &program()).unwrap()
+}
+
+fn scratch(file: &str, src: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("absynthe-codegen-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(file);
+    fs::write(&path, src).unwrap();
+    path
+}
+
+fn stdout(cmd: &mut Command) -> String {
+    let output = cmd.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn rust_backend() {
+    let calls: String = INPUTS
+        .iter()
+        .map(|(s, k)| format!("    println!(\"{{}}\", f({:?}, {}));\n", s, k))
+        .collect();
+    let src = format!("{}\nfn main() {{\n{}}}\n", generate(Language::Rust), calls);
+    let path = scratch("f.rs", &src);
+    let bin = path.with_extension("bin");
+    stdout(Command::new("rustc").arg(&path).arg("-o").arg(&bin));
+    assert_eq!(stdout(&mut Command::new(&bin)), expected());
+}
+
+#[test]
+fn python_backend() {
+    let calls: String = INPUTS
+        .iter()
+        .map(|(s, k)| format!("print(f({:?}, {}))\n", s, k))
+        .collect();
+    let src = format!("{}\n{}", generate(Language::Python), calls);
+    let path = scratch("f.py", &src);
+    let out = stdout(
+        Command::new("python3")
+            .arg(&path)
+            .env("PYTHONIOENCODING", "utf-8"),
+    );
+    assert_eq!(out, expected());
+}
+
+#[test]
+fn javascript_backend() {
+    let calls: String = INPUTS
+        .iter()
+        .map(|(s, k)| format!("console.log(f({:?}, {}n));\n", s, k))
+        .collect();
+    let src = format!("{}\n{}", generate(Language::JavaScript), calls);
+    let path = scratch("f.js", &src);
+    assert_eq!(stdout(Command::new("node").arg(&path)), expected());
+}
+
+// parameters named after keywords, builtins and helpers, and two that only
+// differ in characters no target language allows in a name
+const AWKWARD: &[(&str, TypeLattice)] = &[
+    ("class", TypeLattice::String),
+    ("len", TypeLattice::String),
+    ("str_len", TypeLattice::String),
+    ("a-b", TypeLattice::Integer),
+    ("a_b", TypeLattice::Integer),
+    ("self", TypeLattice::Integer),
+    ("this", TypeLattice::Bool),
+    ("for", TypeLattice::Integer),
+];
+
+// one value per parameter in `AWKWARD`
+type AwkwardInput = (
+    &'static str,
+    &'static str,
+    &'static str,
+    i64,
+    i64,
+    i64,
+    bool,
+    i64,
+);
+
+const AWKWARD_INPUTS: &[AwkwardInput] = &[
+    ("x", "abc", "hello", 2, 1, 4, true, 10),
+    ("y", "", "hi", -1, 0, 3, true, 0),
+    ("z", "abc", "hello", 2, 1, 4, false, 10),
+];
+
+// (ite this
+//      (str.++ (str.++ class (str.from_int (+ (+ (+ (str.len len) a-b) self) for)))
+//              (str.substr str_len a_b (str.len str_len)))
+//      "no")
+fn awkward_program() -> Prog {
+    let var = |i: usize| Rc::new(Expr::Var(format!("arg{}", i)));
+    let len = call(Func::Len(var(1)));
+    let total = call(Func::Add(
+        call(Func::Add(call(Func::Add(len, var(3))), var(5))),
+        var(7),
+    ));
+    let rest = call(Func::Substr(var(2), var(4), call(Func::Len(var(2)))));
+    let yes = call(Func::Append(
+        call(Func::Append(var(0), call(Func::ToStr(total)))),
+        rest,
+    ));
+    Rc::new(Expr::If(var(6), yes, lit("no".to_string())))
+}
+
+fn awkward_expected() -> String {
+    let prog = awkward_program();
+    AWKWARD_INPUTS
+        .iter()
+        .map(|&(c, l, s, ab, a_b, me, this, r)| {
+            let env = bind_args(&[
+                StrVal::from(c.to_string()),
+                StrVal::from(l.to_string()),
+                StrVal::from(s.to_string()),
+                StrVal::from(ab),
+                StrVal::from(a_b),
+                StrVal::from(me),
+                StrVal::Bool(this),
+                StrVal::from(r),
+            ]);
+            match prog.eval(&env) {
+                Ok(StrVal::Str(out)) => format!("{}\n", out),
+                out => panic!("unexpected output {:?}", out),
+            }
+        })
+        .collect()
+}
+
+fn awkward_generate(lang: Language) -> String {
+    let params = AWKWARD
+        .iter()
+        .map(|(x, sort)| (x.to_string(), sort.clone()))
+        .collect::<Vec<_>>();
+    emit(lang, "f", &params, &TypeLattice::String, &awkward_program()).unwrap()
+}
+
+// the calls to `f` on the awkward inputs, given how the language writes an
+// integer and a boolean
+fn awkward_calls(int: impl Fn(i64) -> String, boolean: impl Fn(bool) -> String) -> Vec<String> {
+    AWKWARD_INPUTS
+        .iter()
+        .map(|&(c, l, s, ab, a_b, me, this, r)| {
+            format!(
+                "f({:?}, {:?}, {:?}, {}, {}, {}, {}, {})",
+                c,
+                l,
+                s,
+                int(ab),
+                int(a_b),
+                int(me),
+                boolean(this),
+                int(r)
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn rust_awkward_names() {
+    let calls: String = awkward_calls(|i| i.to_string(), |b| b.to_string())
+        .iter()
+        .map(|call| format!("    println!(\"{{}}\", {});\n", call))
+        .collect();
+    let src = format!(
+        "{}\nfn main() {{\n{}}}\n",
+        awkward_generate(Language::Rust),
+        calls
+    );
+    let path = scratch("awkward.rs", &src);
+    let bin = path.with_extension("bin");
+    stdout(Command::new("rustc").arg(&path).arg("-o").arg(&bin));
+    assert_eq!(stdout(&mut Command::new(&bin)), awkward_expected());
+}
+
+#[test]
+fn python_awkward_names() {
+    let boolean = |b| if b { "True" } else { "False" }.to_string();
+    let calls: String = awkward_calls(|i| i.to_string(), boolean)
+        .iter()
+        .map(|call| format!("print({})\n", call))
+        .collect();
+    let src = format!("{}\n{}", awkward_generate(Language::Python), calls);
+    let path = scratch("awkward.py", &src);
+    assert_eq!(
+        stdout(Command::new("python3").arg(&path)),
+        awkward_expected()
+    );
+}
+
+#[test]
+fn javascript_awkward_names() {
+    let calls: String = awkward_calls(|i| format!("{}n", i), |b| b.to_string())
+        .iter()
+        .map(|call| format!("console.log({});\n", call))
+        .collect();
+    let src = format!("{}\n{}", awkward_generate(Language::JavaScript), calls);
+    let path = scratch("awkward.js", &src);
+    assert_eq!(stdout(Command::new("node").arg(&path)), awkward_expected());
+}
+
+// a function named after a helper or builtin must not replace it
+#[test]
+fn function_named_after_a_helper() {
+    let params = [("s".to_string(), TypeLattice::String)];
+    let body = call(Func::Len(Rc::new(Expr::Var("arg0".to_string()))));
+    let cases = [
+        (Language::Rust, "str_len", "fn str_len("),
+        (Language::Python, "len", "def len("),
+        (Language::JavaScript, "str_len", "function str_len("),
+    ];
+    for (lang, name, def) in cases.iter() {
+        let src = emit(*lang, name, &params, &TypeLattice::Integer, &body).unwrap();
+        assert!(src.matches(def).count() <= 1, "{}", src);
+        assert!(src.contains(&format!("{}_1(", name)), "{}", src);
+    }
+}
+
+// only concrete, complete programs have code
+#[test]
+fn unemittable_programs() {
+    let params = [("s".to_string(), TypeLattice::String)];
+    let symbolic = lit(StrVal::Linear(LinearExpr::from("x".to_string())));
+    let hole: Prog = Rc::new(Expr::Hole(TypeLattice::Integer, None, None));
+    for lang in [Language::Rust, Language::Python, Language::JavaScript] {
+        assert_eq!(
+            emit(lang, "f", &params, &TypeLattice::Integer, &symbolic),
+            Err(EmitError::SymbolicInteger("1x".to_string()))
+        );
+        let len = call(Func::Len(call(Func::Substr(
+            Rc::new(Expr::Var("arg0".to_string())),
+            lit(0),
+            hole.clone(),
+        ))));
+        assert_eq!(
+            emit(lang, "f", &params, &TypeLattice::Integer, &len),
+            Err(EmitError::Incomplete)
+        );
+    }
+    assert_eq!(
+        emit(Language::Rust, "f", &params, &TypeLattice::Top, &lit(0)),
+        Err(EmitError::Sort(TypeLattice::Top))
+    );
+}
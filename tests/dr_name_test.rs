@@ -1,13 +1,13 @@
 use absynthe::concrete::StrVal;
 use absynthe::environment::Environment;
-use absynthe::interpreter::EvalResult;
-use absynthe::linear::LinearExpr;
-use absynthe::strlenlat::StrLenLat;
+use absynthe::spec::{CandidateFn, Spec};
 use absynthe::synthesizer::{Context, Synthesizer};
 use absynthe::types::{TypeLattice, TypeValues};
 
+// the length of the output is not linear in that of the input, and the
+// length domain has no indexof, so only the type domain can find this one
 #[test]
-#[ignore]
+#[ignore = "the search takes minutes"]
 fn dr_name() {
     let consts = vec![
         StrVal::from(0),
@@ -21,11 +21,9 @@ fn dr_name() {
     let mut env = Environment::new();
     env.put("arg0".to_string(), TypeValues::Abs(TypeLattice::String));
 
-    let target = LinearExpr::from("x".to_string()) - LinearExpr::from(3);
-
     let mut ctx = Context::new(&consts, &env);
 
-    let test = |f: Box<dyn Fn(&[StrVal]) -> EvalResult<StrVal>>| {
+    let test = |f: CandidateFn| {
         f(&[StrVal::from("Nancy FreeHafer".to_string())])
             == Ok(StrVal::from("Dr. Nancy".to_string()))
             && f(&[StrVal::from("Andrew Cencici".to_string())])
@@ -36,7 +34,13 @@ fn dr_name() {
                 == Ok(StrVal::from("Dr. Mariya".to_string()))
     };
 
-    let prog = &Synthesizer::synthesize(&mut ctx, TypeLattice::String, &env, Box::new(test))[0];
+    let result = Synthesizer::synthesize(
+        &mut ctx,
+        TypeLattice::String,
+        &env,
+        Spec::Test(Box::new(test)),
+    );
+    let prog = &result.solutions()[0];
 
     assert_eq!(
         "(append \"Dr.\" (append \" \" (substr arg0 0 (indexof arg0 \" \" 0))))",
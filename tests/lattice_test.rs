@@ -0,0 +1,105 @@
+use absynthe::linear::LinearExpr;
+use absynthe::strlenlat::StrLenLat;
+use absynthe::types::TypeLattice;
+use absynthe::values::Lattice;
+use std::fmt::Debug;
+
+// checks every law over all pairs and triples of the given elements
+fn check_laws<L: Lattice + Debug>(elems: &[L]) {
+    let top = L::top();
+    let bot = L::bot();
+    assert!(top.is_top() && !top.is_bot());
+    assert!(bot.is_bot() && !bot.is_top());
+
+    for a in elems {
+        assert_eq!(a.join(a), *a, "join idempotence on {}", a);
+        assert_eq!(a.meet(a), *a, "meet idempotence on {}", a);
+        assert_eq!(a.join(&bot), *a, "bot is the join identity for {}", a);
+        assert_eq!(a.meet(&top), *a, "top is the meet identity for {}", a);
+        assert!(a.join(&top).is_top(), "top absorbs {} in join", a);
+        assert!(a.meet(&bot).is_bot(), "bot absorbs {} in meet", a);
+
+        for b in elems {
+            assert_eq!(a.join(b), b.join(a), "join commutes on {} {}", a, b);
+            assert_eq!(a.meet(b), b.meet(a), "meet commutes on {} {}", a, b);
+            assert_eq!(a.join(&a.meet(b)), *a, "absorption on {} {}", a, b);
+            assert_eq!(a.meet(&a.join(b)), *a, "absorption on {} {}", a, b);
+
+            let join = a.join(b);
+            let meet = a.meet(b);
+            assert!(*a <= join && *b <= join, "{} is an upper bound", join);
+            assert!(meet <= *a && meet <= *b, "{} is a lower bound", meet);
+            assert_eq!(*a <= *b, join == *b, "join agrees with the order");
+            assert_eq!(*a <= *b, meet == *a, "meet agrees with the order");
+
+            for c in elems {
+                assert_eq!(
+                    a.join(&b.join(c)),
+                    a.join(b).join(c),
+                    "join associativity on {} {} {}",
+                    a,
+                    b,
+                    c
+                );
+                assert_eq!(
+                    a.meet(&b.meet(c)),
+                    a.meet(b).meet(c),
+                    "meet associativity on {} {} {}",
+                    a,
+                    b,
+                    c
+                );
+                // least upper bound and greatest lower bound
+                if *a <= *c && *b <= *c {
+                    assert!(a.join(b) <= *c);
+                }
+                if *c <= *a && *c <= *b {
+                    assert!(*c <= a.meet(b));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn type_lattice_laws() {
+    check_laws(&[
+        TypeLattice::Top,
+        TypeLattice::String,
+        TypeLattice::Integer,
+        TypeLattice::Bool,
+        TypeLattice::Bot,
+    ]);
+}
+
+#[test]
+fn strlen_lattice_laws() {
+    let x = || LinearExpr::from("x".to_string());
+    check_laws(&[
+        StrLenLat::Top,
+        StrLenLat::from(0),
+        StrLenLat::from(3),
+        StrLenLat::from(x()),
+        StrLenLat::from(x().checked_sub(LinearExpr::from(3)).unwrap()),
+        StrLenLat::from(LinearExpr::from("y".to_string()).checked_add(x()).unwrap()),
+        StrLenLat::Bot,
+    ]);
+}
+
+#[test]
+fn join_and_meet() {
+    assert_eq!(
+        TypeLattice::String.join(&TypeLattice::Integer),
+        TypeLattice::Top
+    );
+    assert_eq!(
+        TypeLattice::String.meet(&TypeLattice::Integer),
+        TypeLattice::Bot
+    );
+    assert_eq!(
+        StrLenLat::from(3).join(&StrLenLat::from(3)),
+        StrLenLat::from(3)
+    );
+    assert!(StrLenLat::from(3).join(&StrLenLat::from(4)).is_top());
+    assert!(StrLenLat::from(3).meet(&StrLenLat::from(4)).is_bot());
+}
@@ -0,0 +1,222 @@
+use absynthe::concrete::StrVal;
+use absynthe::environment::Environment;
+use absynthe::interpreter::{EvalError, Evaluable};
+use absynthe::regex::Regex;
+use absynthe::syguslang::{Expr, Func, Term};
+use absynthe::types::TypeLattice;
+use std::rc::Rc;
+
+// Expected values follow the SMT-LIB theory of strings
+// (http://smtlib.cs.uiowa.edu/theories-UnicodeStrings.shtml); positions and
+// lengths count characters.
+
+type E = Term<StrVal, TypeLattice>;
+
+fn s(s: &str) -> E {
+    Rc::new(Expr::Const(StrVal::from(s.to_string())))
+}
+
+fn i(i: i32) -> E {
+    Rc::new(Expr::Const(StrVal::from(i)))
+}
+
+fn check(cases: Vec<(Func<StrVal, TypeLattice>, StrVal)>) {
+    for (func, expected) in cases {
+        let expr = Expr::Call(func);
+        assert_eq!(expr.eval(&Environment::new()), Ok(expected), "{}", expr);
+    }
+}
+
+fn str_val(s: &str) -> StrVal {
+    StrVal::from(s.to_string())
+}
+
+#[test]
+fn at() {
+    check(vec![
+        (Func::At(s("abc"), i(0)), str_val("a")),
+        (Func::At(s("abc"), i(2)), str_val("c")),
+        (Func::At(s("abc"), i(3)), str_val("")),
+        (Func::At(s("abc"), i(-1)), str_val("")),
+        (Func::At(s(""), i(0)), str_val("")),
+        (Func::At(s("héllo"), i(1)), str_val("é")),
+    ]);
+}
+
+#[test]
+fn substr() {
+    check(vec![
+        (Func::Substr(s("abcdef"), i(1), i(3)), str_val("bcd")),
+        (Func::Substr(s("abcdef"), i(4), i(10)), str_val("ef")),
+        (Func::Substr(s("abcdef"), i(0), i(0)), str_val("")),
+        (Func::Substr(s("abcdef"), i(2), i(-1)), str_val("")),
+        (Func::Substr(s("abcdef"), i(-1), i(2)), str_val("")),
+        (Func::Substr(s("abcdef"), i(6), i(1)), str_val("")),
+        (Func::Substr(s("héllo"), i(1), i(2)), str_val("él")),
+    ]);
+}
+
+#[test]
+fn indexof() {
+    check(vec![
+        (Func::IndexOf(s("abcabc"), s("c"), i(0)), StrVal::from(2)),
+        (Func::IndexOf(s("abcabc"), s("c"), i(3)), StrVal::from(5)),
+        (Func::IndexOf(s("abcabc"), s("d"), i(0)), StrVal::from(-1)),
+        (Func::IndexOf(s("abc"), s("a"), i(4)), StrVal::from(-1)),
+        (Func::IndexOf(s("abc"), s("a"), i(-1)), StrVal::from(-1)),
+        (Func::IndexOf(s("abc"), s(""), i(1)), StrVal::from(1)),
+        (Func::IndexOf(s("abc"), s(""), i(3)), StrVal::from(3)),
+        (Func::IndexOf(s("abc"), s(""), i(4)), StrVal::from(-1)),
+        (Func::IndexOf(s("héllo"), s("l"), i(0)), StrVal::from(2)),
+    ]);
+}
+
+#[test]
+fn replace() {
+    check(vec![
+        (Func::Replace(s("a-b-c"), s("-"), s("+")), str_val("a+b-c")),
+        (Func::Replace(s("abc"), s("d"), s("x")), str_val("abc")),
+        (Func::Replace(s("abc"), s(""), s("x")), str_val("xabc")),
+        (Func::Replace(s("abc"), s("abc"), s("")), str_val("")),
+    ]);
+}
+
+#[test]
+fn conversions() {
+    check(vec![
+        (Func::ToStr(i(42)), str_val("42")),
+        (Func::ToStr(i(0)), str_val("0")),
+        (Func::ToStr(i(-3)), str_val("")),
+        (Func::ToInt(s("42")), StrVal::from(42)),
+        (Func::ToInt(s("007")), StrVal::from(7)),
+        (Func::ToInt(s("")), StrVal::from(-1)),
+        (Func::ToInt(s("-3")), StrVal::from(-1)),
+        (Func::ToInt(s("4a")), StrVal::from(-1)),
+        (Func::ToInt(s("+4")), StrVal::from(-1)),
+    ]);
+}
+
+#[test]
+fn predicates() {
+    check(vec![
+        (Func::PrefixOf(s("ab"), s("abc")), StrVal::Bool(true)),
+        (Func::PrefixOf(s("abc"), s("ab")), StrVal::Bool(false)),
+        (Func::PrefixOf(s(""), s("abc")), StrVal::Bool(true)),
+        (Func::SuffixOf(s("bc"), s("abc")), StrVal::Bool(true)),
+        (Func::SuffixOf(s("abc"), s("bc")), StrVal::Bool(false)),
+        (Func::Contains(s("abc"), s("b")), StrVal::Bool(true)),
+        (Func::Contains(s("b"), s("abc")), StrVal::Bool(false)),
+        (Func::Contains(s("abc"), s("")), StrVal::Bool(true)),
+    ]);
+}
+
+#[test]
+fn length_and_append() {
+    check(vec![
+        (Func::Len(s("")), StrVal::from(0)),
+        (Func::Len(s("héllo")), StrVal::from(5)),
+        (Func::Append(s("ab"), s("")), str_val("ab")),
+        (Func::Append(s("ab"), s("cd")), str_val("abcd")),
+    ]);
+}
+
+#[test]
+fn replace_all() {
+    check(vec![
+        (
+            Func::ReplaceAll(s("a-b-c"), s("-"), s("+")),
+            str_val("a+b+c"),
+        ),
+        (Func::ReplaceAll(s("aaa"), s("aa"), s("b")), str_val("ba")),
+        (Func::ReplaceAll(s("abc"), s(""), s("x")), str_val("abc")),
+    ]);
+    // the replacement is checked even when there is nothing to replace
+    let ill_typed = Expr::Call(Func::ReplaceAll(s("abc"), s(""), i(5)));
+    assert!(matches!(
+        ill_typed.eval(&Environment::new()),
+        Err(EvalError::TypeMismatch {
+            op: "replace_all",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn lexicographic_order() {
+    check(vec![
+        (Func::StrLt(s("abc"), s("abd")), StrVal::Bool(true)),
+        (Func::StrLt(s("ab"), s("abc")), StrVal::Bool(true)),
+        (Func::StrLt(s("abc"), s("abc")), StrVal::Bool(false)),
+        (Func::StrLt(s("B"), s("a")), StrVal::Bool(true)),
+        (Func::StrLe(s("abc"), s("abc")), StrVal::Bool(true)),
+        (Func::StrLe(s("b"), s("abc")), StrVal::Bool(false)),
+        (Func::StrLe(s(""), s("")), StrVal::Bool(true)),
+    ]);
+}
+
+#[test]
+fn codes_and_digits() {
+    check(vec![
+        (Func::IsDigit(s("7")), StrVal::Bool(true)),
+        (Func::IsDigit(s("77")), StrVal::Bool(false)),
+        (Func::IsDigit(s("")), StrVal::Bool(false)),
+        (Func::IsDigit(s("x")), StrVal::Bool(false)),
+        (Func::ToCode(s("a")), StrVal::from(97)),
+        (Func::ToCode(s("é")), StrVal::from(233)),
+        (Func::ToCode(s("ab")), StrVal::from(-1)),
+        (Func::ToCode(s("")), StrVal::from(-1)),
+        (Func::FromCode(i(97)), str_val("a")),
+        (Func::FromCode(i(-1)), str_val("")),
+        (Func::FromCode(i(0x30000)), str_val("")),
+    ]);
+}
+
+#[test]
+fn int_comparisons() {
+    check(vec![
+        (Func::Lt(i(1), i(2)), StrVal::Bool(true)),
+        (Func::Lt(i(2), i(2)), StrVal::Bool(false)),
+        (Func::Le(i(2), i(2)), StrVal::Bool(true)),
+        (Func::Le(i(3), i(-2)), StrVal::Bool(false)),
+        (Func::Eq(i(-2), i(-2)), StrVal::Bool(true)),
+        (Func::Eq(i(0), i(1)), StrVal::Bool(false)),
+    ]);
+}
+
+#[test]
+fn in_re() {
+    let digits = Regex::Plus(Box::new(Regex::Range('0', '9')));
+    let word = Regex::Concat(vec![
+        Regex::Range('A', 'Z'),
+        Regex::Star(Box::new(Regex::Range('a', 'z'))),
+    ]);
+    check(vec![
+        (Func::InRe(s("123"), digits.clone()), StrVal::Bool(true)),
+        (Func::InRe(s(""), digits.clone()), StrVal::Bool(false)),
+        (Func::InRe(s("12a"), digits), StrVal::Bool(false)),
+        (Func::InRe(s("Hello"), word.clone()), StrVal::Bool(true)),
+        (Func::InRe(s("hello"), word), StrVal::Bool(false)),
+        (
+            Func::InRe(s(""), Regex::Str(String::new())),
+            StrVal::Bool(true),
+        ),
+        (Func::InRe(s("a"), Regex::None), StrVal::Bool(false)),
+    ]);
+}
+
+#[test]
+fn connectives_and_equality() {
+    let b = |b: bool| Rc::new(Expr::Const(StrVal::Bool(b)));
+    check(vec![
+        (Func::And(b(true), b(false)), StrVal::Bool(false)),
+        (Func::And(b(true), b(true)), StrVal::Bool(true)),
+        (Func::Or(b(false), b(true)), StrVal::Bool(true)),
+        (Func::Or(b(false), b(false)), StrVal::Bool(false)),
+        (Func::Not(b(false)), StrVal::Bool(true)),
+        (Func::Eq(s("ab"), s("ab")), StrVal::Bool(true)),
+        (Func::Eq(s("ab"), s("a")), StrVal::Bool(false)),
+        (Func::Eq(b(false), b(false)), StrVal::Bool(true)),
+    ]);
+    let mixed = Expr::Call(Func::<StrVal, TypeLattice>::Eq(s("1"), i(1)));
+    assert!(mixed.eval(&Environment::new()).is_err());
+}
@@ -0,0 +1,68 @@
+use absynthe::sygus::Problem;
+use absynthe::synthesizer::{Context, Engine, SearchConfig, Synthesizer};
+use std::fs;
+
+#[test]
+fn parse_suite() {
+    let mut paths: Vec<_> = fs::read_dir("sygus-strings")
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let problem = Problem::from_file(&path).unwrap();
+        assert_eq!(problem.logic, "SLIA", "{}", path.display());
+        assert!(!problem.consts().is_empty(), "{}", path.display());
+        assert_eq!(
+            problem.examples().len(),
+            problem.constraints.len(),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn bikes_sl() {
+    let problem = Problem::from_file("sygus-strings/bikes.sl").unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+    let prog = &result.solutions()[0];
+
+    assert_eq!("(substr arg0 0 (- (len arg0) 3))", format!("{}", prog));
+}
+
+// the start symbol only produces appends, although the other String
+// nonterminal would allow the input itself
+const TWO_STRINGS: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String ((str.++ ntString ntString)))
+         (ntString String (name ""))))
+    (declare-var name String)
+    (constraint (= (f "ab") "ab"))
+    (check-synth)
+"#;
+
+#[test]
+fn nonterminals_of_one_sort() {
+    let problem = Problem::parse(TWO_STRINGS).unwrap();
+    let env = problem.env();
+
+    for engine in [Engine::TopDown, Engine::BottomUp] {
+        let mut ctx = Context::with_grammar(problem.grammar());
+        ctx.set_config(SearchConfig::new().engine(engine));
+        let result =
+            Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+        assert_eq!(
+            format!("{}", result.solutions()[0]),
+            "(append arg0 \"\")",
+            "{:?}",
+            engine
+        );
+    }
+}
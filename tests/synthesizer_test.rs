@@ -0,0 +1,493 @@
+use absynthe::concrete::StrVal;
+use absynthe::cost::{self, Weights};
+use absynthe::environment::Environment;
+use absynthe::grammar::Grammar;
+use absynthe::spec::Examples;
+use absynthe::sygus::Problem;
+use absynthe::syguslang::{Expr, Term};
+use absynthe::synthesizer::{Context, Engine, Outcome, SearchConfig, Synthesizer};
+use absynthe::types::{TypeLattice, TypeValues};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const UNSOLVABLE: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String (ntString))
+         (ntString String (name "a"))))
+    (declare-var name String)
+    (constraint (= (f "x") "b"))
+    (check-synth)
+"#;
+
+const CASE_SPLIT: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String (ntString))
+         (ntString String (name "Dr. "
+                           (str.++ ntString ntString)
+                           (ite ntBool ntString ntString)))
+         (ntBool Bool ((str.prefixof ntString ntString)))))
+    (declare-var name String)
+    (constraint (= (f "Dr. Who") "Dr. Who"))
+    (constraint (= (f "House") "Dr. House"))
+    (constraint (= (f "Dr. No") "Dr. No"))
+    (constraint (= (f "Strange") "Dr. Strange"))
+    (check-synth)
+"#;
+
+const DOT: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String (ntString))
+         (ntString String ("x" "" "." name (str.++ ntString ntString)))))
+    (declare-var name String)
+    (constraint (= (f "x") "x."))
+    (check-synth)
+"#;
+
+const SECOND: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String (ntString))
+         (ntString String (name
+                           (str.at ntString ntInt)
+                           (str.substr ntString ntInt ntInt)))
+         (ntInt Int (1))))
+    (declare-var name String)
+    (constraint (= (f "ab") "b"))
+    (constraint (= (f "xyz") "y"))
+    (check-synth)
+"#;
+
+const TITLED: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) Bool
+        ((Start Bool (ntBool))
+         (ntBool Bool (true false
+                       (and ntBool ntBool)
+                       (or ntBool ntBool)
+                       (not ntBool)
+                       (str.prefixof ntString ntString)
+                       (= ntString ntString)))
+         (ntString String (name "Dr. " "Mr. "))))
+    (declare-var name String)
+    (constraint (= (f "Dr. Who") false))
+    (constraint (= (f "Mr. Bean") false))
+    (constraint (= (f "House") true))
+    (constraint (= (f "Mr. ") false))
+    (check-synth)
+"#;
+
+// the start symbol fills six holes at once, in 16^6 ways
+const WIDE: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String ((str.++ ntString
+                         (str.++ ntString
+                          (str.++ ntString
+                           (str.++ ntString
+                            (str.++ ntString ntString)))))))
+         (ntString String (name "a" "b" "c" "d" "e" "f" "g"
+                           "h" "i" "j" "k" "l" "m" "n" "o"))))
+    (declare-var name String)
+    (constraint (= (f "x") "?"))
+    (check-synth)
+"#;
+
+// the first character is taken with str.at under one nonterminal and with
+// str.substr under the other, and each is the only way its nonterminal has
+const FIRST_TWICE: &str = r#"
+    (set-logic SLIA)
+    (synth-fun f ((name String)) String
+        ((Start String ((str.++ ntAt ntSubstr)))
+         (ntAt String ("" (str.at name 0)))
+         (ntSubstr String ("" (str.substr name 0 1)))))
+    (declare-var name String)
+    (constraint (= (f "ab") "aa"))
+    (constraint (= (f "xyz") "xx"))
+    (check-synth)
+"#;
+
+#[test]
+fn exhausted() {
+    let problem = Problem::parse(UNSOLVABLE).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert!(!result.is_solved());
+    assert!(result.solutions().is_empty());
+    match result.outcome {
+        Outcome::Exhausted { max_size, explored } => {
+            assert_eq!(max_size, 15);
+            assert_eq!(explored, 1);
+        }
+        _ => panic!("expected the search to be exhausted"),
+    }
+    assert_eq!(result.stats.explored, 1);
+    assert_eq!(result.stats.generated, 2);
+    assert_eq!(result.stats.tested, 2);
+}
+
+#[test]
+fn node_limit() {
+    let problem = Problem::from_file("sygus-strings/dr-name.sl").unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(SearchConfig::new().max_nodes(10));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    match result.outcome {
+        Outcome::NodeLimit { explored } => assert_eq!(explored, 10),
+        _ => panic!("expected the node limit to stop the search"),
+    }
+}
+
+#[test]
+fn timeout() {
+    let problem = Problem::from_file("sygus-strings/dr-name.sl").unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    let started = Instant::now();
+    ctx.set_config(SearchConfig::new().timeout(Duration::from_millis(200)));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert!(matches!(result.outcome, Outcome::Timeout));
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+// one expansion is too large to build, or to check before the deadline
+#[test]
+fn timeout_within_expansion() {
+    let problem = Problem::parse(WIDE).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    let started = Instant::now();
+    ctx.set_config(SearchConfig::new().timeout(Duration::from_millis(200)));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert!(matches!(result.outcome, Outcome::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn cancel() {
+    let problem = Problem::from_file("sygus-strings/dr-name.sl").unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    let flag = Arc::new(AtomicBool::new(false));
+    ctx.set_config(SearchConfig::new().cancel(flag.clone()));
+
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        flag.store(true, Ordering::Relaxed);
+    });
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+    canceller.join().unwrap();
+
+    assert!(matches!(result.outcome, Outcome::Cancelled));
+    assert!(result.stats.explored > 0);
+}
+
+#[test]
+fn observational_equivalence() {
+    let problem = Problem::from_file("sygus-strings/bikes.sl").unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(SearchConfig::new().observational_equivalence(true));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert_eq!(
+        "(substr arg0 0 (- (len arg0) 3))",
+        format!("{}", result.solutions()[0])
+    );
+    assert!(result.stats.pruned > 0);
+}
+
+// equivalent closed terms derived from different nonterminals cannot
+// stand in for one another
+#[test]
+fn observational_equivalence_by_nonterminal() {
+    let problem = Problem::parse(FIRST_TWICE).unwrap();
+    let env = problem.env();
+
+    for engine in [Engine::TopDown, Engine::BottomUp] {
+        let mut ctx = Context::with_grammar(problem.grammar());
+        ctx.set_config(
+            SearchConfig::new()
+                .engine(engine)
+                .observational_equivalence(true),
+        );
+        let result =
+            Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+        assert_eq!(
+            format!("{}", result.solutions()[0]),
+            "(append (at arg0 0) (substr arg0 0 1))",
+            "{:?}",
+            engine
+        );
+    }
+}
+
+#[test]
+fn conditionals() {
+    let problem = Problem::parse(CASE_SPLIT).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(SearchConfig::new().conditionals(true));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert_eq!(
+        format!("{}", result.solutions()[0]),
+        "(if (prefixof \"Dr. \" arg0) arg0 (append \"Dr. \" arg0))"
+    );
+    assert!(problem
+        .examples()
+        .passes(&Expr::from(result.solutions()[0].clone())));
+}
+
+#[test]
+fn n_solutions() {
+    let problem = Problem::parse(DOT).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+
+    let result = Synthesizer::synthesize_n(
+        &mut ctx,
+        problem.target(),
+        &env,
+        problem.examples().into(),
+        3,
+        cost::generality,
+    );
+
+    let progs: Vec<_> = result
+        .solutions()
+        .iter()
+        .map(|p| problem.to_sexpr(&Expr::from(p.clone())).to_string())
+        .collect();
+    assert_eq!(
+        progs,
+        [
+            "(str.++ name \".\")",
+            "(str.++ \"x\" \".\")",
+            "(str.++ (str.++ name \".\") \"\")"
+        ]
+    );
+}
+
+#[test]
+fn cost_model() {
+    let problem = Problem::parse(SECOND).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+    assert_eq!(format!("{}", result.solutions()[0]), "(at arg0 1)");
+
+    ctx.set_cost_model(Weights::new().func("at", 10));
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+    assert_eq!(format!("{}", result.solutions()[0]), "(substr arg0 1 1)");
+}
+
+#[test]
+fn deterministic() {
+    let problem = Problem::from_file("sygus-strings/name-combine.sl").unwrap();
+    let env = problem.env();
+
+    let progs: Vec<String> = (0..3)
+        .map(|_| {
+            let mut ctx = Context::new(&problem.consts(), &env);
+            let result = Synthesizer::synthesize(
+                &mut ctx,
+                problem.target(),
+                &env,
+                problem.examples().into(),
+            );
+            format!("{}", result.solutions()[0])
+        })
+        .collect();
+
+    assert_eq!(progs, vec!["(append (append arg0 \" \") arg1)"; 3]);
+}
+
+// the extended operators are only in the grammar when asked for
+#[test]
+fn full_grammar() {
+    let mut env = Environment::new();
+    env.put("arg0".to_string(), TypeValues::Abs(TypeLattice::String));
+    let leaves: Vec<Term<TypeValues, TypeLattice>> = vec![Rc::new(Expr::Var("arg0".to_string()))];
+    let mut examples = Examples::new();
+    for (input, digit) in [("5", true), ("a", false), ("0", true), (" ", false)] {
+        examples.add(vec![StrVal::from(input.to_string())], StrVal::Bool(digit));
+    }
+
+    let core = Grammar::slia(&leaves, &env);
+    assert!(core.leaves().iter().all(|l| matches!(**l, Expr::Var(_))));
+    let mut ctx = Context::with_grammar(core);
+    ctx.set_config(SearchConfig::new().max_size(2));
+    let result =
+        Synthesizer::synthesize(&mut ctx, TypeLattice::Bool, &env, examples.clone().into());
+    assert!(!result.is_solved());
+
+    let mut ctx = Context::with_grammar(Grammar::slia_full(&leaves, &env));
+    let result = Synthesizer::synthesize(&mut ctx, TypeLattice::Bool, &env, examples.into());
+    assert_eq!(format!("{}", result.solutions()[0]), "(is_digit arg0)");
+}
+
+#[test]
+fn bool_connectives() {
+    let problem = Problem::parse(TITLED).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert_eq!(
+        problem.define_fun(&Expr::from(result.solutions()[0].clone())),
+        "(define-fun f ((name String)) Bool \
+         (not (or (str.prefixof \"Dr. \" name) (str.prefixof \"Mr. \" name))))"
+    );
+}
+
+#[test]
+fn bottom_up() {
+    let problem = Problem::from_file("sygus-strings/bikes.sl").unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(
+        SearchConfig::new()
+            .engine(Engine::BottomUp)
+            .observational_equivalence(true),
+    );
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert_eq!(
+        "(substr arg0 0 (- (len arg0) 3))",
+        format!("{}", result.solutions()[0])
+    );
+    assert!(result.stats.pruned > 0);
+
+    // programs are built smallest first, so the engines agree on the
+    // smallest solution
+    let problem = Problem::parse(TITLED).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(SearchConfig::new().engine(Engine::BottomUp));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert_eq!(
+        format!("{}", result.solutions()[0]),
+        "(not (or (prefixof \"Dr. \" arg0) (prefixof \"Mr. \" arg0)))"
+    );
+}
+
+#[test]
+fn bottom_up_exhausted() {
+    let problem = Problem::parse(DOT).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(SearchConfig::new().engine(Engine::BottomUp).max_size(1));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    // only the leaves fit, an append has size 2
+    match result.outcome {
+        Outcome::Exhausted { max_size, explored } => {
+            assert_eq!(max_size, 1);
+            assert_eq!(explored, 4);
+        }
+        _ => panic!("expected the search to be exhausted"),
+    }
+
+    // without productions the grammar does not describe the search space
+    let problem = Problem::parse(UNSOLVABLE).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+    ctx.set_config(SearchConfig::new().engine(Engine::BottomUp));
+
+    let result =
+        Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+
+    assert!(matches!(result.outcome, Outcome::Unsupported(_)));
+    assert_eq!(result.stats.explored, 0);
+}
+
+#[test]
+fn engines_share_context() {
+    let problem = Problem::parse(SECOND).unwrap();
+    let env = problem.env();
+    let mut ctx = Context::with_grammar(problem.grammar());
+
+    let mut solve = |engine| {
+        ctx.set_config(SearchConfig::new().engine(engine));
+        let result =
+            Synthesizer::synthesize(&mut ctx, problem.target(), &env, problem.examples().into());
+        (format!("{}", result.solutions()[0]), result.stats.explored)
+    };
+    let bottom_up = solve(Engine::BottomUp);
+    let top_down = solve(Engine::TopDown);
+
+    assert_eq!(bottom_up.0, "(at arg0 1)");
+    assert_eq!(top_down.0, "(at arg0 1)");
+    assert_eq!(solve(Engine::BottomUp), bottom_up);
+    assert_eq!(solve(Engine::TopDown), top_down);
+}
+
+// without observational equivalence the levels do not depend on the
+// examples, and a later search on the context picks up where they end
+#[test]
+fn bottom_up_reuses_levels() {
+    let solved = Problem::parse(FIRST_TWICE).unwrap();
+    // no program of the grammar gives "b"
+    let unsolvable = Problem::parse(&FIRST_TWICE.replace("\"aa\"", "\"b\"")).unwrap();
+    let env = solved.env();
+    let config = || SearchConfig::new().engine(Engine::BottomUp);
+    let explored = |ctx: &mut Context<_, _>| {
+        let result =
+            Synthesizer::synthesize(ctx, unsolvable.target(), &env, unsolvable.examples().into());
+        match result.outcome {
+            Outcome::Exhausted { explored, .. } => explored,
+            _ => panic!("expected the search to be exhausted"),
+        }
+    };
+
+    let mut fresh = Context::with_grammar(solved.grammar());
+    fresh.set_config(config());
+    let expected = explored(&mut fresh);
+
+    let mut ctx = Context::with_grammar(solved.grammar());
+    ctx.set_config(config());
+    let result = Synthesizer::synthesize(&mut ctx, solved.target(), &env, solved.examples().into());
+    assert_eq!(
+        format!("{}", result.solutions()[0]),
+        "(append (at arg0 0) (substr arg0 0 1))"
+    );
+    assert_eq!(explored(&mut ctx), expected);
+    assert_eq!(explored(&mut ctx), expected);
+}